@@ -6,7 +6,13 @@ pub use self::opts::*;
 pub mod reporter;
 pub mod subcommand;
 
+mod baseline;
+mod cache;
+mod config;
 mod encoding;
+mod fixer;
+mod ordered;
+mod profile;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,276 @@
+//! This module defines `Baseline`, which records findings accepted so far and omits them on later runs.
+
+use crate::core::{
+    language::Queryable,
+    matcher::MatchedItem,
+    ruleset::{compiled::CompiledRule, Rule},
+    target::Target,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+const BASELINE_VERSION: &str = "1";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BaselineFile {
+    version: String,
+    findings: Vec<BaselineEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct BaselineEntry {
+    id: String,
+    file: String,
+    fingerprint: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Baseline {
+    /// number of findings per fingerprint which were loaded from the baseline file
+    known: HashMap<String, usize>,
+    output: Option<PathBuf>,
+    /// directory which paths of files in the baseline are relative to
+    root: PathBuf,
+
+    recorded: Vec<BaselineEntry>,
+    omitted_items: usize,
+}
+
+impl Baseline {
+    /// `new` loads the baseline from `input`. Paths of files are recorded relative to `root`
+    /// so that the baseline does not depend on the directory where shisho runs.
+    pub fn new(input: Option<&Path>, output: Option<PathBuf>, root: &Path) -> Result<Self> {
+        let root = root.canonicalize().map_err(|e| {
+            anyhow!(
+                "failed to resolve baseline root {}: {}",
+                root.to_string_lossy(),
+                e
+            )
+        })?;
+        let mut known = HashMap::new();
+        if let Some(input) = input {
+            let f = std::fs::File::open(input).map_err(|e| {
+                anyhow!(
+                    "failed to open baseline file {}: {}",
+                    input.to_string_lossy(),
+                    e
+                )
+            })?;
+            let baseline: BaselineFile = serde_json::from_reader(f).map_err(|e| {
+                anyhow!(
+                    "failed to load baseline file {}: {}",
+                    input.to_string_lossy(),
+                    e
+                )
+            })?;
+            if baseline.version != BASELINE_VERSION {
+                return Err(anyhow!(
+                    "unsupported baseline version: {}",
+                    baseline.version
+                ));
+            }
+            for entry in baseline.findings {
+                *known.entry(entry.fingerprint).or_insert(0) += 1;
+            }
+        }
+
+        Ok(Baseline {
+            known,
+            output,
+            root,
+            ..Default::default()
+        })
+    }
+
+    /// `filter` records the given items for a new baseline and returns the ones absent from the loaded baseline.
+    /// Identical findings in a file are distinguished only by their number.
+    pub fn filter<'a, 'tree, T: Queryable>(
+        &mut self,
+        target: &Target,
+        items: Vec<(&'a CompiledRule<T>, MatchedItem<'tree>)>,
+    ) -> Result<Vec<(&'a CompiledRule<T>, MatchedItem<'tree>)>> {
+        let mut filtered = vec![];
+        let file = target.relative_path_from(&self.root);
+        for (rule, mitem) in items {
+            let fingerprint = fingerprint(&file, &rule.rule, &mitem)?;
+            if self.output.is_some() {
+                self.recorded.push(BaselineEntry {
+                    id: rule.rule.id.clone(),
+                    file: file.clone(),
+                    fingerprint: fingerprint.clone(),
+                });
+            }
+
+            match self.known.get_mut(&fingerprint) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    self.omitted_items += 1;
+                }
+                _ => filtered.push((rule, mitem)),
+            }
+        }
+        Ok(filtered)
+    }
+
+    /// `write` saves the recorded findings if an output path was given.
+    pub fn write(&mut self) -> Result<()> {
+        let output = match self.output {
+            Some(ref output) => output,
+            None => return Ok(()),
+        };
+
+        let mut findings = std::mem::take(&mut self.recorded);
+        findings.sort();
+        let f = std::fs::File::create(output).map_err(|e| {
+            anyhow!(
+                "failed to create baseline file {}: {}",
+                output.to_string_lossy(),
+                e
+            )
+        })?;
+        serde_json::to_writer_pretty(
+            f,
+            &BaselineFile {
+                version: BASELINE_VERSION.into(),
+                findings,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn print_summary(&self) {
+        if self.omitted_items > 0 {
+            eprintln!(
+                "{} finding(s) in the baseline were omitted",
+                self.omitted_items
+            );
+        }
+    }
+}
+
+/// `fingerprint` identifies a finding by its rule, file, matched text and captured values.
+/// Whitespaces are normalized so that the fingerprint is stable against reformatting and moving lines.
+pub fn fingerprint(file: &str, rule: &Rule, item: &MatchedItem) -> Result<String> {
+    let mut captures = item
+        .captures
+        .iter()
+        .map(|(mid, citem)| (mid.0.as_str(), normalize(citem.as_str())))
+        .collect::<Vec<(&str, String)>>();
+    captures.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(rule.id.as_bytes());
+    hasher.update([0]);
+    hasher.update(file.as_bytes());
+    hasher.update([0]);
+    hasher.update(normalize(item.area.as_str()?).as_bytes());
+    for (mid, value) in captures {
+        hasher.update([0]);
+        hasher.update(mid.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn normalize(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        language::HCL,
+        ruleset::{Language, RawPatternWithConstraints},
+        tree::{NormalizedTree, Tree},
+    };
+    use std::convert::TryFrom;
+
+    fn rule() -> Rule {
+        Rule::new(
+            "test".into(),
+            Language::HCL,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "encrypted = :[X]".into(),
+                ..Default::default()
+            }],
+            vec![],
+            vec![],
+        )
+    }
+
+    fn fingerprints(rule: &Rule, body: &str) -> Vec<String> {
+        let tree = Tree::<HCL>::try_from(body).unwrap();
+        let ptree = NormalizedTree::from(&tree);
+        let ptree = ptree.as_ref_treeview();
+        rule.find::<HCL>(&ptree)
+            .unwrap()
+            .into_iter()
+            .map(|mitem| fingerprint("main.tf", rule, &mitem).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_stable_fingerprint() {
+        let rule = rule();
+
+        let original = fingerprints(&rule, "resource \"a\" \"b\" {\n  encrypted = false\n}\n");
+        let moved = fingerprints(
+            &rule,
+            "\n\nresource \"a\" \"b\" {\n  size = 40\n  encrypted    =    false\n}\n",
+        );
+        let changed = fingerprints(&rule, "resource \"a\" \"b\" {\n  encrypted = var.x\n}\n");
+        assert_eq!(original.len(), 1);
+        assert_eq!(original, moved);
+        assert_ne!(original, changed);
+    }
+
+    #[test]
+    fn test_path_from_root() {
+        let root =
+            std::env::temp_dir().join(format!("shisho-test-baseline-{}", std::process::id()));
+        let dir = root.join("modules");
+        std::fs::create_dir_all(&dir).unwrap();
+        let body = "resource \"a\" \"b\" {\n  encrypted = false\n}\n";
+        std::fs::write(dir.join("main.tf"), body).unwrap();
+
+        let rule = CompiledRule::<HCL>::new(rule()).unwrap();
+        let tree = Tree::<HCL>::try_from(body).unwrap();
+        let ptree = NormalizedTree::from(&tree);
+        let ptree = ptree.as_ref_treeview();
+        let target = Target {
+            path: Some(dir.join("main.tf")),
+            body: body.into(),
+            ..Default::default()
+        };
+
+        // the recorded path does not depend on the current directory
+        let file = Path::new("modules")
+            .join("main.tf")
+            .to_string_lossy()
+            .to_string();
+        let mut baseline = Baseline::new(None, Some(root.join("baseline.json")), &root).unwrap();
+        let items = rule
+            .find(&ptree)
+            .unwrap()
+            .into_iter()
+            .map(|mitem| (&rule, mitem))
+            .collect();
+        let items = baseline.filter(&target, items).unwrap();
+        assert_eq!(baseline.recorded.len(), 1);
+        assert_eq!(baseline.recorded[0].file, file);
+        assert_eq!(
+            baseline.recorded[0].fingerprint,
+            fingerprint(&file, &rule.rule, &items[0].1).unwrap()
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,460 @@
+//! This module defines `ResultCache`, which stores findings per file content and rule so that unchanged files are not analyzed again.
+
+use crate::core::{
+    matcher::{CaptureItem, CaptureMap, MatchedItem},
+    node::{ConsecutiveNodes, Node, SyntaxError},
+    query::MetavariableId,
+    ruleset::Language,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// `CACHEDIR_TAG` marks a directory as a cache so that backup tools skip it and `prune` can tell it from other directories.
+/// See https://bford.info/cachedir/ for details.
+const CACHEDIR_TAG: (&str, &str) = (
+    "CACHEDIR.TAG",
+    "Signature: 8a477f597d28d172789f06886806bc55\n# This file is a cache directory tag created by shisho.\n",
+);
+
+/// `NodePath` locates a node by the indices of children from the root of a tree.
+type NodePath = Vec<usize>;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    /// findings per digest of a rule
+    findings: HashMap<String, Vec<CachedItem>>,
+    /// facts about the file which are known after parsing it
+    parsed: Option<ParseOutcome>,
+}
+
+/// `ParseOutcome` holds what is reported on a file regardless of rules, which is known only after parsing the file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseOutcome {
+    /// rows of suppression comments without reasons
+    pub invalid_suppressions: Vec<usize>,
+    /// the first part of the file which could not be parsed
+    pub syntax_error: Option<SyntaxError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedItem {
+    area: Vec<NodePath>,
+    captures: Vec<(String, CachedCapture)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum CachedCapture {
+    Empty,
+    Literal(String),
+    Nodes(Vec<NodePath>),
+}
+
+/// `ResultCache` is a directory which stores findings per (file content, rule, shisho version).
+/// Entries of each version are stored in a sub-directory named after it.
+#[derive(Debug)]
+pub struct ResultCache {
+    root: PathBuf,
+    dir: PathBuf,
+
+    /// keys of entries used in this run
+    used: Mutex<HashSet<String>>,
+    /// number of pairs of a rule and a target which were answered from the cache
+    hits: AtomicUsize,
+    /// number of pairs of a rule and a target which were analyzed
+    misses: AtomicUsize,
+    /// counter to name temporary files uniquely
+    writes: AtomicUsize,
+}
+
+impl ResultCache {
+    pub fn new(root: &Path) -> Result<Self> {
+        let dir = root.join(env!("CARGO_PKG_VERSION"));
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            anyhow!(
+                "failed to create cache directory {}: {}",
+                dir.to_string_lossy(),
+                e
+            )
+        })?;
+        let tag = root.join(CACHEDIR_TAG.0);
+        if !tag.exists() {
+            std::fs::write(tag, CACHEDIR_TAG.1)?;
+        }
+
+        Ok(ResultCache {
+            root: root.to_path_buf(),
+            dir,
+            used: Mutex::new(HashSet::new()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            writes: AtomicUsize::new(0),
+        })
+    }
+
+    /// `load` returns cached results for `body` in `language`. Missing or broken entries are regarded as empty.
+    pub fn load(&self, language: Language, body: &str) -> CachedResults<'_> {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{:?}", language).as_bytes());
+        hasher.update([0]);
+        hasher.update(body.as_bytes());
+        let key = format!("{:x}", hasher.finalize());
+
+        self.used.lock().unwrap().insert(key.clone());
+        let file = std::fs::read(self.path_of(&key))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        CachedResults {
+            cache: self,
+            key,
+            file,
+            dirty: false,
+        }
+    }
+
+    fn path_of(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{}.json", &key[2..]))
+    }
+
+    fn store(&self, key: &str, file: &CacheFile) -> Result<()> {
+        let path = self.path_of(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // the entry is replaced atomically since workers may store the same content at once
+        let tmp = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            self.writes.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&tmp, serde_json::to_vec(file)?)?;
+        std::fs::rename(&tmp, &path).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            anyhow!(
+                "failed to write cache entry {}: {}",
+                path.to_string_lossy(),
+                e
+            )
+        })
+    }
+
+    /// `prune` removes entries which were not used in this run, including ones of the other versions of shisho.
+    /// It returns the number of removed entries.
+    pub fn prune(&self) -> Result<usize> {
+        // other directories must not be removed even if they were given by mistake
+        let tag = std::fs::read_to_string(self.root.join(CACHEDIR_TAG.0)).unwrap_or_default();
+        if tag != CACHEDIR_TAG.1 {
+            return Err(anyhow!(
+                "{} does not look like a cache directory of shisho; it was not pruned",
+                self.root.to_string_lossy()
+            ));
+        }
+
+        let used = self.used.lock().unwrap();
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            if path != self.dir {
+                removed += count_files(&path);
+                std::fs::remove_dir_all(&path)?;
+                continue;
+            }
+            for shard in std::fs::read_dir(&path)? {
+                let shard = shard?.path();
+                let prefix = match shard.file_name().and_then(|n| n.to_str()) {
+                    Some(prefix) if shard.is_dir() => prefix.to_string(),
+                    _ => continue,
+                };
+                for file in std::fs::read_dir(&shard)? {
+                    let file = file?.path();
+                    let key = file
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .map(|s| format!("{}{}", prefix, s));
+                    match key {
+                        Some(key) if used.contains(&key) => (),
+                        _ => {
+                            std::fs::remove_file(&file)?;
+                            removed += 1;
+                        }
+                    }
+                }
+                if std::fs::read_dir(&shard)?.next().is_none() {
+                    std::fs::remove_dir(&shard)?;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn print_summary(&self) {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let checked = hits + self.misses.load(Ordering::Relaxed);
+        eprintln!(
+            "{} of {} pair(s) of a rule and a file were answered from the cache",
+            hits, checked
+        );
+    }
+}
+
+fn count_files(dir: &Path) -> usize {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .count()
+}
+
+/// `CachedResults` holds cached findings on a file content, and records new ones to store them later.
+#[derive(Debug)]
+pub struct CachedResults<'c> {
+    cache: &'c ResultCache,
+    key: String,
+    file: CacheFile,
+    dirty: bool,
+}
+
+impl<'c> CachedResults<'c> {
+    /// `answer_without_parsing` returns the outcome of parsing the file if none of the rules with `digests` found anything in the file before.
+    /// In that case the file does not need to be parsed at all.
+    pub fn answer_without_parsing<'a>(
+        &self,
+        digests: impl Iterator<Item = &'a str>,
+    ) -> Option<&ParseOutcome> {
+        let parsed = self.file.parsed.as_ref()?;
+        let digests = digests.collect::<Vec<&str>>();
+        let answered = digests
+            .iter()
+            .all(|digest| match self.file.findings.get(*digest) {
+                Some(items) => items.is_empty(),
+                None => false,
+            });
+        if answered {
+            self.cache.hits.fetch_add(digests.len(), Ordering::Relaxed);
+            Some(parsed)
+        } else {
+            None
+        }
+    }
+
+    /// `findings` restores cached findings of the rule with `digest` on the tree of the file.
+    pub fn findings<'tree>(
+        &self,
+        digest: &str,
+        root: &'tree Node<'tree>,
+    ) -> Option<Vec<MatchedItem<'tree>>> {
+        let restored = self.file.findings.get(digest).and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.restore(root))
+                .collect::<Option<Vec<MatchedItem<'tree>>>>()
+        });
+        match restored {
+            Some(_) => self.cache.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.cache.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        restored
+    }
+
+    /// `record` saves findings of the rule with `digest`. Findings which cannot be located in the tree are not cached.
+    pub fn record(&mut self, digest: &str, items: &[MatchedItem], paths: &NodePaths) {
+        let items = items
+            .iter()
+            .map(|item| CachedItem::new(item, paths))
+            .collect::<Option<Vec<CachedItem>>>();
+        if let Some(items) = items {
+            self.file.findings.insert(digest.to_string(), items);
+            self.dirty = true;
+        }
+    }
+
+    pub fn record_parse_outcome(&mut self, outcome: ParseOutcome) {
+        if self.file.parsed.as_ref() != Some(&outcome) {
+            self.file.parsed = Some(outcome);
+            self.dirty = true;
+        }
+    }
+
+    /// `store` writes the results back to the cache if they were updated.
+    pub fn store(self) -> Result<()> {
+        if self.dirty {
+            self.cache.store(&self.key, &self.file)?;
+        }
+        Ok(())
+    }
+}
+
+/// `NodePaths` maps nodes of a tree to their paths from the root.
+#[derive(Debug)]
+pub struct NodePaths(HashMap<usize, NodePath>);
+
+impl NodePaths {
+    pub fn new(root: &Node) -> Self {
+        let mut paths = HashMap::new();
+        let mut stack = vec![(root, vec![])];
+        while let Some((node, path)) = stack.pop() {
+            for (i, child) in node.children.iter().enumerate() {
+                stack.push((child, [path.clone(), vec![i]].concat()));
+            }
+            paths.insert(node as *const Node as usize, path);
+        }
+        NodePaths(paths)
+    }
+
+    fn get(&self, node: &Node) -> Option<NodePath> {
+        self.0.get(&(node as *const Node as usize)).cloned()
+    }
+
+    fn get_all(&self, nodes: &ConsecutiveNodes) -> Option<Vec<NodePath>> {
+        nodes.as_vec().iter().map(|n| self.get(n)).collect()
+    }
+}
+
+fn resolve<'tree>(root: &'tree Node<'tree>, path: &[usize]) -> Option<&'tree Node<'tree>> {
+    path.iter().try_fold(root, |node, i| node.children.get(*i))
+}
+
+fn resolve_all<'tree>(
+    root: &'tree Node<'tree>,
+    paths: &[NodePath],
+) -> Option<ConsecutiveNodes<'tree>> {
+    let nodes = paths
+        .iter()
+        .map(|p| resolve(root, p))
+        .collect::<Option<Vec<&'tree Node<'tree>>>>()?;
+    ConsecutiveNodes::try_from(nodes).ok()
+}
+
+impl CachedItem {
+    fn new(item: &MatchedItem, paths: &NodePaths) -> Option<Self> {
+        let captures = item
+            .captures
+            .iter()
+            .map(|(mid, citem)| {
+                let capture = match citem {
+                    CaptureItem::Empty => CachedCapture::Empty,
+                    CaptureItem::Literal(s) => CachedCapture::Literal(s.clone()),
+                    CaptureItem::Nodes(n) => CachedCapture::Nodes(paths.get_all(n)?),
+                };
+                Some((mid.0.clone(), capture))
+            })
+            .collect::<Option<Vec<(String, CachedCapture)>>>()?;
+        Some(CachedItem {
+            area: paths.get_all(&item.area)?,
+            captures,
+        })
+    }
+
+    fn restore<'tree>(&self, root: &'tree Node<'tree>) -> Option<MatchedItem<'tree>> {
+        let captures = self
+            .captures
+            .iter()
+            .map(|(mid, capture)| {
+                let citem = match capture {
+                    CachedCapture::Empty => CaptureItem::Empty,
+                    CachedCapture::Literal(s) => CaptureItem::Literal(s.clone()),
+                    CachedCapture::Nodes(paths) => CaptureItem::Nodes(resolve_all(root, paths)?),
+                };
+                Some((MetavariableId(mid.clone()), citem))
+            })
+            .collect::<Option<CaptureMap<'tree>>>()?;
+        Some(MatchedItem {
+            area: resolve_all(root, &self.area)?,
+            captures,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        language::HCL,
+        ruleset::{RawPatternWithConstraints, Rule},
+        tree::{NormalizedTree, Tree},
+    };
+
+    #[test]
+    fn test_result_cache() {
+        let root = std::env::temp_dir().join(format!("shisho-test-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let cache = ResultCache::new(&root).unwrap();
+
+        let rule = Rule::new(
+            "test".into(),
+            Language::HCL,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "encrypted = :[X]".into(),
+                ..Default::default()
+            }],
+            vec![],
+            vec![],
+        );
+        let body = "resource \"a\" \"b\" {\n  encrypted = false\n}\n";
+        let tree = Tree::<HCL>::try_from(body).unwrap();
+        let ptree = NormalizedTree::from(&tree);
+        let ptree = ptree.as_ref_treeview();
+        let items = rule.find::<HCL>(&ptree).unwrap();
+        assert_eq!(items.len(), 1);
+
+        let mut results = cache.load(Language::HCL, body);
+        assert!(results.findings("digest", ptree.view_root).is_none());
+        assert!(results
+            .answer_without_parsing(vec!["digest"].into_iter())
+            .is_none());
+        results.record("digest", &items, &NodePaths::new(ptree.view_root));
+        results.record("empty", &[], &NodePaths::new(ptree.view_root));
+        results.record_parse_outcome(ParseOutcome::default());
+        results.store().unwrap();
+
+        let results = cache.load(Language::HCL, body);
+        let restored = results.findings("digest", ptree.view_root).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(
+            restored[0].area.as_str().unwrap(),
+            items[0].area.as_str().unwrap()
+        );
+        assert_eq!(
+            restored[0]
+                .capture_of(&MetavariableId("X".into()))
+                .map(|c| c.as_str()),
+            Some("false")
+        );
+        assert_eq!(
+            results.answer_without_parsing(vec!["empty"].into_iter()),
+            Some(&ParseOutcome::default())
+        );
+        assert!(results
+            .answer_without_parsing(vec!["empty", "digest"].into_iter())
+            .is_none());
+
+        // entries of the other contents and versions are pruned
+        let results = cache.load(Language::Go, body);
+        assert!(results.findings("digest", ptree.view_root).is_none());
+        std::fs::create_dir_all(root.join("0.0.0")).unwrap();
+        let cache = ResultCache::new(&root).unwrap();
+        cache.load(Language::Go, body);
+        assert_eq!(cache.prune().unwrap(), 1);
+        assert!(!root.join("0.0.0").exists());
+        assert!(cache
+            .load(Language::HCL, body)
+            .answer_without_parsing(vec!["empty"].into_iter())
+            .is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
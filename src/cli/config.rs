@@ -0,0 +1,197 @@
+//! This module defines the project configuration file (`.shisho.yaml`).
+
+use crate::cli::encoding::parse_encoding;
+use crate::cli::reporter::ReporterType;
+use crate::core::ruleset::{Language, Severity};
+use anyhow::{anyhow, Result};
+use encoding_rs::Encoding;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const CONFIG_FILE_NAME: &str = ".shisho.yaml";
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RawConfig {
+    #[serde(default)]
+    pub rulesets: Vec<PathBuf>,
+
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    #[serde(default)]
+    pub encodings: Vec<RawEncodingConfig>,
+    #[serde(default)]
+    pub languages: HashMap<String, Language>,
+
+    pub format: Option<String>,
+    pub min_severity: Option<String>,
+    pub fail_severity: Option<String>,
+
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawEncodingConfig {
+    pub path: String,
+    pub encoding: String,
+}
+
+/// `Config` is a validated project configuration.
+/// Paths and glob patterns in it are relative to `root`, the directory which contains the configuration file.
+#[derive(Debug)]
+pub struct Config {
+    pub root: PathBuf,
+
+    pub rulesets: Vec<PathBuf>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub encodings: Vec<(String, &'static Encoding)>,
+    pub languages: HashMap<String, Language>,
+
+    pub format: Option<ReporterType>,
+    pub min_severity: Option<Severity>,
+    pub fail_severity: Option<Severity>,
+
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// `discover` looks for the configuration file in `start` and its ancestors.
+    pub fn discover(start: &Path) -> Result<Option<Self>> {
+        let start = start.canonicalize()?;
+        let mut dir = if start.is_dir() {
+            Some(start.as_path())
+        } else {
+            start.parent()
+        };
+        while let Some(d) = dir {
+            let p = d.join(CONFIG_FILE_NAME);
+            if p.is_file() {
+                return Self::from_path(&p).map(Some);
+            }
+            dir = d.parent();
+        }
+        Ok(None)
+    }
+
+    pub fn from_path(p: &Path) -> Result<Self> {
+        let load_error =
+            |e: anyhow::Error| anyhow!("failed to load {}: {}", p.to_string_lossy(), e);
+
+        let body = std::fs::read_to_string(p).map_err(|e| load_error(e.into()))?;
+        let raw: RawConfig = if body.trim().is_empty() {
+            RawConfig::default()
+        } else {
+            serde_yaml::from_str(&body).map_err(|e| load_error(e.into()))?
+        };
+        let root = p
+            .canonicalize()
+            .map_err(|e| load_error(e.into()))?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Self::from_raw(root, raw).map_err(load_error)
+    }
+
+    fn from_raw(root: PathBuf, raw: RawConfig) -> Result<Self> {
+        let encodings = raw
+            .encodings
+            .into_iter()
+            .map(|e| Ok((e.path, parse_encoding(&e.encoding)?)))
+            .collect::<Result<Vec<(String, &'static Encoding)>>>()?;
+        let format = raw
+            .format
+            .map(|f| ReporterType::from_str(&f).map_err(|_| anyhow!("unknown format: {}", f)))
+            .transpose()?;
+        // severities are parsed in the same way as `--min-severity` and `--fail-severity`
+        let severity = |s: Option<String>| {
+            s.map(|s| Severity::from_str(&s).map_err(|_| anyhow!("unknown severity: {}", s)))
+                .transpose()
+        };
+        let min_severity = severity(raw.min_severity)?;
+        let fail_severity = severity(raw.fail_severity)?;
+        let languages = raw
+            .languages
+            .into_iter()
+            .map(|(ext, lang)| (ext.trim_start_matches('.').to_string(), lang))
+            .collect();
+
+        Ok(Config {
+            rulesets: raw.rulesets.into_iter().map(|p| root.join(p)).collect(),
+            include: raw.include,
+            exclude: raw.exclude,
+            encodings,
+            languages,
+            format,
+            min_severity,
+            fail_severity,
+            cache_dir: raw.cache_dir.map(|p| root.join(p)),
+            root,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let raw: RawConfig = serde_yaml::from_str(
+            r#"
+rulesets:
+  - ./policies
+exclude:
+  - vendor
+encodings:
+  - path: "legacy/**/*.go"
+    encoding: shift_jis
+languages:
+  .tfvars: hcl
+format: sarif
+min-severity: MEDIUM
+fail-severity: High
+cache-dir: .shisho-cache
+"#,
+        )
+        .unwrap();
+        let config = Config::from_raw(PathBuf::from("/project"), raw).unwrap();
+        assert_eq!(config.rulesets, vec![PathBuf::from("/project/./policies")]);
+        assert_eq!(config.exclude, vec!["vendor".to_string()]);
+        assert_eq!(
+            config.encodings,
+            vec![("legacy/**/*.go".to_string(), encoding_rs::SHIFT_JIS)]
+        );
+        assert_eq!(config.languages.get("tfvars"), Some(&Language::HCL));
+        assert!(matches!(config.format, Some(ReporterType::SARIF)));
+        assert_eq!(config.min_severity, Some(Severity::Medium));
+        assert_eq!(config.fail_severity, Some(Severity::High));
+        assert_eq!(
+            config.cache_dir,
+            Some(PathBuf::from("/project/.shisho-cache"))
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_config() {
+        assert!(serde_yaml::from_str::<RawConfig>("unknown: 1").is_err());
+
+        let raw: RawConfig = serde_yaml::from_str("format: xml").unwrap();
+        assert!(Config::from_raw(PathBuf::from("/project"), raw).is_err());
+
+        let raw: RawConfig = serde_yaml::from_str("min-severity: severe").unwrap();
+        assert!(Config::from_raw(PathBuf::from("/project"), raw).is_err());
+
+        let raw: RawConfig =
+            serde_yaml::from_str("encodings: [{ path: '*.go', encoding: unknown }]").unwrap();
+        assert!(Config::from_raw(PathBuf::from("/project"), raw).is_err());
+    }
+}
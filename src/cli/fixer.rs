@@ -0,0 +1,299 @@
+//! This module defines `Fixer`, which writes suggested changes back to the target files.
+
+use crate::core::{
+    language::Queryable,
+    matcher::MatchedItem,
+    rewriter::{Edit, EditSet},
+    ruleset::compiled::CompiledRule,
+    source::Code,
+    target::Target,
+};
+use ansi_term::Color;
+use anyhow::{anyhow, Result};
+use similar::TextDiff;
+
+#[derive(Debug)]
+pub struct Fixer {
+    /// 0-origin index of the rewrite option to apply
+    option_index: usize,
+    backup: bool,
+    dry_run: bool,
+
+    fixed_items: usize,
+    skipped_items: usize,
+    fixed_files: usize,
+}
+
+impl Fixer {
+    pub fn new(rewrite_option: usize, backup: bool, dry_run: bool) -> Result<Self> {
+        if rewrite_option == 0 {
+            return Err(anyhow!("--rewrite-option is 1-origin"));
+        }
+
+        Ok(Fixer {
+            option_index: rewrite_option - 1,
+            backup,
+            dry_run,
+
+            fixed_items: 0,
+            skipped_items: 0,
+            fixed_files: 0,
+        })
+    }
+
+    /// `fix` applies the selected rewrite option of each rule to the matched items in `target`.
+    /// Items whose ranges overlap with an item fixed earlier are left untouched and reported as skipped.
+    /// It returns whether each item was resolved in the file.
+    pub fn fix<T: Queryable>(
+        &mut self,
+        target: &Target,
+        items: &[(&CompiledRule<T>, MatchedItem)],
+    ) -> Result<Vec<bool>> {
+        let path = match target.path {
+            Some(ref p) => p,
+            None => return Err(anyhow!("changes cannot be written back to stdin")),
+        };
+
+        let mut resolved = vec![false; items.len()];
+        let mut edits = EditSet::new();
+        let mut edited_items = vec![];
+        for (item_idx, (rule, mitem)) in items.iter().enumerate() {
+            let pattern = match rule.rewrite_options().get(self.option_index) {
+                Some(pattern) => pattern,
+                None => {
+                    eprintln!(
+                        "{}: [{}] {} (no rewrite option {})",
+                        Color::Yellow.paint("skipped"),
+                        rule.rule.id,
+                        location_of(target, mitem.area.start_byte()),
+                        self.option_index + 1
+                    );
+                    self.skipped_items += 1;
+                    continue;
+                }
+            };
+            edits.push(Edit::from_rewrite(
+                target.body.len(),
+                mitem,
+                &pattern.as_rewrite_option(),
+            )?);
+            edited_items.push((item_idx, rule));
+        }
+        if edits.is_empty() {
+            return Ok(resolved);
+        }
+        if let Some(encoding) = target.encoding {
+            if encoding.output_encoding() != encoding {
+                return Err(anyhow!(
+                    "changes cannot be written back to {} encoded in {}",
+                    target.relative_path(),
+                    encoding.name()
+                ));
+            }
+        }
+
+        let code: Code<T> = target.body.clone().into();
+        let (new_code, statuses) = code.to_edited_form(&edits)?;
+        for (idx, status) in statuses.iter().enumerate() {
+            let location = location_of(target, edits.get(idx).unwrap().start_byte);
+            let (item_idx, rule) = edited_items[idx];
+            let reason = match status.conflict() {
+                None => {
+                    eprintln!(
+                        "{}: [{}] {}",
+                        Color::Green.paint(if self.dry_run { "fixable" } else { "fixed" }),
+                        rule.rule.id,
+                        location
+                    );
+                    resolved[item_idx] = !self.dry_run;
+                    self.fixed_items += 1;
+                    continue;
+                }
+                Some(reason) => reason,
+            };
+            eprintln!(
+                "{}: [{}] {} ({})",
+                Color::Yellow.paint("skipped"),
+                rule.rule.id,
+                location,
+                reason
+            );
+            self.skipped_items += 1;
+        }
+        self.fixed_files += 1;
+
+        let new_body = new_code.as_str();
+        if self.dry_run {
+            eprint!(
+                "{}",
+                TextDiff::from_lines(target.body.as_str(), new_body)
+                    .unified_diff()
+                    .header(&target.relative_path(), &target.relative_path())
+            );
+            return Ok(resolved);
+        }
+
+        if self.backup {
+            let mut backup_path = path.clone().into_os_string();
+            backup_path.push(".orig");
+            std::fs::copy(path, backup_path)?;
+        }
+        match target.encoding {
+            Some(encoding) => std::fs::write(path, encoding.encode(new_body).0)?,
+            None => std::fs::write(path, new_body)?,
+        }
+
+        Ok(resolved)
+    }
+
+    pub fn print_summary(&self) {
+        eprintln!(
+            "{} item(s) {} in {} file(s), {} item(s) skipped",
+            self.fixed_items,
+            if self.dry_run { "fixable" } else { "fixed" },
+            self.fixed_files,
+            self.skipped_items
+        );
+    }
+}
+
+/// `location_of` formats the line of `byte` in `target` as `<path>:<line>`.
+pub(crate) fn location_of(target: &Target, byte: usize) -> String {
+    format!(
+        "{}:{}",
+        target.relative_path(),
+        target.body[..byte].matches('\n').count() + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        language::HCL,
+        ruleset::{Language, RawPatternWithConstraints, Rule},
+        tree::{NormalizedTree, Tree},
+    };
+    use std::{
+        convert::TryFrom,
+        path::{Path, PathBuf},
+    };
+    use tempfile::TempDir;
+
+    const BODY: &str = "resource \"a\" \"b\" {\n  size = 1\n}\n";
+
+    fn rule(id: &str, rewrite_options: Vec<&str>) -> CompiledRule<HCL> {
+        CompiledRule::new(Rule::new(
+            id.into(),
+            Language::HCL,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "size = :[X]".into(),
+                ..Default::default()
+            }],
+            rewrite_options.into_iter().map(String::from).collect(),
+            vec![],
+        ))
+        .unwrap()
+    }
+
+    /// `fix` writes `BODY` to a file in a fresh directory and applies `rules` to it with `fixer`.
+    /// The directory is removed when the returned `TempDir` is dropped.
+    fn fix(fixer: &mut Fixer, rules: &[CompiledRule<HCL>]) -> (TempDir, PathBuf, Vec<bool>) {
+        let base = tempfile::tempdir().unwrap();
+        let path = base.path().join("main.tf");
+        std::fs::write(&path, BODY).unwrap();
+
+        let tree = Tree::<HCL>::try_from(BODY).unwrap();
+        let ptree = NormalizedTree::from(&tree);
+        let view = ptree.as_ref_treeview();
+        let items = rules
+            .iter()
+            .flat_map(|r| {
+                r.find(&view)
+                    .unwrap()
+                    .into_iter()
+                    .map(move |mitem| (r, mitem))
+            })
+            .collect::<Vec<(&CompiledRule<HCL>, MatchedItem)>>();
+        let target = Target {
+            path: Some(path.clone()),
+            body: BODY.into(),
+            ..Default::default()
+        };
+        let resolved = fixer.fix::<HCL>(&target, &items).unwrap();
+        (base, path, resolved)
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn backup_of(path: &Path) -> PathBuf {
+        let mut backup = path.to_path_buf().into_os_string();
+        backup.push(".orig");
+        PathBuf::from(backup)
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut fixer = Fixer::new(1, false, false).unwrap();
+        let (_dir, path, resolved) = fix(&mut fixer, &[rule("r", vec!["size = 2"])]);
+        assert_eq!(resolved, vec![true]);
+        assert_eq!(read(&path), "resource \"a\" \"b\" {\n  size = 2\n}\n");
+        assert!(!backup_of(&path).exists());
+        assert_eq!(
+            (fixer.fixed_items, fixer.skipped_items, fixer.fixed_files),
+            (1, 0, 1)
+        );
+
+        let mut fixer = Fixer::new(2, false, false).unwrap();
+        let (_dir, path, resolved) = fix(&mut fixer, &[rule("r", vec!["size = 2", "size = 3"])]);
+        assert_eq!(resolved, vec![true]);
+        assert_eq!(read(&path), "resource \"a\" \"b\" {\n  size = 3\n}\n");
+
+        assert!(Fixer::new(0, false, false).is_err());
+    }
+
+    #[test]
+    fn test_backup() {
+        let mut fixer = Fixer::new(1, true, false).unwrap();
+        let (_dir, path, _) = fix(&mut fixer, &[rule("r", vec!["size = 2"])]);
+        assert_eq!(read(&path), "resource \"a\" \"b\" {\n  size = 2\n}\n");
+        assert_eq!(read(&backup_of(&path)), BODY);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let mut fixer = Fixer::new(1, true, true).unwrap();
+        let (_dir, path, resolved) = fix(&mut fixer, &[rule("r", vec!["size = 2"])]);
+        // nothing is written, and the findings are still reported
+        assert_eq!(resolved, vec![false]);
+        assert_eq!(read(&path), BODY);
+        assert!(!backup_of(&path).exists());
+        assert_eq!(fixer.fixed_items, 1);
+    }
+
+    #[test]
+    fn test_skipped_items() {
+        // the second change is inside the first one
+        let mut fixer = Fixer::new(1, false, false).unwrap();
+        let (_dir, path, resolved) = fix(
+            &mut fixer,
+            &[
+                rule("first", vec!["size = 2"]),
+                rule("second", vec!["size = 3"]),
+            ],
+        );
+        assert_eq!(resolved, vec![true, false]);
+        assert_eq!(read(&path), "resource \"a\" \"b\" {\n  size = 2\n}\n");
+        assert_eq!((fixer.fixed_items, fixer.skipped_items), (1, 1));
+
+        // rules without the selected rewrite option are skipped
+        let mut fixer = Fixer::new(2, false, false).unwrap();
+        let (_dir, path, resolved) = fix(&mut fixer, &[rule("r", vec!["size = 2"])]);
+        assert_eq!(resolved, vec![false]);
+        assert_eq!(read(&path), BODY);
+        assert_eq!((fixer.fixed_items, fixer.skipped_items), (0, 1));
+    }
+}
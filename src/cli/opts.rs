@@ -2,7 +2,9 @@
 
 use super::subcommand::*;
 use crate::cli::reporter::ReporterType;
+use crate::core::ruleset::Severity;
 use clap_verbosity_flag::Verbosity;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -17,15 +19,153 @@ pub struct CommonOpts {
     pub verbose: Verbosity,
 }
 
+impl CommonOpts {
+    /// `is_verbose` returns whether `-v` was given at least once.
+    pub fn is_verbose(&self) -> bool {
+        self.verbose.log_level() >= Some(log::Level::Warn)
+    }
+}
+
+const SEVERITY_VARIANTS: [&str; 5] = ["unknown", "low", "medium", "high", "critical"];
+
 #[derive(StructOpt, Debug)]
 pub struct ReportOpts {
-    #[structopt(long, default_value = "console", possible_values(&ReporterType::variants()))]
-    pub format: ReporterType,
+    /// Output format [default: console]
+    #[structopt(long, possible_values(&ReporterType::variants()))]
+    pub format: Option<ReporterType>,
+
+    /// Reports only findings of rules at least as severe as the given one
+    #[structopt(long, possible_values(&SEVERITY_VARIANTS), case_insensitive = true)]
+    pub min_severity: Option<Severity>,
+
+    /// Exits with a non-zero code only when findings at least as severe as the given one are reported
+    #[structopt(long, possible_values(&SEVERITY_VARIANTS), case_insensitive = true)]
+    pub fail_severity: Option<Severity>,
+
+    /// Includes findings suppressed by `shisho:ignore` comments in JSON and SARIF reports
+    #[structopt(long)]
+    pub report_suppressed: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct FixOpts {
+    /// Applies suggested changes to the target files
+    #[structopt(long)]
+    pub apply: bool,
+
+    /// Index (1-origin) of the rewrite option to apply
+    #[structopt(long, default_value = "1")]
+    pub rewrite_option: usize,
+
+    /// Saves the original file as `<file>.orig` before applying changes
+    #[structopt(long, requires = "apply")]
+    pub backup: bool,
+
+    /// Prints changes to be applied without modifying any file
+    #[structopt(long, requires = "apply")]
+    pub dry_run: bool,
+}
+
+impl Default for FixOpts {
+    fn default() -> Self {
+        FixOpts {
+            apply: false,
+            rewrite_option: 1,
+            backup: false,
+            dry_run: false,
+        }
+    }
+}
+
+/// `ApplyOpts` is options of `fix` subcommand, which always applies changes without `--apply`.
+#[derive(StructOpt, Debug)]
+pub struct ApplyOpts {
+    /// Index (1-origin) of the rewrite option to apply
+    #[structopt(long, default_value = "1")]
+    pub rewrite_option: usize,
+
+    /// Saves the original file as `<file>.orig` before applying changes
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// Prints changes to be applied without modifying any file
+    #[structopt(long)]
+    pub dry_run: bool,
+}
+
+impl From<ApplyOpts> for FixOpts {
+    fn from(opts: ApplyOpts) -> Self {
+        FixOpts {
+            apply: true,
+            rewrite_option: opts.rewrite_option,
+            backup: opts.backup,
+            dry_run: opts.dry_run,
+        }
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub struct BaselineOpts {
+    /// Omits findings recorded in the given baseline file
+    #[structopt(long, parse(from_os_str))]
+    pub baseline: Option<PathBuf>,
+
+    /// Records fingerprints of the findings to the given baseline file
+    #[structopt(long, parse(from_os_str))]
+    pub write_baseline: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct TraversalOpts {
+    /// Loads files matching ignore files (`.gitignore`, `.ignore` and `.shishoignore`), hidden files and vendored files
+    #[structopt(long)]
+    pub no_ignore: bool,
+
+    /// Skips files larger than the given size in bytes; 0 means no limit
+    #[structopt(long, default_value = "1048576")]
+    pub max_file_size: u64,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct MatchOpts {
+    /// Aborts matching a rule against a file after the given number of steps; 0 means no limit
+    #[structopt(long, default_value = "1000000")]
+    pub max_match_steps: usize,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CacheOpts {
+    /// Stores findings per file content and rule in the given directory and reuses them for unchanged files
+    #[structopt(long, parse(from_os_str))]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Disables the cache even if `cache-dir` is declared in the configuration file
+    #[structopt(long)]
+    pub no_cache: bool,
+
+    /// Removes cache entries which were not used in this run
+    #[structopt(long)]
+    pub prune_cache: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ProfileOpts {
+    /// Prints the slowest rules, files and pairs of them to stderr
+    #[structopt(long)]
+    pub profile: bool,
+
+    /// Saves parse time per file and match time per rule and file to the given file as JSON
+    #[structopt(long, parse(from_os_str))]
+    pub profile_output: Option<PathBuf>,
 }
 
 #[derive(StructOpt, Debug)]
 pub enum SubCommand {
     Completion(completion::CompletionOpts),
     Find(find::FindOpts),
-    Check(check::CheckOpts),
+    Check(check::CheckCommandOpts),
+    /// Applies suggested changes of the given rule sets to files under the given path
+    Fix(fix::FixCommandOpts),
+    Test(test::TestOpts),
+    Validate(validate::ValidateOpts),
 }
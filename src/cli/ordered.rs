@@ -0,0 +1,104 @@
+//! This module defines `OrderedSink`, which lets workers running in parallel access a shared state in a fixed order.
+
+use std::{
+    collections::BTreeSet,
+    sync::{Condvar, Mutex},
+};
+
+struct Turns<S> {
+    state: S,
+    /// index of the next turn
+    next: usize,
+    /// indices of turns which will never be taken
+    abandoned: BTreeSet<usize>,
+}
+
+impl<S> Turns<S> {
+    fn advance(&mut self) {
+        self.next += 1;
+        while self.abandoned.remove(&self.next) {
+            self.next += 1;
+        }
+    }
+}
+
+/// `OrderedSink` holds a state which is updated in turns numbered from 0.
+/// Each turn must be either taken with `with` or given up with `abandon` exactly once.
+pub struct OrderedSink<S> {
+    turns: Mutex<Turns<S>>,
+    cond: Condvar,
+}
+
+impl<S> OrderedSink<S> {
+    pub fn new(state: S) -> Self {
+        OrderedSink {
+            turns: Mutex::new(Turns {
+                state,
+                next: 0,
+                abandoned: BTreeSet::new(),
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// `with` waits for all the turns before `idx` and then runs `f` with the state.
+    pub fn with<R>(&self, idx: usize, f: impl FnOnce(&mut S) -> R) -> R {
+        let mut turns = self
+            .cond
+            .wait_while(self.turns.lock().unwrap(), |t| t.next != idx)
+            .unwrap();
+        let r = f(&mut turns.state);
+        turns.advance();
+        self.cond.notify_all();
+        r
+    }
+
+    /// `abandon` gives up the turn `idx` if it has not been taken yet, so that later turns are not blocked.
+    pub fn abandon(&self, idx: usize) {
+        let mut turns = self.turns.lock().unwrap_or_else(|e| e.into_inner());
+        if idx < turns.next {
+            return;
+        }
+        if idx == turns.next {
+            turns.advance();
+        } else {
+            turns.abandoned.insert(idx);
+        }
+        self.cond.notify_all();
+    }
+
+    pub fn into_inner(self) -> S {
+        self.turns
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_sink() {
+        let sink = OrderedSink::new(vec![]);
+        std::thread::scope(|s| {
+            for worker in 0..4 {
+                let sink = &sink;
+                s.spawn(move || {
+                    for idx in (worker..20).step_by(4) {
+                        if idx % 7 == 3 {
+                            sink.abandon(idx);
+                        } else {
+                            sink.with(idx, |state| state.push(idx));
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            sink.into_inner(),
+            (0..20).filter(|idx| idx % 7 != 3).collect::<Vec<usize>>()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! This module defines `Profiler`, which records time spent for parsing files and matching rules.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{collections::HashMap, path::Path, sync::Mutex, time::Duration};
+
+/// number of entries printed in each section of the summary
+const SUMMARY_LENGTH: usize = 10;
+
+#[derive(Debug, Default)]
+struct Records {
+    /// parse time per file
+    files: Vec<(String, Duration)>,
+    /// match time and number of matches per pair of a rule and a file
+    pairs: Vec<(String, String, Duration, usize)>,
+}
+
+#[derive(Debug, Serialize)]
+struct Profile {
+    rules: Vec<RuleProfile>,
+    files: Vec<FileProfile>,
+    pairs: Vec<PairProfile>,
+}
+
+#[derive(Debug, Serialize)]
+struct RuleProfile {
+    id: String,
+    /// total match time in seconds
+    time: f64,
+    matches: usize,
+    files: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct FileProfile {
+    path: String,
+    /// parse time in seconds
+    time: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct PairProfile {
+    id: String,
+    path: String,
+    /// match time in seconds
+    time: f64,
+    matches: usize,
+}
+
+/// `Profiler` collects timings from workers checking files in parallel.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    records: Mutex<Records>,
+}
+
+impl Profiler {
+    pub fn record_parse(&self, path: String, time: Duration) {
+        self.records.lock().unwrap().files.push((path, time));
+    }
+
+    pub fn record_match(&self, rule_id: &str, path: String, time: Duration, matches: usize) {
+        self.records
+            .lock()
+            .unwrap()
+            .pairs
+            .push((rule_id.to_string(), path, time, matches));
+    }
+
+    /// `profile` aggregates the records into rules, files and pairs sorted from the slowest.
+    fn profile(&self) -> Profile {
+        let records = self.records.lock().unwrap();
+
+        let mut rules = HashMap::<&str, RuleProfile>::new();
+        for (id, _, time, matches) in &records.pairs {
+            let r = rules.entry(id).or_insert_with(|| RuleProfile {
+                id: id.clone(),
+                time: 0.0,
+                matches: 0,
+                files: 0,
+            });
+            r.time += time.as_secs_f64();
+            r.matches += matches;
+            r.files += 1;
+        }
+        let mut rules = rules.into_values().collect::<Vec<RuleProfile>>();
+        rules.sort_by(|a, b| b.time.total_cmp(&a.time).then(a.id.cmp(&b.id)));
+
+        let mut files = records
+            .files
+            .iter()
+            .map(|(path, time)| FileProfile {
+                path: path.clone(),
+                time: time.as_secs_f64(),
+            })
+            .collect::<Vec<FileProfile>>();
+        files.sort_by(|a, b| b.time.total_cmp(&a.time).then(a.path.cmp(&b.path)));
+
+        let mut pairs = records
+            .pairs
+            .iter()
+            .map(|(id, path, time, matches)| PairProfile {
+                id: id.clone(),
+                path: path.clone(),
+                time: time.as_secs_f64(),
+                matches: *matches,
+            })
+            .collect::<Vec<PairProfile>>();
+        pairs.sort_by(|a, b| {
+            b.time
+                .total_cmp(&a.time)
+                .then(a.id.cmp(&b.id))
+                .then(a.path.cmp(&b.path))
+        });
+
+        Profile {
+            rules,
+            files,
+            pairs,
+        }
+    }
+
+    /// `print_summary` prints the slowest rules, files and pairs of them to stderr.
+    pub fn print_summary(&self) {
+        let profile = self.profile();
+
+        eprintln!("slowest rules:");
+        for r in profile.rules.iter().take(SUMMARY_LENGTH) {
+            eprintln!(
+                "  {:>10.3}ms  {} ({} match(es) in {} file(s))",
+                r.time * 1000.0,
+                r.id,
+                r.matches,
+                r.files
+            );
+        }
+        eprintln!("slowest files to parse:");
+        for f in profile.files.iter().take(SUMMARY_LENGTH) {
+            eprintln!("  {:>10.3}ms  {}", f.time * 1000.0, f.path);
+        }
+        eprintln!("slowest pairs of a rule and a file:");
+        for p in profile.pairs.iter().take(SUMMARY_LENGTH) {
+            eprintln!(
+                "  {:>10.3}ms  {} on {} ({} match(es))",
+                p.time * 1000.0,
+                p.id,
+                p.path,
+                p.matches
+            );
+        }
+    }
+
+    /// `write` saves the whole profile as JSON.
+    pub fn write(&self, output: &Path) -> Result<()> {
+        let f = std::fs::File::create(output).map_err(|e| {
+            anyhow!(
+                "failed to create profile file {}: {}",
+                output.to_string_lossy(),
+                e
+            )
+        })?;
+        serde_json::to_writer_pretty(f, &self.profile())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let profiler = Profiler::default();
+        profiler.record_parse("a.tf".into(), Duration::from_millis(3));
+        profiler.record_parse("b.tf".into(), Duration::from_millis(5));
+        profiler.record_match("fast", "a.tf".into(), Duration::from_millis(1), 2);
+        profiler.record_match("slow", "a.tf".into(), Duration::from_millis(4), 0);
+        profiler.record_match("fast", "b.tf".into(), Duration::from_millis(2), 1);
+        profiler.record_match("slow", "b.tf".into(), Duration::from_millis(7), 1);
+
+        let profile = profiler.profile();
+        assert_eq!(
+            profile
+                .rules
+                .iter()
+                .map(|r| (r.id.as_str(), r.matches, r.files))
+                .collect::<Vec<_>>(),
+            vec![("slow", 1, 2), ("fast", 3, 2)]
+        );
+        assert_eq!(
+            profile
+                .files
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b.tf", "a.tf"]
+        );
+        assert_eq!(
+            profile
+                .pairs
+                .iter()
+                .map(|p| (p.id.as_str(), p.path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("slow", "b.tf"),
+                ("slow", "a.tf"),
+                ("fast", "b.tf"),
+                ("fast", "a.tf")
+            ]
+        );
+    }
+}
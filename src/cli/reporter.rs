@@ -1,13 +1,22 @@
+mod combined;
+pub use self::combined::*;
+
 mod console;
 pub use self::console::*;
 
 mod json;
 pub use self::json::*;
 
+mod patch;
+pub use self::patch::*;
+
 mod sarif;
 pub use self::sarif::*;
 
-use crate::core::{language::Queryable, matcher::MatchedItem, ruleset::Rule, target::Target};
+use crate::core::{
+    language::Queryable, matcher::MatchedItem, node::SyntaxError, ruleset::compiled::CompiledRule,
+    suppression::Suppression, target::Target,
+};
 use anyhow::Result;
 use std::str::FromStr;
 
@@ -20,17 +29,34 @@ pub trait Reporter<'a> {
     fn add_entry<T: Queryable>(
         &mut self,
         target: &Target,
-        items: Vec<(&Rule, MatchedItem)>,
+        items: Vec<(&CompiledRule<T>, MatchedItem)>,
     ) -> Result<()>;
 
+    /// `add_suppressed_entry` records items suppressed by inline comments.
+    /// Reporters which cannot express suppressions ignore them.
+    fn add_suppressed_entry<T: Queryable>(
+        &mut self,
+        _target: &Target,
+        _items: Vec<(&CompiledRule<T>, MatchedItem, &Suppression)>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// `add_parse_warning` records that `target` could be parsed only partially.
+    /// Reporters which cannot express notifications ignore them; they are printed to stderr anyway.
+    fn add_parse_warning(&mut self, _target: &Target, _error: &SyntaxError) -> Result<()> {
+        Ok(())
+    }
+
     fn report(&mut self) -> Result<()>;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum ReporterType {
     JSON,
     Console,
     SARIF,
+    Patch,
 }
 
 impl FromStr for ReporterType {
@@ -41,13 +67,14 @@ impl FromStr for ReporterType {
             "json" => Ok(ReporterType::JSON),
             "console" => Ok(ReporterType::Console),
             "sarif" => Ok(ReporterType::SARIF),
+            "patch" => Ok(ReporterType::Patch),
             _ => Err("".into()),
         }
     }
 }
 
 impl ReporterType {
-    pub fn variants() -> [&'static str; 3] {
-        ["json", "console", "sarif"]
+    pub fn variants() -> [&'static str; 4] {
+        ["json", "console", "sarif", "patch"]
     }
 }
@@ -0,0 +1,186 @@
+use crate::{
+    cli::fixer::location_of,
+    core::{
+        language::Queryable,
+        matcher::MatchedItem,
+        rewriter::{Edit, EditSet},
+        ruleset::compiled::CompiledRule,
+        target::Target,
+    },
+};
+use ansi_term::Color;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+
+/// `FilePatch` is a unified diff which combines the first rewrite options of all the findings in a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilePatch {
+    pub file: String,
+    pub diff: String,
+}
+
+/// `PatchBuilder` collects the first rewrite options of findings and combines them into a `FilePatch` per target.
+/// Findings in a target may be added in several batches (e.g. one per language for stdin), so a patch is completed
+/// when findings in another target are added or `finish` is called.
+#[derive(Debug, Default)]
+pub struct PatchBuilder {
+    pending: Option<PendingPatch>,
+}
+
+#[derive(Debug)]
+struct PendingPatch {
+    file: String,
+    body: String,
+    edits: EditSet,
+    /// rule ID and location of each edit, used to report skipped ones
+    origins: Vec<(String, String)>,
+}
+
+impl PatchBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `add` records the edits for `items` in `target`, and returns the patch of the previous target if it is completed.
+    pub fn add<T: Queryable>(
+        &mut self,
+        target: &Target,
+        items: &[(&CompiledRule<T>, MatchedItem)],
+    ) -> Result<Option<FilePatch>> {
+        let file = target.relative_path();
+        let completed = match self.pending {
+            Some(ref p) if p.file == file => None,
+            _ => self.finish()?,
+        };
+
+        let pending = self.pending.get_or_insert_with(|| PendingPatch {
+            file,
+            body: target.body.clone(),
+            edits: EditSet::new(),
+            origins: vec![],
+        });
+        for (rule, mitem) in items {
+            if let Some(pattern) = rule.rewrite_options().first() {
+                pending.edits.push(Edit::from_rewrite(
+                    target.body.len(),
+                    mitem,
+                    &pattern.as_rewrite_option(),
+                )?);
+                pending.origins.push((
+                    rule.rule.id.clone(),
+                    location_of(target, mitem.area.start_byte()),
+                ));
+            }
+        }
+
+        Ok(completed)
+    }
+
+    /// `finish` completes the patch of the last target, if it has any change.
+    /// Edits left out of the patch due to conflicts are reported to stderr in the same manner as `shisho fix`.
+    pub fn finish(&mut self) -> Result<Option<FilePatch>> {
+        let pending = match self.pending.take() {
+            Some(p) if !p.edits.is_empty() => p,
+            _ => return Ok(None),
+        };
+
+        let (new_body, statuses) = pending.edits.apply(&pending.body)?;
+        for ((id, location), status) in pending.origins.iter().zip(statuses) {
+            if let Some(reason) = status.conflict() {
+                eprintln!(
+                    "{}: [{}] {} ({})",
+                    Color::Yellow.paint("skipped"),
+                    id,
+                    location,
+                    reason
+                );
+            }
+        }
+
+        let diff = TextDiff::from_lines(pending.body.as_str(), new_body.as_str())
+            .unified_diff()
+            .header(&pending.file, &pending.file)
+            .to_string();
+        Ok(Some(FilePatch {
+            file: pending.file,
+            diff,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        language::Kubernetes,
+        ruleset::{Language, RawPatternWithConstraints, Rule},
+        tree::{NormalizedTree, Tree},
+    };
+    use std::convert::TryFrom;
+
+    fn rule(id: &str, pattern: &str, rewrite: &str) -> CompiledRule<Kubernetes> {
+        CompiledRule::new(Rule::new(
+            id.into(),
+            Language::Kubernetes,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: pattern.into(),
+                ..Default::default()
+            }],
+            vec![rewrite.into()],
+            vec![],
+        ))
+        .unwrap()
+    }
+
+    fn add(
+        builder: &mut PatchBuilder,
+        target: &Target,
+        rules: &[CompiledRule<Kubernetes>],
+    ) -> Option<FilePatch> {
+        let tree = Tree::<Kubernetes>::try_from(target.body.as_str()).unwrap();
+        let ptree = NormalizedTree::from(&tree);
+        let view = ptree.as_ref_treeview();
+        let items = rules
+            .iter()
+            .flat_map(|r| r.find(&view).unwrap().into_iter().map(move |m| (r, m)))
+            .collect::<Vec<_>>();
+        builder.add(target, &items).unwrap()
+    }
+
+    #[test]
+    fn test_combined_patch() {
+        let body = "image: nginx:latest\nreplicas: 1\n";
+        let target = Target {
+            body: body.into(),
+            ..Default::default()
+        };
+        let image = rule("image", "image: nginx:latest", "image: nginx:1.21");
+        let replicas = rule("replicas", "replicas: 1", "replicas: 2");
+
+        // findings in the same target added in several batches are combined into a single patch
+        let mut builder = PatchBuilder::new();
+        assert_eq!(add(&mut builder, &target, &[image]), None);
+        assert_eq!(add(&mut builder, &target, &[replicas]), None);
+        let patch = builder.finish().unwrap().unwrap();
+        assert_eq!(patch.diff.matches("@@ ").count(), 1);
+        assert!(patch.diff.contains("+image: nginx:1.21\n+replicas: 2\n"));
+        assert_eq!(builder.finish().unwrap(), None);
+
+        // a patch is completed when findings in another target are added
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("other.yaml");
+        std::fs::write(&path, body).unwrap();
+        let other = Target {
+            path: Some(path),
+            body: body.into(),
+            ..Default::default()
+        };
+        let replicas = rule("replicas", "replicas: 1", "replicas: 2");
+        add(&mut builder, &target, std::slice::from_ref(&replicas));
+        let patch = add(&mut builder, &other, &[]).unwrap();
+        assert_eq!(patch.file, target.relative_path());
+        assert_eq!(builder.finish().unwrap(), None);
+    }
+}
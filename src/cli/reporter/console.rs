@@ -1,8 +1,6 @@
-use std::convert::TryFrom;
-
 use super::Reporter;
 use crate::core::{
-    language::Queryable, matcher::MatchedItem, node::Range, pattern::Pattern, ruleset::Rule,
+    language::Queryable, matcher::MatchedItem, node::Range, ruleset::compiled::CompiledRule,
     source::Code, target::Target,
 };
 use ansi_term::{Color, Style};
@@ -22,11 +20,13 @@ impl<'a, W: std::io::Write> Reporter<'a> for ConsoleReporter<'a, W> {
     fn add_entry<T: Queryable>(
         &mut self,
         target: &Target,
-        items: Vec<(&Rule, MatchedItem)>,
+        items: Vec<(&CompiledRule<T>, MatchedItem)>,
     ) -> Result<()> {
         let lines = target.body.split('\n').collect::<Vec<&str>>();
 
-        for (rule, mitem) in items {
+        for (compiled, mitem) in items {
+            let rule = &compiled.rule;
+
             // print metadata of the matched items
 
             if let Some(title) = &rule.title {
@@ -93,13 +93,12 @@ impl<'a, W: std::io::Write> Reporter<'a> for ConsoleReporter<'a, W> {
 
             // print suggested changes
 
-            for (idx, rewrite) in rule.get_rewrite_options()?.into_iter().enumerate() {
+            for (idx, pattern) in compiled.rewrite_options().iter().enumerate() {
                 if idx > 0 {
                     writeln!(self.writer)?;
                 }
                 writeln!(self.writer, "Suggested changes ({}):", idx + 1)?;
                 let old_code: Code<T> = target.body.clone().into();
-                let pattern = Pattern::try_from(rewrite.as_str())?;
                 let new_code = old_code.to_rewritten_form(&mitem, pattern.as_rewrite_option())?;
 
                 let diff = TextDiff::from_lines(target.body.as_str(), new_code.as_str());
@@ -1,9 +1,7 @@
-use std::convert::TryFrom;
-
-use super::Reporter;
+use super::{FilePatch, PatchBuilder, Reporter};
 use crate::core::{
-    language::Queryable, matcher::MatchedItem, node::Range, pattern::Pattern, ruleset::Rule,
-    source::Code, target::Target,
+    language::Queryable, matcher::MatchedItem, node::Range, ruleset::compiled::CompiledRule,
+    source::Code, suppression::Suppression, target::Target,
 };
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -12,6 +10,17 @@ use similar::TextDiff;
 pub struct JSONReporter<'a, Writer: std::io::Write> {
     writer: &'a mut Writer,
     entries: Vec<Entry>,
+    patches: Vec<FilePatch>,
+    builder: PatchBuilder,
+}
+
+/// `Output` is the document printed by `JSONReporter`.
+/// `patches` has a single diff per file which combines the first rewrite options of all the unsuppressed findings in it,
+/// while `rewrite` of each entry has a diff per rewrite option of the finding alone.
+#[derive(Debug, Serialize, Deserialize)]
+struct Output {
+    pub entries: Vec<Entry>,
+    pub patches: Vec<FilePatch>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,6 +28,8 @@ struct Entry {
     pub id: String,
     pub location: Location,
     pub rewrite: Vec<JSONPatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppression: Option<JSONSuppression>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,41 +43,77 @@ struct JSONPatch {
     pub diff: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct JSONSuppression {
+    pub reason: String,
+}
+
+impl Entry {
+    fn new<T: Queryable>(
+        target: &Target,
+        rule: &CompiledRule<T>,
+        mitem: &MatchedItem,
+    ) -> Result<Self> {
+        let mut r = Entry {
+            id: rule.rule.id.clone(),
+            location: Location {
+                file: target.relative_path(),
+                range: mitem.area.range::<T>(),
+            },
+            rewrite: vec![],
+            suppression: None,
+        };
+
+        for pattern in rule.rewrite_options() {
+            let old_code: Code<T> = target.body.clone().into();
+            let new_code = old_code.to_rewritten_form(mitem, pattern.as_rewrite_option())?;
+
+            let diff = TextDiff::from_lines(target.body.as_str(), new_code.as_str())
+                .unified_diff()
+                .to_string();
+            r.rewrite.push(JSONPatch { diff });
+        }
+
+        Ok(r)
+    }
+}
+
 impl<'a, W: std::io::Write> Reporter<'a> for JSONReporter<'a, W> {
     type Writer = W;
     fn new(writer: &'a mut Self::Writer) -> Self {
         Self {
             writer,
             entries: vec![],
+            patches: vec![],
+            builder: PatchBuilder::new(),
         }
     }
 
     fn add_entry<T: Queryable>(
         &mut self,
         target: &Target,
-        items: Vec<(&Rule, MatchedItem)>,
+        items: Vec<(&CompiledRule<T>, MatchedItem)>,
     ) -> Result<()> {
-        for (rule, mitem) in items {
-            let mut r = Entry {
-                id: rule.id.clone(),
-                location: Location {
-                    file: target.relative_path(),
-                    range: mitem.area.range::<T>(),
-                },
-                rewrite: vec![],
-            };
-
-            for rewrite in rule.get_rewrite_options()? {
-                let old_code: Code<T> = target.body.clone().into();
-                let pattern = Pattern::try_from(rewrite.as_str())?;
-                let new_code = old_code.to_rewritten_form(&mitem, pattern.as_rewrite_option())?;
-
-                let diff = TextDiff::from_lines(target.body.as_str(), new_code.as_str())
-                    .unified_diff()
-                    .to_string();
-                r.rewrite.push(JSONPatch { diff });
-            }
+        for (rule, mitem) in items.iter() {
+            self.entries.push(Entry::new::<T>(target, rule, mitem)?);
+        }
+        if let Some(patch) = self.builder.add(target, &items)? {
+            self.patches.push(patch);
+        }
+
+        Ok(())
+    }
 
+    fn add_suppressed_entry<T: Queryable>(
+        &mut self,
+        target: &Target,
+        items: Vec<(&CompiledRule<T>, MatchedItem, &Suppression)>,
+    ) -> Result<()> {
+        for (rule, mitem, suppression) in items {
+            let mut r = Entry::new::<T>(target, rule, &mitem)?;
+            r.suppression = Some(JSONSuppression {
+                reason: suppression.reason.clone().unwrap_or_default(),
+            });
             self.entries.push(r);
         }
 
@@ -74,8 +121,13 @@ impl<'a, W: std::io::Write> Reporter<'a> for JSONReporter<'a, W> {
     }
 
     fn report(&mut self) -> Result<()> {
-        let s = serde_json::to_string(&self.entries)?;
-        self.entries = vec![];
+        if let Some(patch) = self.builder.finish()? {
+            self.patches.push(patch);
+        }
+        let s = serde_json::to_string(&Output {
+            entries: std::mem::take(&mut self.entries),
+            patches: std::mem::take(&mut self.patches),
+        })?;
         write!(self.writer, "{}", s)?;
         Ok(())
     }
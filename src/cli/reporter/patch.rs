@@ -0,0 +1,44 @@
+use super::{FilePatch, PatchBuilder, Reporter};
+use crate::core::{
+    language::Queryable, matcher::MatchedItem, ruleset::compiled::CompiledRule, target::Target,
+};
+use anyhow::Result;
+
+/// `PatchReporter` prints a unified diff per file, which combines the first rewrite options of all the findings in the file.
+pub struct PatchReporter<'a, Writer: std::io::Write> {
+    writer: &'a mut Writer,
+    builder: PatchBuilder,
+}
+
+impl<'a, W: std::io::Write> PatchReporter<'a, W> {
+    fn write(&mut self, patch: Option<FilePatch>) -> Result<()> {
+        if let Some(patch) = patch {
+            write!(self.writer, "{}", patch.diff)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: std::io::Write> Reporter<'a> for PatchReporter<'a, W> {
+    type Writer = W;
+    fn new(writer: &'a mut Self::Writer) -> Self {
+        Self {
+            writer,
+            builder: PatchBuilder::new(),
+        }
+    }
+
+    fn add_entry<T: Queryable>(
+        &mut self,
+        target: &Target,
+        items: Vec<(&CompiledRule<T>, MatchedItem)>,
+    ) -> Result<()> {
+        let completed = self.builder.add(target, &items)?;
+        self.write(completed)
+    }
+
+    fn report(&mut self) -> Result<()> {
+        let completed = self.builder.finish()?;
+        self.write(completed)
+    }
+}
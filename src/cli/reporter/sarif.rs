@@ -2,7 +2,9 @@ use super::Reporter;
 use crate::core::{
     language::Queryable,
     matcher::MatchedItem,
-    ruleset::{Rule, Severity},
+    node::SyntaxError,
+    ruleset::{compiled::CompiledRule, Rule, Severity},
+    suppression::Suppression,
     target::Target,
 };
 use anyhow::Result;
@@ -13,11 +15,102 @@ pub struct SARIFReporter<'a, Writer: std::io::Write> {
     writer: &'a mut Writer,
 
     results: Vec<sarif::Result>,
+    /// notifications of files which could be parsed only partially
+    notifications: Vec<sarif::Notification>,
 
     descriptors_idx_map: HashMap<String, usize>,
     descriptors: Vec<sarif::ReportingDescriptor>,
 }
 
+impl<'a, W: std::io::Write> SARIFReporter<'a, W> {
+    fn add_result<T: Queryable>(
+        &mut self,
+        target: &Target,
+        rule: &Rule,
+        mitem: &MatchedItem,
+        suppression: Option<&Suppression>,
+    ) -> Result<()> {
+        let descriptor_idx = {
+            if let Some(idx) = self.descriptors_idx_map.get(&rule.id) {
+                *idx
+            } else {
+                let descriptor = sarif::ReportingDescriptorBuilder::default()
+                    .id(rule.id.clone())
+                    .short_description::<sarif::MultiformatMessageString>(
+                        sarif::MultiformatMessageStringBuilder::default()
+                            .markdown(rule.title.clone().unwrap_or(rule.message.clone()))
+                            .text(rule.title.clone().unwrap_or(rule.message.clone()))
+                            .build()?,
+                    )
+                    .full_description::<sarif::MultiformatMessageString>(
+                        sarif::MultiformatMessageStringBuilder::default()
+                            .markdown(rule.message.clone())
+                            .text(rule.message.clone())
+                            .build()?,
+                    )
+                    .help(
+                        sarif::MultiformatMessageStringBuilder::default()
+                            .markdown(rule.message.clone())
+                            .text(rule.message.clone())
+                            .build()?,
+                    )
+                    .build()?;
+                self.descriptors.push(descriptor);
+                let idx = self.descriptors.len() - 1;
+                self.descriptors_idx_map.insert(rule.id.clone(), idx);
+                idx
+            }
+        };
+
+        let mut builder = sarif::ResultBuilder::default();
+        builder
+            .rule_id(rule.id.clone())
+            .rule_index(descriptor_idx as i64)
+            .message::<sarif::Message>(
+                sarif::MessageBuilder::default()
+                    .markdown(rule.message.clone())
+                    .text(rule.message.clone())
+                    .build()?,
+            )
+            .locations(vec![sarif::LocationBuilder::default()
+                .physical_location(
+                    sarif::PhysicalLocationBuilder::default()
+                        .artifact_location(
+                            sarif::ArtifactLocationBuilder::default()
+                                .uri(target.relative_path())
+                                .build()?,
+                        )
+                        .region(
+                            sarif::RegionBuilder::default()
+                                .start_line(mitem.area.range::<T>().start.row as i64)
+                                .start_column(mitem.area.range::<T>().start.column as i64)
+                                .build()?,
+                        )
+                        .build()?,
+                )
+                .build()?])
+            .level(
+                match rule.get_severity() {
+                    Severity::Unknown => sarif::ResultLevel::None,
+                    Severity::Low => sarif::ResultLevel::Note,
+                    Severity::Medium => sarif::ResultLevel::Warning,
+                    Severity::High => sarif::ResultLevel::Error,
+                    Severity::Critical => sarif::ResultLevel::Error,
+                }
+                .to_string(),
+            );
+        if let Some(suppression) = suppression {
+            builder.suppressions(vec![sarif::SuppressionBuilder::default()
+                .kind(sarif::SupressionKind::InSource.to_string())
+                .justification(suppression.reason.clone().unwrap_or_default())
+                .build()?]);
+        }
+        self.results.push(builder.build()?);
+
+        Ok(())
+    }
+}
+
 impl<'a, W: std::io::Write> Reporter<'a> for SARIFReporter<'a, W> {
     type Writer = W;
     fn new(writer: &'a mut Self::Writer) -> Self {
@@ -25,6 +118,7 @@ impl<'a, W: std::io::Write> Reporter<'a> for SARIFReporter<'a, W> {
             writer,
 
             results: vec![],
+            notifications: vec![],
 
             descriptors_idx_map: HashMap::new(),
             descriptors: vec![],
@@ -34,50 +128,36 @@ impl<'a, W: std::io::Write> Reporter<'a> for SARIFReporter<'a, W> {
     fn add_entry<T: Queryable>(
         &mut self,
         target: &Target,
-        items: Vec<(&Rule, MatchedItem)>,
+        items: Vec<(&CompiledRule<T>, MatchedItem)>,
     ) -> Result<()> {
         for (rule, mitem) in items {
-            let descriptor_idx = {
-                if let Some(idx) = self.descriptors_idx_map.get(&rule.id) {
-                    *idx
-                } else {
-                    let descriptor = sarif::ReportingDescriptorBuilder::default()
-                        .id(rule.id.clone())
-                        .short_description::<sarif::MultiformatMessageString>(
-                            sarif::MultiformatMessageStringBuilder::default()
-                                .markdown(rule.title.clone().unwrap_or(rule.message.clone()))
-                                .text(rule.title.clone().unwrap_or(rule.message.clone()))
-                                .build()?,
-                        )
-                        .full_description::<sarif::MultiformatMessageString>(
-                            sarif::MultiformatMessageStringBuilder::default()
-                                .markdown(rule.message.clone())
-                                .text(rule.message.clone())
-                                .build()?,
-                        )
-                        .help(
-                            sarif::MultiformatMessageStringBuilder::default()
-                                .markdown(rule.message.clone())
-                                .text(rule.message.clone())
-                                .build()?,
-                        )
-                        .build()?;
-                    self.descriptors.push(descriptor);
-                    let idx = self.descriptors.len() - 1;
-                    self.descriptors_idx_map.insert(rule.id.clone(), idx);
-                    idx
-                }
-            };
-
-            let result = sarif::ResultBuilder::default()
-                .rule_id(rule.id.clone())
-                .rule_index(descriptor_idx as i64)
-                .message::<sarif::Message>(
-                    sarif::MessageBuilder::default()
-                        .markdown(rule.message.clone())
-                        .text(rule.message.clone())
-                        .build()?,
-                )
+            self.add_result::<T>(target, &rule.rule, &mitem, None)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_suppressed_entry<T: Queryable>(
+        &mut self,
+        target: &Target,
+        items: Vec<(&CompiledRule<T>, MatchedItem, &Suppression)>,
+    ) -> Result<()> {
+        for (rule, mitem, suppression) in items {
+            self.add_result::<T>(target, &rule.rule, &mitem, Some(suppression))?;
+        }
+
+        Ok(())
+    }
+
+    fn add_parse_warning(&mut self, target: &Target, error: &SyntaxError) -> Result<()> {
+        let message = format!(
+            "failed to parse a part of the file, so findings in it may be missed: {}",
+            error
+        );
+        self.notifications.push(
+            sarif::NotificationBuilder::default()
+                .level(sarif::NotificationLevel::Warning.to_string())
+                .message::<sarif::Message>(sarif::MessageBuilder::default().text(message).build()?)
                 .locations(vec![sarif::LocationBuilder::default()
                     .physical_location(
                         sarif::PhysicalLocationBuilder::default()
@@ -88,27 +168,15 @@ impl<'a, W: std::io::Write> Reporter<'a> for SARIFReporter<'a, W> {
                             )
                             .region(
                                 sarif::RegionBuilder::default()
-                                    .start_line(mitem.area.range::<T>().start.row as i64)
-                                    .start_column(mitem.area.range::<T>().start.column as i64)
+                                    .start_line((error.row + 1) as i64)
+                                    .start_column((error.column + 1) as i64)
                                     .build()?,
                             )
                             .build()?,
                     )
                     .build()?])
-                .level(
-                    match rule.get_severity() {
-                        Severity::Unknown => sarif::ResultLevel::None,
-                        Severity::Low => sarif::ResultLevel::Note,
-                        Severity::Medium => sarif::ResultLevel::Warning,
-                        Severity::High => sarif::ResultLevel::Error,
-                        Severity::Critical => sarif::ResultLevel::Error,
-                    }
-                    .to_string(),
-                )
-                .build()?;
-            self.results.push(result);
-        }
-
+                .build()?,
+        );
         Ok(())
     }
 
@@ -120,10 +188,16 @@ impl<'a, W: std::io::Write> Reporter<'a> for SARIFReporter<'a, W> {
             .rules(self.descriptors.clone())
             .build()?;
 
-        let run = sarif::RunBuilder::default()
-            .tool::<sarif::Tool>(tool_component.try_into()?)
-            .results(self.results.clone())
-            .build()?;
+        let mut run = sarif::RunBuilder::default();
+        run.tool::<sarif::Tool>(tool_component.try_into()?)
+            .results(self.results.clone());
+        if !self.notifications.is_empty() {
+            run.invocations(vec![sarif::InvocationBuilder::default()
+                .execution_successful(true)
+                .tool_execution_notifications(self.notifications.clone())
+                .build()?]);
+        }
+        let run = run.build()?;
 
         let sarif = sarif::SarifBuilder::default()
             .version(sarif::Version::V2_1_0.to_string())
@@ -135,6 +209,7 @@ impl<'a, W: std::io::Write> Reporter<'a> for SARIFReporter<'a, W> {
         write!(self.writer, "{}", s)?;
 
         self.results = vec![];
+        self.notifications = vec![];
         self.descriptors = vec![];
         self.descriptors_idx_map = HashMap::new();
         Ok(())
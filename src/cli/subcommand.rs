@@ -3,3 +3,6 @@
 pub mod check;
 pub mod completion;
 pub mod find;
+pub mod fix;
+pub mod test;
+pub mod validate;
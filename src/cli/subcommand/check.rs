@@ -1,35 +1,64 @@
 //! This module defines `check` subcommand.
 
+use crate::cli::baseline::Baseline;
+use crate::cli::cache::{NodePaths, ParseOutcome, ResultCache};
+use crate::cli::config::{Config, CONFIG_FILE_NAME};
 use crate::cli::encoding::{parse_encoding, LABELS_SORTED};
-use crate::cli::reporter::{ConsoleReporter, JSONReporter, Reporter, ReporterType, SARIFReporter};
-use crate::cli::{CommonOpts, ReportOpts};
+use crate::cli::fixer::Fixer;
+use crate::cli::ordered::OrderedSink;
+use crate::cli::profile::Profiler;
+use crate::cli::reporter::{
+    ConsoleReporter, JSONReporter, PatchReporter, Reporter, ReporterType, SARIFReporter,
+};
+use crate::cli::{
+    BaselineOpts, CacheOpts, CommonOpts, FixOpts, MatchOpts, ProfileOpts, ReportOpts, TraversalOpts,
+};
 use crate::core::source::NormalizedSource;
 use crate::core::target::TargetLoader;
 use crate::core::tree::NormalizedTree;
 use crate::core::{
-    language::{Dockerfile, Go, Queryable, HCL},
-    ruleset::{self, Rule},
+    changes::{repository_root, ChangeSet},
+    language::Queryable,
+    matcher::StepLimitExceeded,
+    ruleset::{
+        self,
+        compiled::{CompiledRuleGroup, CompiledRuleSet, CompiledRules},
+        Language, LoadMode, RuleSetErrors, Severity,
+    },
+    suppression::{find_suppressions, SUPPRESSION_MARKER},
     target::Target,
     tree::Tree,
 };
+use crate::dispatch_language;
 use ansi_term::Color;
 use anyhow::{anyhow, Result};
 use encoding_rs::Encoding;
-use std::{collections::HashMap, convert::TryFrom};
+use std::{
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Once,
+    },
+    time::Instant,
+};
 use std::{iter::repeat, path::PathBuf};
 use structopt::StructOpt;
 
 // Checks files under the given path with the given rule sets
 #[derive(StructOpt, Debug)]
 pub struct CheckOpts {
-    /// Rule Set for searching
+    /// Rule Set for searching; `rulesets` in the project configuration file is used when omitted
     #[structopt(parse(from_os_str))]
-    pub ruleset_path: PathBuf,
+    pub ruleset_path: Option<PathBuf>,
 
     /// File path to search    
     #[structopt(parse(from_os_str))]
     pub target_path: Option<PathBuf>,
 
+    /// File path to search, which is given with this flag instead when rule sets come from the project configuration file
+    #[structopt(long = "target", parse(from_os_str), conflicts_with = "target_path")]
+    pub target: Option<PathBuf>,
+
     #[structopt(flatten)]
     pub common: CommonOpts,
 
@@ -42,8 +71,54 @@ pub struct CheckOpts {
     #[structopt(flatten)]
     pub report: ReportOpts,
 
+    /// options to apply changes, which differ between `check` and `fix`
+    #[structopt(skip)]
+    pub fix: FixOpts,
+
+    #[structopt(flatten)]
+    pub baseline: BaselineOpts,
+
     #[structopt(long)]
     pub exclude: Vec<String>,
+
+    #[structopt(flatten)]
+    pub traversal: TraversalOpts,
+
+    #[structopt(flatten)]
+    pub matching: MatchOpts,
+
+    #[structopt(flatten)]
+    pub cache: CacheOpts,
+
+    #[structopt(flatten)]
+    pub profile: ProfileOpts,
+
+    /// Reports only findings on lines changed against the given git revision
+    #[structopt(long)]
+    pub diff_base: Option<String>,
+
+    /// Number of files checked in parallel [default: the number of CPUs]
+    #[structopt(short, long)]
+    pub jobs: Option<usize>,
+}
+
+/// `CheckCommandOpts` is options of `check` subcommand, which applies changes only with `--apply`.
+#[derive(StructOpt, Debug)]
+pub struct CheckCommandOpts {
+    #[structopt(flatten)]
+    pub check: CheckOpts,
+
+    #[structopt(flatten)]
+    pub fix: FixOpts,
+}
+
+impl From<CheckCommandOpts> for CheckOpts {
+    fn from(opts: CheckCommandOpts) -> Self {
+        CheckOpts {
+            fix: opts.fix,
+            ..opts.check
+        }
+    }
 }
 
 pub fn run(opts: CheckOpts) -> i32 {
@@ -64,116 +139,698 @@ pub fn run(opts: CheckOpts) -> i32 {
 }
 
 pub(crate) fn handle_opts(opts: CheckOpts) -> Result<usize> {
-    let mut rule_map = HashMap::<ruleset::Language, Vec<Rule>>::new();
+    // the configuration is looked up from the target, so that the target is decided first
+    let target_path = opts.target_path.or(opts.target);
+    let config = discover_config(&target_path)?;
 
-    let rulesets = ruleset::from_path(&opts.ruleset_path).map_err(|e| {
-        anyhow!(
-            "failed to load ruleset file {}: {}",
-            opts.ruleset_path.as_os_str().to_string_lossy(),
-            e
-        )
-    })?;
+    let (ruleset_paths, target_path) = resolve_paths(
+        opts.ruleset_path,
+        target_path,
+        config
+            .as_ref()
+            .map(|c| c.rulesets.as_slice())
+            .unwrap_or(&[]),
+    )?;
 
-    for ruleset in rulesets {
-        for rule in ruleset.rules {
-            if let Some(v) = rule_map.get_mut(&rule.language) {
-                v.push(rule);
-            } else {
-                rule_map.insert(rule.language, vec![rule]);
-            }
+    let policy = ReportPolicy::new(&opts.report, config.as_ref());
+
+    let mut rules = vec![];
+    let mut errors = RuleSetErrors::default();
+    for ruleset_path in ruleset_paths {
+        // all the broken rules are reported at once so that they can be fixed at once
+        match ruleset::load(&ruleset_path, LoadMode::CollectErrors) {
+            Ok(rulesets) => rules.extend(rulesets.into_iter().flat_map(|r| r.rules)),
+            Err(e) => errors.0.extend(e.0),
         }
     }
+    if !errors.0.is_empty() {
+        return Err(anyhow!("failed to load rule sets:\n{}", errors));
+    }
+    // rules are filtered by severity in advance so that they are not compiled needlessly
+    let rule_map = CompiledRuleSet::new(
+        rules
+            .into_iter()
+            .filter(|r| r.get_severity() >= policy.min_severity),
+    )?
+    .with_step_limit(max_match_steps(&opts.matching));
+    // without --verbose, rules which cannot be translated are reported only when Terraform JSON files are checked
+    if opts.common.is_verbose() {
+        warn_untranslated(&rule_map);
+    }
+
+    let mut fixer = build_fixer(&opts.fix, &target_path)?;
+    let mut baseline = build_baseline(&opts.baseline, config.as_ref(), &target_path)?;
+    let mut loader = build_loader(config.as_ref(), opts.exclude, opts.encoding)?
+        .with_ignore(!opts.traversal.no_ignore)
+        .with_max_file_size(max_file_size(&opts.traversal))
+        .with_verbose(opts.common.is_verbose());
+    if let Some(ref base) = opts.diff_base {
+        let target_path = target_path
+            .as_ref()
+            .ok_or(anyhow!("--diff-base cannot be used with stdin"))?;
+        loader = loader.with_changes(ChangeSet::from_git(base, target_path)?);
+    }
+
+    let cache = build_cache(&opts.cache, config.as_ref())?;
+    let profiler = build_profiler(&opts.profile);
+    let context = ScanContext {
+        policy: &policy,
+        rule_map: &rule_map,
+        cache: cache.as_ref(),
+        profiler: profiler.as_ref(),
+        untranslated_warning: (!opts.common.is_verbose()).then(Once::new),
+    };
+    let jobs = jobs(opts.jobs);
 
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
-    match opts.report.format {
+    let mut stdout = std::io::stdout();
+    let total_findings = match policy.format {
         ReporterType::JSON => handle_rulemap(
-            JSONReporter::new(&mut stdout),
-            opts.target_path,
-            opts.exclude,
-            opts.encoding,
-            rule_map,
+            ScanState {
+                reporter: JSONReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: baseline.as_mut(),
+            },
+            target_path,
+            loader,
+            &context,
+            jobs,
         ),
         ReporterType::Console => handle_rulemap(
-            ConsoleReporter::new(&mut stdout),
-            opts.target_path,
-            opts.exclude,
-            opts.encoding,
-            rule_map,
+            ScanState {
+                reporter: ConsoleReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: baseline.as_mut(),
+            },
+            target_path,
+            loader,
+            &context,
+            jobs,
         ),
         ReporterType::SARIF => handle_rulemap(
-            SARIFReporter::new(&mut stdout),
-            opts.target_path,
-            opts.exclude,
-            opts.encoding,
-            rule_map,
+            ScanState {
+                reporter: SARIFReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: baseline.as_mut(),
+            },
+            target_path,
+            loader,
+            &context,
+            jobs,
         ),
+        ReporterType::Patch => handle_rulemap(
+            ScanState {
+                reporter: PatchReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: baseline.as_mut(),
+            },
+            target_path,
+            loader,
+            &context,
+            jobs,
+        ),
+    };
+
+    if let Some(ref profiler) = profiler {
+        report_profile(profiler, &opts.profile)?;
+    }
+    if let Some(ref cache) = cache {
+        if opts.cache.prune_cache {
+            let removed = cache.prune()?;
+            eprintln!("{} unused cache entry(ies) were pruned", removed);
+        }
+        if opts.common.is_verbose() {
+            cache.print_summary();
+        }
     }
+    if opts.common.is_verbose() {
+        print_prefilter_stats(&rule_map);
+    }
+    total_findings
+}
+
+pub(crate) fn print_prefilter_stats(rule_map: &CompiledRuleSet) {
+    let (checked, skipped) = rule_map.prefilter_stats();
+    eprintln!(
+        "{} of {} pair(s) of a rule and a file were skipped since the file lacks literals required by the rule",
+        skipped, checked
+    );
+}
+
+/// `ReportPolicy` decides how findings are reported and which of them fail the check.
+#[derive(Debug)]
+pub(crate) struct ReportPolicy {
+    pub format: ReporterType,
+    pub report_suppressed: bool,
+    pub min_severity: Severity,
+    pub fail_severity: Severity,
 }
 
-pub(crate) fn handle_rulemap<'a>(
-    mut reporter: impl Reporter<'a>,
+impl ReportPolicy {
+    /// `new` merges options given in the command line and the project configuration; the former take precedence.
+    pub fn new(opts: &ReportOpts, config: Option<&Config>) -> Self {
+        ReportPolicy {
+            format: opts
+                .format
+                .or(config.and_then(|c| c.format))
+                .unwrap_or(ReporterType::Console),
+            report_suppressed: opts.report_suppressed,
+            min_severity: opts
+                .min_severity
+                .or(config.and_then(|c| c.min_severity))
+                .unwrap_or(Severity::Unknown),
+            fail_severity: opts
+                .fail_severity
+                .or(config.and_then(|c| c.fail_severity))
+                .unwrap_or(Severity::Unknown),
+        }
+    }
+}
+
+/// `resolve_paths` decides rule sets and the target from the arguments and `rulesets` in the project configuration.
+/// A rule set given in the command line always takes precedence over `rulesets`, so the meaning of the arguments never depends on the configuration.
+fn resolve_paths(
+    ruleset_path: Option<PathBuf>,
     target_path: Option<PathBuf>,
+    config_rulesets: &[PathBuf],
+) -> Result<(Vec<PathBuf>, Option<PathBuf>)> {
+    match (ruleset_path, target_path) {
+        (Some(r), t) => Ok((vec![r], t)),
+        // when rule sets come from the configuration without any target, the current directory is checked
+        (None, t) if !config_rulesets.is_empty() => Ok((
+            config_rulesets.to_vec(),
+            Some(t.unwrap_or_else(|| PathBuf::from("."))),
+        )),
+        (None, _) => Err(anyhow!(
+            "no ruleset was given; pass a path to rule sets or declare `rulesets` in {}",
+            CONFIG_FILE_NAME
+        )),
+    }
+}
 
-    exclude_path_pattern: Vec<String>,
+/// `discover_config` looks for the project configuration file from the target path, or the current directory for stdin.
+pub(crate) fn discover_config(target_path: &Option<PathBuf>) -> Result<Option<Config>> {
+    match target_path {
+        Some(p) => Config::discover(p),
+        None => Config::discover(&std::env::current_dir()?),
+    }
+}
+
+pub(crate) fn build_loader(
+    config: Option<&Config>,
+    exclude: Vec<String>,
     encoding: Option<&'static Encoding>,
-    rule_map: HashMap<ruleset::Language, Vec<Rule>>,
+) -> Result<TargetLoader> {
+    let config = match config {
+        Some(config) => config,
+        None => return TargetLoader::new(exclude, encoding),
+    };
+
+    // `--exclude` given in the command line replaces `exclude` in the configuration
+    let use_config_exclude = exclude.is_empty();
+    let mut loader = TargetLoader::new(exclude, encoding)?
+        .with_includes(&config.root, &config.include)?
+        .with_encodings(&config.root, &config.encodings)?
+        .with_languages(config.languages.clone());
+    if use_config_exclude {
+        loader = loader.with_excludes(&config.root, &config.exclude)?;
+    }
+    Ok(loader)
+}
+
+/// `jobs` decides the number of workers from `--jobs`, which defaults to the number of CPUs.
+pub(crate) fn jobs(jobs: Option<usize>) -> usize {
+    jobs.or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .max(1)
+}
+
+/// `max_match_steps` converts `--max-match-steps`, where 0 means no limit.
+pub(crate) fn max_match_steps(opts: &MatchOpts) -> Option<usize> {
+    match opts.max_match_steps {
+        0 => None,
+        steps => Some(steps),
+    }
+}
+
+/// `max_file_size` converts `--max-file-size`, where 0 means no limit.
+pub(crate) fn max_file_size(opts: &TraversalOpts) -> Option<u64> {
+    match opts.max_file_size {
+        0 => None,
+        size => Some(size),
+    }
+}
+
+pub(crate) fn build_fixer(opts: &FixOpts, target_path: &Option<PathBuf>) -> Result<Option<Fixer>> {
+    if !opts.apply {
+        return Ok(None);
+    }
+    if target_path.is_none() {
+        return Err(anyhow!("changes cannot be applied to stdin"));
+    }
+    Ok(Some(Fixer::new(
+        opts.rewrite_option,
+        opts.backup,
+        opts.dry_run,
+    )?))
+}
+
+/// `build_cache` opens the cache directory given in the command line or the configuration, unless `--no-cache` is given.
+pub(crate) fn build_cache(
+    opts: &CacheOpts,
+    config: Option<&Config>,
+) -> Result<Option<ResultCache>> {
+    let dir = match opts
+        .cache_dir
+        .clone()
+        .or_else(|| config.and_then(|c| c.cache_dir.clone()))
+    {
+        Some(dir) if !opts.no_cache => dir,
+        _ if opts.prune_cache => {
+            return Err(anyhow!(
+                "--prune-cache requires a cache directory; pass --cache-dir or declare `cache-dir` in {}",
+                CONFIG_FILE_NAME
+            ))
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(ResultCache::new(&dir)?))
+}
+
+pub(crate) fn build_profiler(opts: &ProfileOpts) -> Option<Profiler> {
+    if opts.profile || opts.profile_output.is_some() {
+        Some(Profiler::default())
+    } else {
+        None
+    }
+}
+
+/// `report_profile` prints the summary of the profile and saves the whole profile as requested.
+pub(crate) fn report_profile(profiler: &Profiler, opts: &ProfileOpts) -> Result<()> {
+    if opts.profile {
+        profiler.print_summary();
+    }
+    match opts.profile_output {
+        Some(ref output) => profiler.write(output),
+        None => Ok(()),
+    }
+}
+
+/// `build_baseline` loads the baseline, whose paths are relative to the project root.
+/// The root is the directory of the project configuration file, the git working tree containing the target, or the current directory in this order.
+pub(crate) fn build_baseline(
+    opts: &BaselineOpts,
+    config: Option<&Config>,
+    target_path: &Option<PathBuf>,
+) -> Result<Option<Baseline>> {
+    if opts.baseline.is_none() && opts.write_baseline.is_none() {
+        return Ok(None);
+    }
+    let cwd = std::env::current_dir()?;
+    let root = match config {
+        Some(config) => config.root.clone(),
+        None => repository_root(target_path.as_deref().unwrap_or(&cwd)).unwrap_or(cwd),
+    };
+    Ok(Some(Baseline::new(
+        opts.baseline.as_deref(),
+        opts.write_baseline.clone(),
+        &root,
+    )?))
+}
+
+/// `ScanContext` holds what workers checking targets refer to.
+pub(crate) struct ScanContext<'c> {
+    pub policy: &'c ReportPolicy,
+    pub rule_map: &'c CompiledRuleSet,
+    pub cache: Option<&'c ResultCache>,
+    pub profiler: Option<&'c Profiler>,
+    /// set when rules not applied to Terraform JSON files are to be reported on the first Terraform JSON target
+    pub untranslated_warning: Option<Once>,
+}
+
+impl<'c> ScanContext<'c> {
+    /// `enter_language` is called before rules for `language` are applied to a target.
+    fn enter_language(&self, language: Language) {
+        if let (Language::TerraformJSON, Some(once)) = (language, &self.untranslated_warning) {
+            once.call_once(|| warn_untranslated(self.rule_map));
+        }
+    }
+}
+
+/// `warn_untranslated` warns about rules for HCL which are not applied to Terraform JSON files.
+fn warn_untranslated(rule_map: &CompiledRuleSet) {
+    for (id, e) in rule_map.untranslated() {
+        eprintln!(
+            "{}: rule {} is not applied to Terraform JSON files: {}",
+            Color::Yellow.paint("warning"),
+            id,
+            e
+        );
+    }
+}
+
+/// `ScanState` is the state shared by workers checking targets.
+pub(crate) struct ScanState<'f, E> {
+    pub reporter: E,
+    pub fixer: Option<&'f mut Fixer>,
+    pub baseline: Option<&'f mut Baseline>,
+}
+
+pub(crate) fn handle_rulemap<'a, E: Reporter<'a> + Send>(
+    state: ScanState<'_, E>,
+    target_path: Option<PathBuf>,
+    loader: TargetLoader,
+    context: &ScanContext,
+    jobs: usize,
 ) -> Result<usize> {
-    let mut total_findings = 0;
-    let loader = TargetLoader::new(exclude_path_pattern, encoding)?;
-    match target_path {
+    // targets are checked in parallel, but the state is updated in the order of targets so that outputs are reproducible
+    let sink = OrderedSink::new(state);
+    let total_findings = match target_path {
         Some(p) => {
-            for target in loader.from(p)? {
-                if let Some(lang) = target.language() {
-                    if let Some(rules) = rule_map.get(&lang) {
-                        total_findings += handle_rules(&mut reporter, &target, rules, &lang)?;
-                    }
-                }
-            }
+            let ignore_load_errors = p.is_dir();
+            let paths = loader.paths(p);
+            let next = AtomicUsize::new(0);
+            let failed = AtomicBool::new(false);
+
+            std::thread::scope(|s| {
+                let workers = (0..jobs.clamp(1, paths.len().max(1)))
+                    .map(|_| {
+                        s.spawn(|| -> Result<usize> {
+                            let mut findings = 0;
+                            while !failed.load(Ordering::SeqCst) {
+                                let idx = next.fetch_add(1, Ordering::SeqCst);
+                                let p = match paths.get(idx) {
+                                    Some(p) => p,
+                                    None => break,
+                                };
+
+                                let _turn = AbandonOnDrop(&sink, idx);
+                                let r = match loader.load(p.clone()) {
+                                    Ok(Some(target)) => handle_target(&sink, idx, &target, context),
+                                    Err(e) if !ignore_load_errors => Err(e),
+                                    _ => Ok(0),
+                                };
+                                match r {
+                                    Ok(n) => findings += n,
+                                    Err(e) => {
+                                        failed.store(true, Ordering::SeqCst);
+                                        return Err(e);
+                                    }
+                                }
+                            }
+                            Ok(findings)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                workers
+                    .into_iter()
+                    .map(|w| w.join().unwrap())
+                    .sum::<Result<usize>>()
+            })?
         }
         _ => {
             let target = loader.from_reader(std::io::stdin())?;
-            for (lang, rules) in rule_map {
-                total_findings += handle_rules(&mut reporter, &target, &rules, &lang)?;
+            let mut total_findings = 0;
+            for (idx, (language, rules)) in context.rule_map.iter().enumerate() {
+                context.enter_language(*language);
+                total_findings += handle_rules(&sink, idx, &target, rules, context)?;
             }
+            total_findings
         }
-    }
+    };
 
+    let state = sink.into_inner();
+    let mut reporter = state.reporter;
     reporter.report()?;
+    if let Some(baseline) = state.baseline {
+        baseline.write()?;
+        baseline.print_summary();
+    }
+    if let Some(fixer) = state.fixer {
+        fixer.print_summary();
+    }
     Ok(total_findings)
 }
 
-fn handle_rules<'a, E: Reporter<'a>>(
-    reporter: &mut E,
+/// `AbandonOnDrop` gives up a turn unless it has been taken, e.g. when the target was skipped or the check failed.
+struct AbandonOnDrop<'s, S>(&'s OrderedSink<S>, usize);
+
+impl<'s, S> Drop for AbandonOnDrop<'s, S> {
+    fn drop(&mut self) {
+        self.0.abandon(self.1);
+    }
+}
+
+fn handle_target<'a, E: Reporter<'a>>(
+    sink: &OrderedSink<ScanState<'_, E>>,
+    idx: usize,
     target: &Target,
-    rules: &[Rule],
-    as_lang: &ruleset::Language,
+    context: &ScanContext,
 ) -> Result<usize> {
-    match as_lang {
-        ruleset::Language::HCL => handle_typed_rules::<E, HCL>(reporter, target, rules),
-        ruleset::Language::Dockerfile => {
-            handle_typed_rules::<E, Dockerfile>(reporter, target, rules)
+    let language = match target.language() {
+        Some(language) => language,
+        None => return Ok(0),
+    };
+    match context.rule_map.get(&language) {
+        Some(rules) => {
+            context.enter_language(language);
+            handle_rules(sink, idx, target, rules, context)
         }
-        ruleset::Language::Go => handle_typed_rules::<E, Go>(reporter, target, rules),
+        None => Ok(0),
     }
 }
 
+fn handle_rules<'a, E: Reporter<'a>>(
+    sink: &OrderedSink<ScanState<'_, E>>,
+    idx: usize,
+    target: &Target,
+    rules: &CompiledRules,
+    context: &ScanContext,
+) -> Result<usize> {
+    dispatch_language!(rules, |rules: Lang| {
+        handle_typed_rules::<E, Lang>(sink, idx, target, rules, context)
+    })
+}
+
 fn handle_typed_rules<'a, E: Reporter<'a>, Lang: Queryable>(
-    reporter: &mut E,
+    sink: &OrderedSink<ScanState<'_, E>>,
+    idx: usize,
     target: &Target,
-    rules: &[Rule],
+    rules: &CompiledRuleGroup<Lang>,
+    context: &ScanContext,
 ) -> Result<usize> {
+    let policy = context.policy;
+    let candidates = rules.candidates(&target.body);
+    if candidates.is_empty() {
+        // no rule can match the target, so parsing it is also skipped;
+        // syntax errors and invalid suppressions in it are not reported either, as documented in "Skipping Files"
+        sink.abandon(idx);
+        return Ok(0);
+    }
+
+    let mut cached = context
+        .cache
+        .map(|c| c.load(rules.language(), &target.body));
+    let answered = cached
+        .as_ref()
+        .and_then(|c| c.answer_without_parsing(candidates.iter().map(|r| r.digest())));
+    if let Some(outcome) = answered {
+        // none of the rules found anything in the same content before, so parsing it is also skipped
+        return sink.with(idx, |state| {
+            report_parse_outcome(&mut state.reporter, target, outcome)?;
+            Ok(0)
+        });
+    }
+
+    let started = Instant::now();
     let source = NormalizedSource::from(target.body.as_str());
     let tree = Tree::<Lang>::try_from(source).unwrap();
     let ptree = NormalizedTree::from(&tree);
-    let ptree = ptree.as_ref_treeview();
+    if let Some(profiler) = context.profiler {
+        profiler.record_parse(target.relative_path(), started.elapsed());
+    }
+    let ptree = ptree.as_ref_treeview().with_budget(rules.budget());
 
-    let mut total_findings = 0;
-    for rule in rules {
-        let findings = rule.find::<Lang>(&ptree)?;
-        total_findings += findings.len();
-        reporter.add_entry::<Lang>(target, repeat(rule).zip(findings).collect())?;
+    let mut entries = vec![];
+    let mut aborted = vec![];
+    let mut paths = None;
+    for rule in candidates {
+        let started = Instant::now();
+        let found = match cached
+            .as_ref()
+            .and_then(|c| c.findings(rule.digest(), ptree.view_root))
+        {
+            Some(findings) => Ok(findings),
+            None => {
+                let found = rule.find(&ptree);
+                if let (Some(cached), Ok(findings)) = (&mut cached, &found) {
+                    let paths = paths.get_or_insert_with(|| NodePaths::new(ptree.view_root));
+                    cached.record(rule.digest(), findings, paths);
+                }
+                found
+            }
+        };
+        if let (Some(profiler), Ok(findings)) = (context.profiler, &found) {
+            profiler.record_match(
+                &rule.rule.id,
+                target.relative_path(),
+                started.elapsed(),
+                findings.len(),
+            );
+        }
+
+        match found {
+            Ok(findings) => entries.extend(repeat(rule).zip(findings)),
+            Err(e) => match e.downcast::<StepLimitExceeded>() {
+                // an aborted rule should not stop checking the other rules and targets
+                Ok(e) => aborted.push((&rule.rule, e)),
+                Err(e) => return Err(e),
+            },
+        }
     }
 
-    Ok(total_findings)
+    let suppressions = find_suppressions(&ptree);
+    let outcome = ParseOutcome {
+        invalid_suppressions: suppressions
+            .iter()
+            .filter(|s| !s.is_valid())
+            .map(|s| s.start_row)
+            .collect(),
+        syntax_error: tree.syntax_error(),
+    };
+    if let Some(mut cached) = cached {
+        cached.record_parse_outcome(outcome.clone());
+        cached.store()?;
+    }
+    let (suppressed, entries): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        // findings out of changed lines are dropped whether they are suppressed or not
+        .filter(|(_, mitem)| {
+            target.is_changed(
+                mitem.area.start_position().row,
+                mitem.area.end_position().row,
+            )
+        })
+        .map(|(rule, mitem)| {
+            let row = mitem.area.start_position().row;
+            let suppression = suppressions
+                .iter()
+                .find(|s| s.suppresses(&rule.rule.id, row));
+            (rule, mitem, suppression)
+        })
+        .partition(|(_, _, suppression)| suppression.is_some());
+    let entries = entries
+        .into_iter()
+        .map(|(rule, mitem, _)| (rule, mitem))
+        .collect::<Vec<_>>();
+
+    sink.with(idx, |state| {
+        for (rule, e) in aborted {
+            eprintln!(
+                "{}: rule {} on {}: {}; its findings on the file were dropped (see --max-match-steps)",
+                Color::Yellow.paint("warning"),
+                rule.id,
+                target.relative_path(),
+                e
+            );
+        }
+        report_parse_outcome(&mut state.reporter, target, &outcome)?;
+
+        let entries = match state.baseline {
+            Some(ref mut baseline) => baseline.filter(target, entries)?,
+            None => entries,
+        };
+        let resolved = match state.fixer {
+            Some(ref mut fixer) => fixer.fix::<Lang>(target, &entries)?,
+            None => vec![false; entries.len()],
+        };
+        let failed_findings = entries
+            .iter()
+            .zip(resolved)
+            .filter(|((rule, _), resolved)| {
+                !resolved && rule.rule.get_severity() >= policy.fail_severity
+            })
+            .count();
+        state.reporter.add_entry::<Lang>(target, entries)?;
+        if policy.report_suppressed {
+            state.reporter.add_suppressed_entry::<Lang>(
+                target,
+                suppressed
+                    .into_iter()
+                    .filter_map(|(rule, mitem, suppression)| Some((rule, mitem, suppression?)))
+                    .collect(),
+            )?;
+        }
+
+        Ok(failed_findings)
+    })
+}
+
+/// `report_parse_outcome` warns about suppression comments without reasons and code which could not be parsed in `target`.
+fn report_parse_outcome<'a, E: Reporter<'a>>(
+    reporter: &mut E,
+    target: &Target,
+    outcome: &ParseOutcome,
+) -> Result<()> {
+    for row in &outcome.invalid_suppressions {
+        eprintln!(
+            "{}: {}:{}: {} without a reason (`-- <reason>`) was ignored",
+            Color::Yellow.paint("warning"),
+            target.relative_path(),
+            row + 1,
+            SUPPRESSION_MARKER
+        );
+    }
+    if let Some(ref e) = outcome.syntax_error {
+        eprintln!(
+            "{}: {}:{}:{}: failed to parse a part of the file, so findings in it may be missed\n{}",
+            Color::Yellow.paint("warning"),
+            target.relative_path(),
+            e.row + 1,
+            e.column + 1,
+            e.snippet
+        );
+        reporter.add_parse_warning(target, e)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_paths() {
+        let configured = vec![PathBuf::from("/project/policies")];
+        let path = |p: &str| Some(PathBuf::from(p));
+
+        // without rule sets in the configuration, positional arguments are a rule set and a target
+        assert_eq!(
+            resolve_paths(path("rules.yaml"), path("main.tf"), &[]).unwrap(),
+            (vec![PathBuf::from("rules.yaml")], path("main.tf"))
+        );
+        assert_eq!(
+            resolve_paths(path("rules.yaml"), None, &[]).unwrap(),
+            (vec![PathBuf::from("rules.yaml")], None)
+        );
+        assert!(resolve_paths(None, None, &[]).is_err());
+
+        // with rule sets in the configuration, a single positional argument is still a rule set
+        assert_eq!(
+            resolve_paths(path("rules.yaml"), None, &configured).unwrap(),
+            (vec![PathBuf::from("rules.yaml")], None)
+        );
+        assert_eq!(
+            resolve_paths(None, path("./terraform"), &configured).unwrap(),
+            (configured.clone(), path("./terraform"))
+        );
+        assert!(resolve_paths(None, path("./terraform"), &[]).is_err());
+        assert_eq!(
+            resolve_paths(None, None, &configured).unwrap(),
+            (configured.clone(), path("."))
+        );
+        assert_eq!(
+            resolve_paths(path("rules.yaml"), path("main.tf"), &configured).unwrap(),
+            (vec![PathBuf::from("rules.yaml")], path("main.tf"))
+        );
+    }
 }
@@ -1,13 +1,22 @@
 //! This module defines `check` subcommand.
 
 use crate::cli::encoding::{parse_encoding, LABELS_SORTED};
-use crate::cli::reporter::{ConsoleReporter, JSONReporter, Reporter, ReporterType, SARIFReporter};
-use crate::cli::{subcommand::check::handle_rulemap, CommonOpts, ReportOpts};
-use crate::core::ruleset::{self, RawPatternWithConstraints, Rule};
+use crate::cli::reporter::{
+    ConsoleReporter, JSONReporter, PatchReporter, Reporter, ReporterType, SARIFReporter,
+};
+use crate::cli::{
+    subcommand::check::{
+        build_fixer, build_loader, build_profiler, discover_config, handle_rulemap, jobs,
+        max_file_size, max_match_steps, print_prefilter_stats, report_profile, ReportPolicy,
+        ScanContext, ScanState,
+    },
+    CommonOpts, FixOpts, MatchOpts, ProfileOpts, ReportOpts, TraversalOpts,
+};
+use crate::core::ruleset::{self, compiled::CompiledRuleSet, RawPatternWithConstraints, Rule};
 use ansi_term::Color;
 use anyhow::Result;
 use encoding_rs::Encoding;
-use std::{array::IntoIter, collections::HashMap, path::PathBuf};
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 /// Checks files with a pattern given in command line arguments
@@ -41,8 +50,24 @@ pub struct FindOpts {
     #[structopt(flatten)]
     pub report: ReportOpts,
 
+    #[structopt(flatten)]
+    pub fix: FixOpts,
+
     #[structopt(long)]
     pub exclude: Vec<String>,
+
+    #[structopt(flatten)]
+    pub traversal: TraversalOpts,
+
+    #[structopt(flatten)]
+    pub matching: MatchOpts,
+
+    #[structopt(flatten)]
+    pub profile: ProfileOpts,
+
+    /// Number of files checked in parallel [default: the number of CPUs]
+    #[structopt(short, long)]
+    pub jobs: Option<usize>,
 }
 
 pub fn run(opts: FindOpts) -> i32 {
@@ -69,38 +94,86 @@ fn handle_opts(opts: FindOpts) -> Result<usize> {
         "matched with the given rule".into(),
         vec![RawPatternWithConstraints {
             pattern: opts.pattern,
-            constraints: vec![],
+            ..Default::default()
         }],
         opts.rewrite.map_or(vec![], |x| vec![x]),
         vec![],
     );
 
     let rule_map =
-        IntoIter::new([(opts.lang, vec![rule])]).collect::<HashMap<ruleset::Language, Vec<Rule>>>();
+        CompiledRuleSet::new(vec![rule])?.with_step_limit(max_match_steps(&opts.matching));
+
+    let config = discover_config(&opts.target_path)?;
+    let mut fixer = build_fixer(&opts.fix, &opts.target_path)?;
+    let loader = build_loader(config.as_ref(), opts.exclude, opts.encoding)?
+        .with_ignore(!opts.traversal.no_ignore)
+        .with_max_file_size(max_file_size(&opts.traversal))
+        .with_verbose(opts.common.is_verbose());
+    let policy = ReportPolicy::new(&opts.report, config.as_ref());
+
+    let profiler = build_profiler(&opts.profile);
+    let context = ScanContext {
+        policy: &policy,
+        rule_map: &rule_map,
+        cache: None,
+        profiler: profiler.as_ref(),
+        untranslated_warning: None,
+    };
+    let jobs = jobs(opts.jobs);
 
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
-    match opts.report.format {
+    let mut stdout = std::io::stdout();
+    let total_findings = match policy.format {
         ReporterType::JSON => handle_rulemap(
-            JSONReporter::new(&mut stdout),
+            ScanState {
+                reporter: JSONReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: None,
+            },
             opts.target_path,
-            opts.exclude,
-            opts.encoding,
-            rule_map,
+            loader,
+            &context,
+            jobs,
         ),
         ReporterType::Console => handle_rulemap(
-            ConsoleReporter::new(&mut stdout),
+            ScanState {
+                reporter: ConsoleReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: None,
+            },
             opts.target_path,
-            opts.exclude,
-            opts.encoding,
-            rule_map,
+            loader,
+            &context,
+            jobs,
         ),
         ReporterType::SARIF => handle_rulemap(
-            SARIFReporter::new(&mut stdout),
+            ScanState {
+                reporter: SARIFReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: None,
+            },
             opts.target_path,
-            opts.exclude,
-            opts.encoding,
-            rule_map,
+            loader,
+            &context,
+            jobs,
         ),
+        ReporterType::Patch => handle_rulemap(
+            ScanState {
+                reporter: PatchReporter::new(&mut stdout),
+                fixer: fixer.as_mut(),
+                baseline: None,
+            },
+            opts.target_path,
+            loader,
+            &context,
+            jobs,
+        ),
+    };
+
+    if let Some(ref profiler) = profiler {
+        report_profile(profiler, &opts.profile)?;
+    }
+    if opts.common.is_verbose() {
+        print_prefilter_stats(&rule_map);
     }
+    total_findings
 }
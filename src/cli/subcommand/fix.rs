@@ -0,0 +1,23 @@
+//! This module defines `fix` subcommand.
+
+use super::check::{self, CheckOpts};
+use crate::cli::ApplyOpts;
+use structopt::StructOpt;
+
+/// `FixCommandOpts` is options of `fix` subcommand, which takes the same options as `check` except `--apply`.
+#[derive(StructOpt, Debug)]
+pub struct FixCommandOpts {
+    #[structopt(flatten)]
+    pub check: CheckOpts,
+
+    #[structopt(flatten)]
+    pub apply: ApplyOpts,
+}
+
+/// `run` behaves as same as `check --apply`.
+pub fn run(opts: FixCommandOpts) -> i32 {
+    check::run(CheckOpts {
+        fix: opts.apply.into(),
+        ..opts.check
+    })
+}
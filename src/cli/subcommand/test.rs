@@ -0,0 +1,600 @@
+//! This module defines `test` subcommand.
+//!
+//! Fixtures of a rule file `<name>.yaml` are the files named `<name>.*` in the same directory (e.g. `<name>.tf` or `<name>.test.Dockerfile`).
+//! Comments in a fixture declare which rules should or should not match the next line:
+//!
+//! ```hcl
+//! # shisho: expect unencrypted-ebs
+//! encrypted = false
+//! # shisho: ok unencrypted-ebs
+//! encrypted = true
+//! ```
+//!
+//! If `<name>.fixed.<ext>` exists next to a fixture `<name>.<ext>`, the fixture rewritten with the first rewrite option of each finding must be equal to it.
+
+use crate::cli::CommonOpts;
+use crate::core::{
+    language::Queryable,
+    rewriter::{Edit, EditSet},
+    ruleset::{
+        self,
+        compiled::{CompiledRuleGroup, CompiledRuleSet},
+    },
+    source::{Code, NormalizedSource},
+    suppression::find_suppressions,
+    target::Target,
+    tree::{NormalizedTree, Tree},
+};
+use crate::dispatch_language;
+use ansi_term::Color;
+use anyhow::{anyhow, Result};
+use similar::TextDiff;
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use walkdir::WalkDir;
+
+/// Runs rules against fixtures placed next to rule files and checks expectations annotated in them
+#[derive(StructOpt, Debug)]
+pub struct TestOpts {
+    /// Rule set files or directories containing them [default: the current directory]
+    #[structopt(parse(from_os_str))]
+    pub paths: Vec<PathBuf>,
+
+    #[structopt(flatten)]
+    pub common: CommonOpts,
+}
+
+pub fn run(opts: TestOpts) -> i32 {
+    match handle_opts(opts) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("{}: {}", Color::Red.paint("error"), e);
+            1
+        }
+    }
+}
+
+/// `handle_opts` runs all the fixtures and returns whether all of them passed.
+fn handle_opts(opts: TestOpts) -> Result<bool> {
+    let paths = if opts.paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        opts.paths
+    };
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for ruleset_path in paths.iter().flat_map(|p| rule_files(p)) {
+        let fixtures = fixtures_of(&ruleset_path)?;
+        if fixtures.is_empty() {
+            if opts.common.is_verbose() {
+                eprintln!(
+                    "{}: no fixture was found for {}",
+                    Color::Yellow.paint("skipped"),
+                    ruleset_path.to_string_lossy()
+                );
+            }
+            continue;
+        }
+
+        let rules = ruleset::from_filepath(&ruleset_path)
+            .map_err(|e| {
+                anyhow!(
+                    "failed to load ruleset file {}: {}",
+                    ruleset_path.to_string_lossy(),
+                    e
+                )
+            })?
+            .rules;
+        let rule_ids = rules.iter().map(|r| r.id.clone()).collect::<BTreeSet<_>>();
+        let rule_map = CompiledRuleSet::new(rules)?;
+
+        for fixture in fixtures {
+            let failures = run_fixture(&fixture, &rule_map, &rule_ids)?;
+            let name = format!(
+                "{} > {}",
+                ruleset_path.to_string_lossy(),
+                fixture.file_name().unwrap_or_default().to_string_lossy()
+            );
+            if failures.is_empty() {
+                passed += 1;
+                println!("{} {}", Color::Green.paint("PASS"), name);
+            } else {
+                failed += 1;
+                println!("{} {}", Color::Red.paint("FAIL"), name);
+                for f in failures {
+                    println!("    {}", f.trim_end().replace('\n', "\n    "));
+                }
+            }
+        }
+    }
+
+    if passed + failed == 0 {
+        return Err(anyhow!("no fixture was found next to the rule files"));
+    }
+    println!("{} fixture(s) passed, {} failed", passed, failed);
+    Ok(failed == 0)
+}
+
+/// `rule_files` lists rule files in `path`.
+/// YAML files named after another rule file in the same directory are fixtures rather than rule files, unless they are rule sets themselves.
+fn rule_files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+
+    let yaml_files = WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            p.is_file()
+                && matches!(
+                    p.extension().and_then(|e| e.to_str()),
+                    Some("yaml") | Some("yml")
+                )
+        })
+        .collect::<Vec<PathBuf>>();
+    yaml_files
+        .iter()
+        .filter(|p| {
+            is_ruleset_file(p)
+                || !yaml_files
+                    .iter()
+                    .any(|other| other != *p && is_fixture_of(p, other))
+        })
+        .cloned()
+        .collect()
+}
+
+/// `is_fixture_of` returns whether `p` is a fixture of the rule file `ruleset_path`, including expected outputs of rewrites.
+fn is_fixture_of(p: &Path, ruleset_path: &Path) -> bool {
+    let (stem, name) = match (
+        ruleset_path.file_stem().and_then(|s| s.to_str()),
+        p.file_name().and_then(|s| s.to_str()),
+    ) {
+        (Some(stem), Some(name)) => (stem, name),
+        _ => return false,
+    };
+    p.parent() == ruleset_path.parent() && name.starts_with(&format!("{}.", stem))
+}
+
+/// `is_ruleset_file` returns whether the file at `p` is a rule set, which has `version` and `rules` at the top level.
+/// It tells rule files such as `s3.public.yaml` from YAML fixtures of `s3.yaml`.
+fn is_ruleset_file(p: &Path) -> bool {
+    std::fs::read_to_string(p)
+        .ok()
+        .and_then(|body| serde_yaml::from_str::<serde_yaml::Value>(&body).ok())
+        .map(|v| v.get("version").is_some() && v.get("rules").is_some())
+        .unwrap_or(false)
+}
+
+fn is_fixed_file(p: &Path) -> bool {
+    p.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.split('.').any(|s| s == "fixed"))
+        .unwrap_or(false)
+}
+
+/// `fixed_file_of` returns the path of the expected output of rewrites on `fixture`: `<name>.fixed.<ext>` for `<name>.<ext>`.
+fn fixed_file_of(fixture: &Path) -> Option<PathBuf> {
+    let name = fixture.file_name()?.to_str()?;
+    let (stem, ext) = name.rsplit_once('.')?;
+    Some(fixture.with_file_name(format!("{}.fixed.{}", stem, ext)))
+}
+
+fn fixtures_of(ruleset_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = match ruleset_path.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Path::new("."),
+        Some(dir) => dir,
+        None => return Ok(vec![]),
+    };
+    let mut fixtures = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.file_name() != ruleset_path.file_name()
+                && is_fixture_of(p, &dir.join(ruleset_path.file_name().unwrap_or_default()))
+                && !is_fixed_file(p)
+                && !is_ruleset_file(p)
+        })
+        .collect::<Vec<PathBuf>>();
+    fixtures.sort();
+    Ok(fixtures)
+}
+
+/// `Expectation` is an annotation in a fixture which tells whether a rule should match a row.
+#[derive(Debug, Clone, PartialEq)]
+struct Expectation {
+    /// 0-origin row which the annotation applies to
+    row: usize,
+    rule_id: String,
+    should_match: bool,
+}
+
+/// `parse_annotation` reads `shisho: expect <ids>` or `shisho: ok <ids>` in a line including comment delimiters (`#`, `//` or `/* */`).
+/// It returns `None` if the line is not an annotation.
+fn parse_annotation(line: &str) -> Option<(bool, Vec<String>)> {
+    let line = line.trim();
+    if !(line.starts_with('#') || line.starts_with("//") || line.starts_with("/*")) {
+        return None;
+    }
+    let body = line.strip_suffix("*/").unwrap_or(line);
+    let body = body
+        .trim_start_matches(|c: char| c == '#' || c == '/' || c == '*' || c.is_whitespace())
+        .strip_prefix("shisho:")?
+        .trim_start();
+
+    let (should_match, ids) = if let Some(ids) = body.strip_prefix("expect") {
+        (true, ids)
+    } else if let Some(ids) = body.strip_prefix("ok") {
+        (false, ids)
+    } else {
+        return None;
+    };
+    let ids = ids.strip_prefix(':').unwrap_or(ids);
+    if !ids.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((
+        should_match,
+        ids.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|id| !id.is_empty())
+            .map(String::from)
+            .collect(),
+    ))
+}
+
+/// `parse_expectations` collects annotations in `body`. Each annotation applies to the next line which is not an annotation.
+fn parse_expectations(body: &str) -> Vec<Expectation> {
+    let mut expectations = vec![];
+    let mut pending = vec![];
+    for (row, line) in body.split('\n').enumerate() {
+        match parse_annotation(line) {
+            Some((should_match, ids)) => {
+                pending.extend(ids.into_iter().map(|id| (should_match, id)));
+            }
+            None => {
+                expectations.extend(
+                    pending
+                        .drain(..)
+                        .map(|(should_match, rule_id)| Expectation {
+                            row,
+                            rule_id,
+                            should_match,
+                        }),
+                )
+            }
+        }
+    }
+    expectations
+}
+
+/// `run_fixture` checks the findings on `fixture` against its annotations and its expected rewrite output.
+/// It returns descriptions of failures.
+fn run_fixture(
+    fixture: &Path,
+    rule_map: &CompiledRuleSet,
+    rule_ids: &BTreeSet<String>,
+) -> Result<Vec<String>> {
+    let body = std::fs::read_to_string(fixture)?;
+    let target = Target {
+        path: Some(fixture.to_path_buf()),
+        body,
+        ..Default::default()
+    };
+    let name = fixture
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let fixed_file = fixed_file_of(fixture).filter(|p| p.is_file());
+    let expected_body = fixed_file
+        .as_ref()
+        .map(std::fs::read_to_string)
+        .transpose()?;
+
+    let (findings, rewritten, skipped) =
+        match target.language().and_then(|lang| rule_map.get(&lang)) {
+            Some(rules) => {
+                dispatch_language!(rules, |rules: Lang| run_rules::<Lang>(&target, rules)?)
+            }
+            None => (BTreeSet::new(), target.body.clone(), vec![]),
+        };
+
+    let mut failures = vec![];
+    let expectations = parse_expectations(&target.body);
+    for e in &expectations {
+        if !rule_ids.contains(&e.rule_id) {
+            failures.push(format!(
+                "{}:{}: no rule named {} is in the rule file",
+                name,
+                e.row + 1,
+                e.rule_id
+            ));
+            continue;
+        }
+        let found = findings.contains(&(e.row, e.rule_id.clone()));
+        if e.should_match && !found {
+            failures.push(format!(
+                "{}:{}: expected a finding of {}, but none was found",
+                name,
+                e.row + 1,
+                e.rule_id
+            ));
+        }
+    }
+    for (row, rule_id) in &findings {
+        let annotated = expectations
+            .iter()
+            .find(|e| e.row == *row && e.rule_id == *rule_id)
+            .map(|e| e.should_match);
+        match annotated {
+            Some(true) => (),
+            Some(false) => failures.push(format!(
+                "{}:{}: {} matched, but the line is annotated with `shisho: ok {}`",
+                name,
+                row + 1,
+                rule_id,
+                rule_id
+            )),
+            None => failures.push(format!(
+                "{}:{}: unexpected finding of {}",
+                name,
+                row + 1,
+                rule_id
+            )),
+        }
+    }
+
+    if let (Some(fixed_file), Some(expected_body)) = (fixed_file, expected_body) {
+        // the expected output cannot be reached by a single `shisho fix` if some rewrites conflict
+        for (row, rule_id, reason) in skipped {
+            failures.push(format!(
+                "{}:{}: rewrite of {} was not applied ({})",
+                name,
+                row + 1,
+                rule_id,
+                reason
+            ));
+        }
+        if rewritten != expected_body {
+            let fixed_name = fixed_file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            failures.push(format!(
+                "{}: rewritten code differs from the expected one:\n{}",
+                fixed_name,
+                TextDiff::from_lines(expected_body.as_str(), rewritten.as_str())
+                    .unified_diff()
+                    .header(&fixed_name, "actual")
+            ));
+        }
+    }
+    Ok(failures)
+}
+
+/// `RunResult` is the findings, the rewritten code and the skipped rewrites returned by `run_rules`.
+type RunResult = (
+    BTreeSet<(usize, String)>,
+    String,
+    Vec<(usize, String, &'static str)>,
+);
+
+/// `run_rules` returns the rows and rule IDs of unsuppressed findings on `target`, and the code rewritten with the first rewrite option of each finding.
+/// It also returns the rows, rule IDs and reasons of rewrites which were not applied due to conflicts with others.
+fn run_rules<T: Queryable>(target: &Target, rules: &CompiledRuleGroup<T>) -> Result<RunResult> {
+    let source = NormalizedSource::from(target.body.as_str());
+    let tree = Tree::<T>::try_from(source)
+        .map_err(|e| anyhow!("failed to parse {}: {}", target.relative_path(), e))?;
+    let ptree = NormalizedTree::from(&tree);
+    let ptree = ptree.as_ref_treeview();
+    let suppressions = find_suppressions(&ptree);
+
+    let mut findings = BTreeSet::new();
+    let mut edits = EditSet::new();
+    let mut origins = vec![];
+    for rule in rules.rules() {
+        for mitem in rule.find(&ptree)? {
+            let row = mitem.area.start_position().row;
+            if suppressions
+                .iter()
+                .any(|s| s.suppresses(&rule.rule.id, row))
+            {
+                continue;
+            }
+            findings.insert((row, rule.rule.id.clone()));
+            if let Some(pattern) = rule.rewrite_options().first() {
+                edits.push(Edit::from_rewrite(
+                    target.body.len(),
+                    &mitem,
+                    &pattern.as_rewrite_option(),
+                )?);
+                origins.push((row, rule.rule.id.clone()));
+            }
+        }
+    }
+
+    let code: Code<T> = target.body.clone().into();
+    let (rewritten, statuses) = code.to_edited_form(&edits)?;
+    let skipped = origins
+        .into_iter()
+        .zip(statuses)
+        .filter_map(|((row, rule_id), status)| Some((row, rule_id, status.conflict()?)))
+        .collect();
+    Ok((findings, rewritten.as_str().to_string(), skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expectations() {
+        assert_eq!(
+            parse_annotation("# shisho: expect no-latest-tag"),
+            Some((true, vec!["no-latest-tag".to_string()]))
+        );
+        assert_eq!(
+            parse_annotation("  // shisho: ok: a, b"),
+            Some((false, vec!["a".to_string(), "b".to_string()]))
+        );
+        assert_eq!(
+            parse_annotation("/* shisho:expect a */"),
+            Some((true, vec!["a".to_string()]))
+        );
+        assert_eq!(parse_annotation("# shisho:ignore a -- reason"), None);
+        assert_eq!(parse_annotation("# shisho: expected a"), None);
+        assert_eq!(parse_annotation("a = 1 # shisho: expect a"), None);
+
+        let expectations = parse_expectations(
+            r#"# shisho: expect a
+# shisho: ok b
+FROM ubuntu:latest
+
+// shisho: ok a
+FROM ubuntu:20.04"#,
+        );
+        assert_eq!(
+            expectations,
+            vec![
+                Expectation {
+                    row: 2,
+                    rule_id: "a".into(),
+                    should_match: true
+                },
+                Expectation {
+                    row: 2,
+                    rule_id: "b".into(),
+                    should_match: false
+                },
+                Expectation {
+                    row: 5,
+                    rule_id: "a".into(),
+                    should_match: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixture_paths() {
+        let rule = Path::new("rules/no-latest-tag.yaml");
+        assert!(is_fixture_of(
+            Path::new("rules/no-latest-tag.Dockerfile"),
+            rule
+        ));
+        assert!(is_fixture_of(
+            Path::new("rules/no-latest-tag.test.yaml"),
+            rule
+        ));
+        assert!(!is_fixture_of(
+            Path::new("rules/no-latest.Dockerfile"),
+            rule
+        ));
+        assert!(!is_fixture_of(
+            Path::new("other/no-latest-tag.Dockerfile"),
+            rule
+        ));
+
+        assert_eq!(
+            fixed_file_of(Path::new("rules/no-latest-tag.Dockerfile")),
+            Some(PathBuf::from("rules/no-latest-tag.fixed.Dockerfile"))
+        );
+        assert!(is_fixed_file(Path::new(
+            "rules/no-latest-tag.fixed.Dockerfile"
+        )));
+        assert!(!is_fixed_file(Path::new("rules/no-latest-tag.Dockerfile")));
+    }
+
+    #[test]
+    fn test_rule_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let ruleset = "version: \"1\"\nrules: []\n";
+        std::fs::write(dir.path().join("s3.yaml"), ruleset).unwrap();
+        std::fs::write(dir.path().join("s3.public.yaml"), ruleset).unwrap();
+        std::fs::write(
+            dir.path().join("s3.test.yaml"),
+            "apiVersion: rbac.authorization.k8s.io/v1\nkind: Role\nrules: []\n",
+        )
+        .unwrap();
+
+        // rule sets named after another rule set are not its fixtures
+        assert_eq!(
+            rule_files(dir.path()),
+            vec![
+                dir.path().join("s3.public.yaml"),
+                dir.path().join("s3.yaml")
+            ]
+        );
+        assert_eq!(
+            fixtures_of(&dir.path().join("s3.yaml")).unwrap(),
+            vec![dir.path().join("s3.test.yaml")]
+        );
+    }
+
+    #[test]
+    fn test_run_fixtures() {
+        let mut dir = PathBuf::from(file!());
+        dir.pop();
+        dir.pop();
+        dir.push("tests");
+        dir.push("fixture");
+
+        let failures = |name: &str| {
+            let ruleset_path = dir.join(format!("{}.yaml", name));
+            let rules = ruleset::from_filepath(&ruleset_path).unwrap().rules;
+            let rule_ids = rules.iter().map(|r| r.id.clone()).collect::<BTreeSet<_>>();
+            let rule_map = CompiledRuleSet::new(rules).unwrap();
+            fixtures_of(&ruleset_path)
+                .unwrap()
+                .iter()
+                .flat_map(|f| run_fixture(f, &rule_map, &rule_ids).unwrap())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(failures("passing"), Vec::<String>::new());
+
+        // rewrites which cannot be applied together fail even if the rest matches the expected output
+        assert_eq!(
+            failures("conflicting"),
+            vec![
+                "conflicting.tf:3: rewrite of unencrypted-removal was not applied (inside another change)"
+            ]
+        );
+
+        let failures = failures("failing");
+        assert_eq!(failures.len(), 4);
+        assert_eq!(
+            failures[0],
+            "failing.tf:3: expected a finding of unencrypted, but none was found"
+        );
+        assert_eq!(
+            failures[1],
+            "failing.tf:7: unexpected finding of unencrypted"
+        );
+        assert_eq!(
+            failures[2],
+            "failing.tf:12: unencrypted matched, but the line is annotated with `shisho: ok unencrypted`"
+        );
+        assert!(failures[3]
+            .starts_with("failing.fixed.tf: rewritten code differs from the expected one:"));
+
+        assert!(!handle_opts(TestOpts {
+            paths: vec![dir.clone()],
+            common: CommonOpts {
+                verbose: clap_verbosity_flag::Verbosity::new(0, 0, 0),
+            },
+        })
+        .unwrap());
+    }
+}
@@ -0,0 +1,142 @@
+//! This module defines `validate` subcommand.
+
+use crate::cli::CommonOpts;
+use crate::core::ruleset::{
+    self, rule_lines,
+    validation::{validate, Problem, ProblemLevel},
+};
+use ansi_term::Color;
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use walkdir::WalkDir;
+
+/// Checks rule set files for mistakes without running them
+#[derive(StructOpt, Debug)]
+pub struct ValidateOpts {
+    /// Rule set files or directories containing them [default: the current directory]
+    #[structopt(parse(from_os_str))]
+    pub paths: Vec<PathBuf>,
+
+    #[structopt(flatten)]
+    pub common: CommonOpts,
+}
+
+pub fn run(opts: ValidateOpts) -> i32 {
+    match handle_opts(opts) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("{}: {}", Color::Red.paint("error"), e);
+            1
+        }
+    }
+}
+
+/// `handle_opts` validates all the rule set files and returns whether no error was found.
+fn handle_opts(opts: ValidateOpts) -> Result<bool> {
+    let paths = if opts.paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        opts.paths
+    };
+
+    let files = paths
+        .iter()
+        .flat_map(|p| ruleset_files(p))
+        .collect::<Vec<PathBuf>>();
+    if files.is_empty() {
+        return Err(anyhow!("no rule set file was found"));
+    }
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    let mut rules = 0;
+    // locations where each rule ID is defined first
+    let mut defined = HashMap::<String, (String, usize)>::new();
+    for file in &files {
+        let path = file.to_string_lossy().to_string();
+        let body =
+            std::fs::read_to_string(file).map_err(|e| anyhow!("failed to read {}: {}", path, e))?;
+        let ruleset = match ruleset::parse(&body, Some(file)) {
+            Ok(r) => r,
+            Err(e) => {
+                for e in e.0 {
+                    errors += 1;
+                    println!("{}: {}", Color::Red.paint("error"), e);
+                }
+                continue;
+            }
+        };
+
+        let lines = rule_lines(&body, ruleset.rules.iter().map(|r| r.id.as_str()));
+        for (rule, line) in ruleset.rules.iter().zip(lines) {
+            rules += 1;
+            let mut problems = validate(rule);
+            match defined.get(&rule.id) {
+                Some((first_path, first_line)) => problems.push(Problem {
+                    level: ProblemLevel::Error,
+                    message: format!(
+                        "the rule ID is already used at {}:{}",
+                        first_path, first_line
+                    ),
+                }),
+                None => {
+                    defined.insert(rule.id.clone(), (path.clone(), line));
+                }
+            }
+
+            for p in problems {
+                let level = match p.level {
+                    ProblemLevel::Error => {
+                        errors += 1;
+                        Color::Red.paint("error")
+                    }
+                    ProblemLevel::Warning => {
+                        warnings += 1;
+                        Color::Yellow.paint("warning")
+                    }
+                };
+                println!(
+                    "{}:{}: {}: rule {}: {}",
+                    path, line, level, rule.id, p.message
+                );
+            }
+        }
+        if opts.common.is_verbose() {
+            eprintln!("validated {} rule(s) in {}", ruleset.rules.len(), path);
+        }
+    }
+
+    println!(
+        "{} rule(s) in {} file(s) were validated: {} error(s), {} warning(s)",
+        rules,
+        files.len(),
+        errors,
+        warnings
+    );
+    Ok(errors == 0)
+}
+
+/// `ruleset_files` lists YAML files in `path` in the same way as `ruleset::from_path`.
+fn ruleset_files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            p.is_file()
+                && matches!(
+                    p.extension().and_then(|e| e.to_str()),
+                    Some("yaml") | Some("yml")
+                )
+        })
+        .collect()
+}
@@ -0,0 +1,4 @@
+resource "aws_ebs_volume" "unencrypted" {
+  # shisho: expect unencrypted, unencrypted-removal
+  encrypted = true
+}
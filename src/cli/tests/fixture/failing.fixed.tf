@@ -0,0 +1,13 @@
+resource "aws_ebs_volume" "missed" {
+  # shisho: expect unencrypted
+  encrypted = true
+}
+
+resource "aws_ebs_volume" "unexpected" {
+  encrypted = true
+}
+
+resource "aws_ebs_volume" "violated" {
+  # shisho: ok unencrypted
+  encrypted = false
+}
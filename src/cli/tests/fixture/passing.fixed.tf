@@ -0,0 +1,9 @@
+resource "aws_ebs_volume" "unencrypted" {
+  # shisho: expect unencrypted
+  encrypted = true
+}
+
+resource "aws_ebs_volume" "encrypted" {
+  # shisho: ok unencrypted
+  encrypted = true
+}
@@ -0,0 +1,9 @@
+resource "aws_ebs_volume" "unencrypted" {
+  # shisho: expect unencrypted
+  encrypted = false
+}
+
+resource "aws_ebs_volume" "encrypted" {
+  # shisho: ok unencrypted
+  encrypted = true
+}
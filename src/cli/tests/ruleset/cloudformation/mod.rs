@@ -0,0 +1,11 @@
+#[cfg(test)]
+mod tests {
+    ruleset_test! {
+        // constraints are evaluated against values of captured strings, with or without quotes
+        bucket_name: [
+            ("ruleset.yaml", "match.yaml", Ok(2), None),
+            ("ruleset.yaml", "match.json", Ok(1), None),
+            ("ruleset.yaml", "unmatch.json", Ok(0), None),
+        ],
+    }
+}
@@ -46,6 +46,33 @@ mod tests {
             ("mixed-pattern-like.yaml", "unmatch.tf", Result::Err(anyhow::anyhow!("")), None),
             ("no-pattern-like.yaml", "unmatch.tf", Result::Err(anyhow::anyhow!("")), None),
         ],
+        suppression: [
+            ("ruleset.yaml", "match.tf", Result::Ok(4), None),
+            ("ruleset.yaml", "unmatch.tf", Result::Ok(0), None),
+        ],
+        pattern_filters: [
+            ("pattern-not.yaml", "match.tf", Result::Ok(3), None),
+            ("pattern-not.yaml", "unmatch.tf", Result::Ok(0), None),
+            ("pattern-inside.yaml", "match.tf", Result::Ok(2), None),
+            ("pattern-inside.yaml", "unmatch.tf", Result::Ok(1), None),
+            ("pattern-not-inside.yaml", "match.tf", Result::Ok(2), None),
+            ("pattern-not-inside.yaml", "unmatch.tf", Result::Ok(1), None),
+            ("patterns.yaml", "match.tf", Result::Ok(2), None),
+            ("patterns.yaml", "unmatch.tf", Result::Ok(0), None),
+            ("constraint-with-filters.yaml", "match.tf", Result::Ok(1), None),
+            ("constraint-with-filters.yaml", "unmatch.tf", Result::Ok(0), None),
+            ("constraints-on-filters.yaml", "match.tf", Result::Ok(2), None),
+            ("constraints-on-filters.yaml", "unmatch.tf", Result::Ok(0), None),
+        ],
+        pattern_expressions: [
+            ("composition.yaml", "match.tf", Result::Ok(2), None),
+            ("composition.yaml", "unmatch.tf", Result::Ok(0), None),
+            ("intersection.yaml", "match.tf", Result::Ok(1), None),
+            ("intersection.yaml", "unmatch.tf", Result::Ok(0), None),
+            ("duplicated-captures.yaml", "match.tf", Result::Ok(3), None),
+            ("conflicting-captures.yaml", "match.tf", Result::Ok(0), None),
+            ("invalid.yaml", "match.tf", Result::Err(anyhow::anyhow!("")), None),
+        ],
         shared_constraints: [
             ("ruleset.yaml", "test.Dockerfile", Result::Ok(8), None),
             ("ruleset.yaml", "dockerfile", Result::Ok(8), None),
@@ -0,0 +1,12 @@
+resource "aws_ebs_volume" "a" {
+  encrypted = false
+  size      = 40
+}
+
+resource "aws_ebs_volume" "b" {
+  encrypted = true
+}
+
+resource "aws_instance" "c" {
+  encrypted = false
+}
@@ -0,0 +1,7 @@
+resource "aws_ebs_volume" "b" {
+  encrypted = true
+}
+
+resource "aws_instance" "c" {
+  encrypted = false
+}
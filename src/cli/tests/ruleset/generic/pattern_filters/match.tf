@@ -0,0 +1,19 @@
+resource "aws_ebs_volume" "a" {
+  encrypted = false
+}
+
+resource "aws_ebs_volume" "b" {
+  encrypted = true
+}
+
+resource "aws_instance" "c" {
+  ebs_block_device {
+    encrypted = false
+  }
+}
+
+resource "aws_instance" "ignored" {
+  ebs_block_device {
+    encrypted = false
+  }
+}
@@ -0,0 +1,9 @@
+resource "aws_ebs_volume" "b" {
+  encrypted = true
+}
+
+resource "aws_instance" "ignored" {
+  ebs_block_device {
+    encrypted = true
+  }
+}
@@ -0,0 +1,16 @@
+resource "aws_ebs_volume" "no_reason" {
+  # shisho:ignore unencrypted
+  encrypted = false
+  size      = 40
+}
+
+resource "aws_ebs_volume" "other_rule" {
+  # shisho:ignore other -- reviewed
+  encrypted = false
+}
+
+resource "aws_ebs_volume" "not_adjacent" {
+  // shisho:ignore unencrypted -- reviewed
+
+  encrypted = false
+}
@@ -0,0 +1,14 @@
+resource "aws_ebs_volume" "line_above" {
+  # shisho:ignore unencrypted -- accepted in the test environment
+  encrypted = false
+}
+
+resource "aws_ebs_volume" "same_line" {
+  encrypted = false // shisho:ignore unencrypted -- reviewed
+}
+
+resource "aws_ebs_volume" "all_rules" {
+  /* shisho:ignore -- reviewed */
+  encrypted = false
+  size      = 40 # shisho:ignore other, unencrypted -- reviewed
+}
@@ -4,5 +4,7 @@ mod tests {
         unencrypted_ebs: [("ruleset.yaml", "match.tf", Ok(2), None), ("ruleset.yaml", "unmatch.tf", Ok(0), None)],
         uncontrolled_ebs_encryption_key: [("ruleset.yaml", "match.tf", Ok(2), None), ("ruleset.yaml", "unmatch.tf", Ok(0), None)],
         comment: [("ruleset.yaml", "match.tf", Ok(4), None), ("ruleset.yaml", "unmatch.tf", Ok(0), None)],
+        // a rule for HCL also matches Terraform configurations in the JSON syntax
+        terraform_json: [("ruleset.yaml", "match.tf", Ok(1), None), ("ruleset.yaml", "match.tf.json", Ok(1), None), ("ruleset.yaml", "unmatch.tf.json", Ok(0), None)],
     }
 }
@@ -0,0 +1,9 @@
+resource "aws_s3_bucket" "logs" {
+  bucket = "logs"
+  acl    = "public-read"
+}
+
+resource "aws_s3_bucket" "assets" {
+  bucket = "assets"
+  acl    = "private"
+}
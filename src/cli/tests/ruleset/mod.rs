@@ -25,12 +25,21 @@ macro_rules! ruleset_test {
                 let mitem_num: Result<usize> = mitem_num;
                 let r = subcommand::check::handle_opts(subcommand::check::CheckOpts{
                     common: opts::CommonOpts { verbose: Verbosity::new(0, 0, 0) },
-                    report: opts::ReportOpts { format: ReporterType::Console, },
-                    ruleset_path: ruleset,
-                    encoding: encoding,
+                    report: opts::ReportOpts { format: Some(ReporterType::Console), min_severity: None, fail_severity: None, report_suppressed: false },
+                    fix: opts::FixOpts::default(),
+                    baseline: opts::BaselineOpts { baseline: None, write_baseline: None },
+                    ruleset_path: Some(ruleset),
+                    encoding,
                     target_path: Some(target),
+                    target: None,
                     exit_zero: false,
                     exclude: vec![],
+                    traversal: opts::TraversalOpts { no_ignore: false, max_file_size: 1048576 },
+                    matching: opts::MatchOpts { max_match_steps: 1000000 },
+                    cache: opts::CacheOpts { cache_dir: None, no_cache: false, prune_cache: false },
+                    profile: opts::ProfileOpts { profile: false, profile_output: None },
+                    diff_base: None,
+                    jobs: None,
                 });
                 match (r, mitem_num) {
                     (Ok(x), Ok(y)) if x == y => (),
@@ -43,6 +52,9 @@ macro_rules! ruleset_test {
     }
 }
 
+#[cfg(test)]
+mod cloudformation;
+
 #[cfg(test)]
 mod generic;
 
@@ -1,11 +1,14 @@
+pub mod changes;
 pub mod constraint;
 pub mod language;
 pub mod matcher;
 pub mod node;
 pub mod pattern;
+pub mod prefilter;
 pub mod query;
 pub mod rewriter;
 pub mod ruleset;
 pub mod source;
+pub mod suppression;
 pub mod target;
 pub mod tree;
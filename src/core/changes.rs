@@ -0,0 +1,218 @@
+//! This module defines `ChangeSet`, which describes lines changed in a git working tree against a revision.
+
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Debug, Default)]
+pub struct ChangeSet {
+    /// changed rows (0-origin, end-exclusive) of each file, keyed by canonicalized paths
+    files: HashMap<PathBuf, Vec<Range<usize>>>,
+}
+
+impl ChangeSet {
+    /// `from_git` collects changes between `base` and the working tree which contains `path`.
+    /// Untracked files are regarded as changed entirely.
+    pub fn from_git(base: &str, path: &Path) -> Result<Self> {
+        let root = repository_root(path)?;
+
+        let diff = git(
+            &root,
+            &[
+                "-c",
+                "core.quotePath=false",
+                "diff",
+                "--unified=0",
+                "--no-color",
+                "--no-ext-diff",
+                "--src-prefix=a/",
+                "--dst-prefix=b/",
+                base,
+                "--",
+            ],
+        )?;
+        let mut changes = Self::from_unified_diff(&root, &diff);
+
+        let untracked = git(&root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+        for p in untracked.split('\0').filter(|p| !p.is_empty()) {
+            changes.insert(&root.join(p), 0..usize::MAX);
+        }
+        Ok(changes)
+    }
+
+    /// `from_unified_diff` reads changed rows from the output of `git diff` run in `root`.
+    fn from_unified_diff(root: &Path, diff: &str) -> Self {
+        let mut changes = Self::default();
+        let mut current: Option<PathBuf> = None;
+        // lines of the old and new files left in the current hunk
+        let (mut old_left, mut new_left) = (0usize, 0usize);
+        for line in diff.lines() {
+            if old_left > 0 || new_left > 0 {
+                // lines in a hunk are not headers even if they look like ones (e.g. an added line `++ x`)
+                match line.chars().next() {
+                    Some('-') => old_left = old_left.saturating_sub(1),
+                    Some('+') => new_left = new_left.saturating_sub(1),
+                    Some(' ') => {
+                        old_left = old_left.saturating_sub(1);
+                        new_left = new_left.saturating_sub(1);
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+
+            if let Some(p) = line.strip_prefix("+++ ") {
+                // `+++ /dev/null` indicates the file was deleted
+                current = p.strip_prefix("b/").map(|p| root.join(p));
+            } else if let Some(header) = line.strip_prefix("@@ ") {
+                if let Some(hunk) = parse_hunk_header(header) {
+                    old_left = hunk.old_len;
+                    new_left = hunk.new_len;
+                    if let Some(ref p) = current {
+                        changes.insert(p, hunk.rows);
+                    }
+                }
+            }
+        }
+        changes
+    }
+
+    fn insert(&mut self, p: &Path, rows: Range<usize>) {
+        let p = p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+        self.files.entry(p).or_default().push(rows);
+    }
+
+    /// `changed_rows` returns changed rows of the file at `p`, or `None` if the file was not changed.
+    pub fn changed_rows(&self, p: &Path) -> Option<&Vec<Range<usize>>> {
+        p.canonicalize().ok().and_then(|p| self.files.get(&p))
+    }
+}
+
+/// `Hunk` describes a hunk header of a unified diff.
+#[derive(Debug, PartialEq)]
+struct Hunk {
+    /// number of lines of the old file in the hunk
+    old_len: usize,
+    /// number of lines of the new file in the hunk
+    new_len: usize,
+    /// changed rows of the new file
+    rows: Range<usize>,
+}
+
+/// `parse_hunk_header` parses a hunk header such as `-1,2 +3,4 @@`.
+fn parse_hunk_header(header: &str) -> Option<Hunk> {
+    let mut ranges = header.split_whitespace();
+    let (_, old_len) = parse_hunk_range(ranges.next()?.strip_prefix('-')?)?;
+    let (start, new_len) = parse_hunk_range(ranges.next()?.strip_prefix('+')?)?;
+
+    let rows = if new_len == 0 {
+        // lines were removed right after `start`; regard the lines around them as changed
+        start.saturating_sub(1)..start + 1
+    } else {
+        start - 1..start - 1 + new_len
+    };
+    Some(Hunk {
+        old_len,
+        new_len,
+        rows,
+    })
+}
+
+/// `parse_hunk_range` parses a range in a hunk header such as `3,4` into its start line and length.
+fn parse_hunk_range(range: &str) -> Option<(usize, usize)> {
+    let mut parts = range.splitn(2, ',');
+    let start = parts.next()?.parse().ok()?;
+    let len = match parts.next() {
+        Some(len) => len.parse().ok()?,
+        None => 1,
+    };
+    Some((start, len))
+}
+
+/// `repository_root` returns the root of the git working tree which contains `path`.
+pub fn repository_root(path: &Path) -> Result<PathBuf> {
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+    };
+    Ok(PathBuf::from(
+        git(dir, &["rev-parse", "--show-toplevel"])?.trim_end(),
+    ))
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hunk_header() {
+        let rows = |header| parse_hunk_header(header).map(|h| h.rows);
+        assert_eq!(rows("-1,2 +3,4 @@"), Some(2..6));
+        assert_eq!(rows("-1 +3 @@ resource"), Some(2..3));
+        assert_eq!(rows("-3,2 +2,0 @@"), Some(1..3));
+        assert_eq!(rows("-1,2 +0,0 @@"), Some(0..1));
+        assert_eq!(
+            parse_hunk_header("-3,2 +2 @@"),
+            Some(Hunk {
+                old_len: 2,
+                new_len: 1,
+                rows: 1..2
+            })
+        );
+        assert_eq!(parse_hunk_header("+2,0 @@"), None);
+    }
+
+    #[test]
+    fn test_from_unified_diff() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let diff = r#"diff --git a/src/core/changes.rs b/src/core/changes.rs
+index 0000000..1111111 100644
+--- a/src/core/changes.rs
++++ b/src/core/changes.rs
+@@ -0,0 +1,3 @@ header
++added
+++++ b/src/core/target.rs
++@@ -1 +1 @@
+@@ -10 +11 @@
+---- a/src/core/target.rs
++added
+diff --git a/deleted.rs b/deleted.rs
+--- a/deleted.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+-removed
+-removed
+"#;
+        let changes = ChangeSet::from_unified_diff(&root, diff);
+        assert_eq!(
+            changes.changed_rows(&root.join("src/core/changes.rs")),
+            Some(&vec![0..3, 10..11])
+        );
+        assert_eq!(changes.changed_rows(&root.join("src/core/target.rs")), None);
+        assert_eq!(changes.files.len(), 1);
+    }
+}
@@ -8,7 +8,7 @@ use crate::core::{
     ruleset::{RawConstraint, RawPredicate},
 };
 
-use super::pattern::PatternWithConstraints;
+use super::pattern::PatternExpression;
 
 #[derive(Debug)]
 pub struct Constraint<T>
@@ -24,10 +24,10 @@ pub enum Predicate<T>
 where
     T: Queryable,
 {
-    MatchQuery(PatternWithConstraints<T>),
-    NotMatchQuery(PatternWithConstraints<T>),
-    MatchAnyOfQuery(Vec<PatternWithConstraints<T>>),
-    NotMatchAnyOfQuery(Vec<PatternWithConstraints<T>>),
+    MatchQuery(PatternExpression<T>),
+    NotMatchQuery(PatternExpression<T>),
+    MatchAnyOfQuery(Vec<PatternExpression<T>>),
+    NotMatchAnyOfQuery(Vec<PatternExpression<T>>),
 
     MatchRegex(Regex),
     NotMatchRegex(Regex),
@@ -51,7 +51,9 @@ where
                 let mut rrps = rc.get_regex_patterns()?;
                 match (rpwcs.len(), rrps.len()) {
                     (1, 0) => {
-                        let pc = PatternWithConstraints::<T>::try_from(rpwcs.pop().unwrap())?;
+                        let pc = PatternExpression::<T>::try_from(
+                            rpwcs.pop().unwrap().into_expression(),
+                        )?;
                         if rc.should == RawPredicate::Match {
                             Predicate::MatchQuery(pc)
                         } else if rc.should == RawPredicate::NotMatch {
@@ -89,8 +91,8 @@ where
                     (l, 0) if l > 0 => {
                         let pwcs = rpwcs
                             .into_iter()
-                            .map(|x| PatternWithConstraints::<T>::try_from(x))
-                            .collect::<Result<Vec<PatternWithConstraints<T>>>>()?;
+                            .map(|x| PatternExpression::<T>::try_from(x.into_expression()))
+                            .collect::<Result<Vec<PatternExpression<T>>>>()?;
                         if rc.should == RawPredicate::MatchAnyOf {
                             Predicate::MatchAnyOfQuery(pwcs)
                         } else if rc.should == RawPredicate::NotMatchAnyOf {
@@ -1,19 +1,42 @@
+mod arm;
+mod cloudformation;
 mod docker;
 mod go;
 mod hcl;
+mod kubernetes;
+mod terraform_json;
+mod yaml;
+
+use std::borrow::Cow;
 
 use crate::core::node::Position;
 
+pub use self::arm::ARM;
+pub use self::cloudformation::CloudFormation;
 pub use self::docker::Dockerfile;
 pub use self::go::Go;
 pub use self::hcl::HCL;
+pub use self::kubernetes::Kubernetes;
+pub use self::terraform_json::TerraformJSON;
 
-use super::node::{Node, Range, RootNode};
+use super::node::{Node, NodeType, Range, RootNode};
 
 pub trait Queryable {
     fn target_language() -> tree_sitter::Language;
     fn query_language() -> tree_sitter::Language;
 
+    /// `query_source` returns the code which a pattern is parsed from in place of the given one.
+    /// Languages whose patterns are parsed with the grammar of targets rewrite what the grammar does not accept (e.g. metavariables),
+    /// keeping byte offsets of the pattern so that nodes of the query tree point to the original code.
+    fn query_source(source: &[u8]) -> Cow<'_, [u8]> {
+        Cow::Borrowed(source)
+    }
+
+    /// `query_node_kind` returns the kind which the given node of the query tree is regarded as, if the grammar does not distinguish it (e.g. metavariables).
+    fn query_node_kind(_node: &Node) -> Option<&'static str> {
+        None
+    }
+
     /// `unwrap_root` takes a root of the query tree and returns nodes for matching.
     fn unwrap_root<'tree, 'a>(root: &'a RootNode<'tree>) -> &'a Vec<Node<'tree>>;
 
@@ -30,6 +53,29 @@ pub trait Queryable {
         false
     }
 
+    /// `string_value` returns the text of the given string literal which is compared on matching.
+    /// Languages where quotes of strings are optional return the text without them.
+    fn string_value<'tree>(node: &Node<'tree>) -> &'tree str {
+        node.as_str()
+    }
+
+    /// `is_unordered` returns whether the order of children of the given node is insignificant (e.g. keys of a mapping).
+    /// Named children of such a node are matched in any order.
+    fn is_unordered(_node: &Node) -> bool {
+        false
+    }
+
+    /// `normalize_kind` returns the kind which nodes of the given kind are compared as on matching,
+    /// so that different notations of the same structure match each other.
+    fn normalize_kind(kind: &'static str) -> &'static str {
+        kind
+    }
+
+    /// `is_comment` returns whether the given node of the target tree is a comment.
+    fn is_comment(node: &Node) -> bool {
+        node.kind() == NodeType::Normal("comment")
+    }
+
     fn range(node: &Node) -> Range {
         Self::default_range(node)
     }
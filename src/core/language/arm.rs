@@ -0,0 +1,100 @@
+use crate::yaml_language;
+
+/// `ARM` matches Azure Resource Manager templates, which are written in JSON.
+#[derive(Debug, Clone)]
+pub struct ARM;
+
+yaml_language!(ARM);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::matcher::MatchedItem;
+    use crate::core::query::MetavariableId;
+    use crate::match_pt;
+    use anyhow::Result;
+    use std::convert::TryFrom;
+
+    const TEMPLATE: &str = r#"{
+  "$schema": "https://schema.management.azure.com/schemas/2019-04-01/deploymentTemplate.json#",
+  "contentVersion": "1.0.0.0",
+  "parameters": {
+    "name": { "type": "string" }
+  },
+  "resources": [
+    {
+      "type": "Microsoft.Storage/storageAccounts",
+      "apiVersion": "2021-04-01",
+      "name": "[parameters('name')]",
+      "properties": {
+        "supportsHttpsTrafficOnly": false,
+        "minimumTlsVersion": "TLS1_0"
+      }
+    },
+    {
+      "type": "Microsoft.Web/sites",
+      "apiVersion": "2021-02-01",
+      "name": "app",
+      "properties": { "httpsOnly": true }
+    }
+  ]
+}
+"#;
+
+    #[test]
+    fn test_resources() {
+        match_pt!(
+            ARM,
+            r#"
+type: Microsoft.Storage/storageAccounts
+name: :[NAME]
+properties:
+  supportsHttpsTrafficOnly: false
+  ...
+"#,
+            TEMPLATE,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("NAME".into()))
+                        .map(|x| x.as_str()),
+                    Some("\"[parameters('name')]\"")
+                );
+            }
+        );
+
+        match_pt!(
+            ARM,
+            r#"{ "type": :[TYPE], "properties": { "httpsOnly": true } }"#,
+            TEMPLATE,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("TYPE".into()))
+                        .map(|x| x.as_str()),
+                    Some("\"Microsoft.Web/sites\"")
+                );
+            }
+        );
+    }
+
+    #[test]
+    fn test_template_expressions() {
+        match_pt!(
+            ARM,
+            r#"name: "[parameters(':[PARAM]')]""#,
+            TEMPLATE,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("PARAM".into()))
+                        .map(|x| x.as_str()),
+                    Some("name")
+                );
+            }
+        );
+    }
+}
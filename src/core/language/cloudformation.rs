@@ -0,0 +1,164 @@
+use crate::yaml_language;
+
+/// `CloudFormation` matches AWS CloudFormation templates in YAML and JSON.
+#[derive(Debug, Clone)]
+pub struct CloudFormation;
+
+yaml_language!(CloudFormation);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::matcher::MatchedItem;
+    use crate::core::query::MetavariableId;
+    use crate::match_pt;
+    use anyhow::Result;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_basic_query() {
+        match_pt!(
+            CloudFormation,
+            r#"
+:[NAME]:
+  Type: AWS::S3::Bucket
+  Properties:
+    :[...]
+"#,
+            r#"AWSTemplateFormatVersion: "2010-09-09"
+Resources:
+  Logs:
+    Properties:
+      BucketName: logs
+    Type: AWS::S3::Bucket
+  Queue:
+    Type: AWS::SQS::Queue
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("NAME".into()))
+                        .map(|x| x.as_str()),
+                    Some("Logs")
+                );
+            }
+        );
+    }
+
+    #[test]
+    fn test_json_template() {
+        match_pt!(
+            CloudFormation,
+            r#"
+:[NAME]:
+  Type: AWS::S3::Bucket
+  Properties:
+    BucketName: :[BUCKET]
+"#,
+            r#"{
+  "AWSTemplateFormatVersion": "2010-09-09",
+  "Resources": {
+    "Logs": {
+      "Type": "AWS::S3::Bucket",
+      "Properties": { "BucketName": "logs" }
+    }
+  }
+}
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("BUCKET".into()))
+                        .map(|x| x.as_str()),
+                    Some("\"logs\"")
+                );
+            }
+        );
+    }
+
+    #[test]
+    fn test_intrinsic_functions() {
+        match_pt!(
+            CloudFormation,
+            r#"BucketName: !Ref :[PARAM]"#,
+            r#"
+Resources:
+  Logs:
+    Type: AWS::S3::Bucket
+    Properties:
+      BucketName: !Ref LogBucketName
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("PARAM".into()))
+                        .map(|x| x.as_str()),
+                    Some("LogBucketName")
+                );
+            }
+        );
+
+        match_pt!(
+            CloudFormation,
+            r#"Value: !GetAtt :[RESOURCE].Arn"#,
+            r#"
+Outputs:
+  QueueArn:
+    Value: !GetAtt Queue.Arn
+  BucketUrl:
+    Value: !Sub "https://${Logs}.s3.amazonaws.com"
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("RESOURCE".into()))
+                        .map(|x| x.as_str()),
+                    Some("Queue")
+                );
+            }
+        );
+    }
+
+    #[test]
+    fn test_long_form_intrinsic_functions() {
+        // short-form intrinsic functions are not translated into the long form, which JSON templates have
+        let json = r#"{
+  "Resources": {
+    "Logs": {
+      "Type": "AWS::S3::Bucket",
+      "Properties": { "BucketName": { "Ref": "LogBucketName" } }
+    }
+  }
+}
+"#;
+        match_pt!(
+            CloudFormation,
+            r#"BucketName: !Ref :[PARAM]"#,
+            json,
+            |c: Result<Vec<MatchedItem>>| {
+                assert_eq!(c.unwrap().len(), 0);
+            }
+        );
+        match_pt!(
+            CloudFormation,
+            r#"
+BucketName:
+  Ref: :[PARAM]
+"#,
+            json,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("PARAM".into()))
+                        .map(|x| x.value_as_str::<CloudFormation>()),
+                    Some("LogBucketName")
+                );
+            }
+        );
+    }
+}
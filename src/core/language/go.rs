@@ -193,6 +193,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_deep_expression() {
+        match_pt!(
+            Go,
+            r#"exec.Command(<... os.Getenv(:[X]) ...>)"#,
+            r#"exec.Command("sh", "-c", "echo " + os.Getenv("CMD"))"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 0);
+            }
+        );
+
+        match_pt!(
+            Go,
+            r#"exec.Command(:[...], <... os.Getenv(:[X]) ...>)"#,
+            r#"exec.Command("sh", "-c", "echo " + os.Getenv("CMD"))"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("X".into()))
+                        .map(|x| x.as_str()),
+                    Some("\"CMD\"")
+                );
+            }
+        );
+
+        match_pt!(
+            Go,
+            r#"exec.Command(:[...], <... os.Getenv(:[X]) ...>)"#,
+            r#"exec.Command("sh", "-c", os.Getenv("CMD"))"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+            }
+        );
+    }
+
     #[test]
     fn test_function_definitions() {
         match_pt!(
@@ -0,0 +1,308 @@
+use crate::yaml_language;
+
+/// `Kubernetes` matches Kubernetes manifests.
+#[derive(Debug, Clone)]
+pub struct Kubernetes;
+
+yaml_language!(Kubernetes);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::matcher::MatchedItem;
+    use crate::core::pattern::Pattern;
+    use crate::core::query::MetavariableId;
+    use crate::match_pt;
+    use anyhow::Result;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_basic_query() {
+        match_pt!(
+            Kubernetes,
+            r#"kind: Pod"#,
+            r#"apiVersion: v1
+kind: Pod
+metadata:
+  name: test
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+            }
+        );
+
+        match_pt!(
+            Kubernetes,
+            r#"image: :[IMAGE]"#,
+            r#"spec:
+  containers:
+    - name: app
+      image: nginx:latest
+    - name: sidecar
+      image: envoy
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 2);
+                let mut images = c
+                    .iter()
+                    .map(|m| {
+                        m.capture_of(&MetavariableId("IMAGE".into()))
+                            .map(|x| x.as_str())
+                    })
+                    .collect::<Vec<_>>();
+                images.sort();
+                assert_eq!(images, vec![Some("envoy"), Some("nginx:latest")]);
+            }
+        );
+
+        // quotes of scalars are optional
+        match_pt!(
+            Kubernetes,
+            r#"image: "nginx::[TAG]""#,
+            r#"image: 'nginx:1.21'"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("TAG".into()))
+                        .map(|x| x.as_str()),
+                    Some("1.21")
+                );
+            }
+        );
+    }
+
+    #[test]
+    fn test_unordered_keys() {
+        match_pt!(
+            Kubernetes,
+            r#"
+securityContext:
+  runAsUser: 0
+  privileged: true
+"#,
+            r#"
+securityContext:
+  privileged: true
+  runAsUser: 0
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+            }
+        );
+
+        // a nested mapping must have no other keys unless the pattern has an ellipsis
+        match_pt!(
+            Kubernetes,
+            r#"
+securityContext:
+  privileged: true
+"#,
+            r#"
+securityContext:
+  runAsUser: 0
+  privileged: true
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 0);
+            }
+        );
+
+        match_pt!(
+            Kubernetes,
+            r#"
+securityContext:
+  privileged: true
+  :[...REST]
+"#,
+            r#"
+securityContext:
+  privileged: true
+  runAsUser: 0
+  readOnlyRootFilesystem: false
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("REST".into()))
+                        .map(|x| x.as_str()),
+                    Some("runAsUser: 0\n  readOnlyRootFilesystem: false")
+                );
+            }
+        );
+
+        match_pt!(
+            Kubernetes,
+            r#"securityContext: { privileged: true, ... }"#,
+            r#"
+securityContext:
+  runAsUser: 0
+  privileged: true
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+            }
+        );
+    }
+
+    #[test]
+    fn test_sequences() {
+        match_pt!(
+            Kubernetes,
+            r#"
+args:
+  - --port
+  - :[PORT]
+  - ...
+"#,
+            r#"
+args:
+  - --port
+  - "8080"
+  - --verbose
+  - --debug
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("PORT".into()))
+                        .map(|x| x.as_str()),
+                    Some("\"8080\"")
+                );
+            }
+        );
+
+        // metavariables are available in flow sequences as well
+        match_pt!(
+            Kubernetes,
+            r#"command: [ :[SHELL], "-c", ... ]"#,
+            r#"command: [ "/bin/sh", "-c", "echo $HOME" ]"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("SHELL".into()))
+                        .map(|x| x.as_str()),
+                    Some("\"/bin/sh\"")
+                );
+            }
+        );
+    }
+
+    #[test]
+    fn test_multiple_documents() {
+        match_pt!(
+            Kubernetes,
+            r#"
+kind: :[KIND]
+metadata:
+  name: :[NAME]
+  ...
+"#,
+            r#"apiVersion: v1
+kind: Service
+metadata:
+  name: web
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  labels:
+    app: web
+  name: web
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 2);
+                let mut kinds = c
+                    .iter()
+                    .map(|m| {
+                        m.capture_of(&MetavariableId("KIND".into()))
+                            .map(|x| x.as_str())
+                    })
+                    .collect::<Vec<_>>();
+                kinds.sort();
+                assert_eq!(kinds, vec![Some("Deployment"), Some("Service")]);
+            }
+        );
+    }
+
+    #[test]
+    fn test_deep_expression() {
+        match_pt!(
+            Kubernetes,
+            r#"
+spec: <... privileged: true ...>
+"#,
+            r#"
+spec:
+  containers:
+    - name: app
+      image: nginx
+      securityContext:
+        privileged: true
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+            }
+        );
+
+        // metavariables in deep expressions are captured as well
+        match_pt!(
+            Kubernetes,
+            r#"
+spec: <... image: :[IMAGE] ...>
+"#,
+            r#"
+spec:
+  containers:
+    - name: app
+      image: nginx
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("IMAGE".into()))
+                        .map(|x| x.as_str()),
+                    Some("nginx")
+                );
+            }
+        );
+
+        match_pt!(
+            Kubernetes,
+            r#"
+spec: <... privileged: true ...>
+"#,
+            r#"
+metadata:
+  privileged: true
+spec:
+  containers:
+    - name: app
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 0);
+            }
+        );
+
+        assert!(Pattern::<Kubernetes>::try_from("spec: <... privileged: true").is_err());
+    }
+
+    #[test]
+    fn test_empty_pattern() {
+        for source in ["", "\n", "# comment only\n"] {
+            assert!(Pattern::<Kubernetes>::try_from(source).is_err());
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::yaml_language;
+
+/// `TerraformJSON` matches Terraform configurations in the JSON syntax (`*.tf.json`).
+/// Rules for HCL are translated for this language (see `ruleset::translation`).
+#[derive(Debug, Clone)]
+pub struct TerraformJSON;
+
+yaml_language!(TerraformJSON);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::matcher::MatchedItem;
+    use crate::core::query::MetavariableId;
+    use crate::match_pt;
+    use anyhow::Result;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_basic_query() {
+        match_pt!(
+            TerraformJSON,
+            r#"
+resource:
+  "aws_s3_bucket":
+    :[NAME]:
+      acl: "public-read"
+      :[...]
+    ...
+  ...
+"#,
+            r#"{
+  "resource": {
+    "aws_s3_bucket": {
+      "logs": { "bucket": "logs", "acl": "public-read" },
+      "assets": { "bucket": "assets", "acl": "private" }
+    },
+    "aws_sqs_queue": { "jobs": {} }
+  }
+}
+"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("NAME".into()))
+                        .map(|x| x.as_str()),
+                    Some("\"logs\"")
+                );
+            }
+        );
+
+        // expressions are written in string templates
+        match_pt!(
+            TerraformJSON,
+            r#"bucket: "${var.:[NAME]}""#,
+            r#"{ "resource": { "aws_s3_bucket": { "logs": { "bucket": "${var.bucket}" } } } }"#,
+            |c: Result<Vec<MatchedItem>>| {
+                let c = c.unwrap();
+                assert_eq!(c.len(), 1);
+                assert_eq!(
+                    c[0].capture_of(&MetavariableId("NAME".into()))
+                        .map(|x| x.as_str()),
+                    Some("bucket")
+                );
+            }
+        );
+    }
+}
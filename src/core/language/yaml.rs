@@ -0,0 +1,243 @@
+//! This module defines how YAML documents are matched, which is shared by languages written in YAML.
+//! Languages written in JSON (e.g. ARM templates) are also matched as YAML, since the flow style of YAML is a superset of JSON;
+//! this lets patterns in YAML match both, and a JSON object match a mapping in the block style.
+
+use std::borrow::Cow;
+
+use once_cell::sync::Lazy;
+use regex::bytes::Regex;
+
+use crate::core::node::{
+    Node, NodeType, RootNode, SHISHO_NODE_ELLIPSIS, SHISHO_NODE_ELLIPSIS_METAVARIABLE,
+    SHISHO_NODE_METAVARIABLE,
+};
+
+/// `yaml_language!` implements `Queryable` for a language whose targets and patterns are parsed as YAML documents.
+#[macro_export]
+macro_rules! yaml_language {
+    ($lang:ident) => {
+        impl $crate::core::language::Queryable for $lang {
+            fn target_language() -> tree_sitter::Language {
+                tree_sitter_yaml::language()
+            }
+
+            fn query_language() -> tree_sitter::Language {
+                tree_sitter_yaml::language()
+            }
+
+            fn query_source(source: &[u8]) -> std::borrow::Cow<'_, [u8]> {
+                $crate::core::language::yaml::query_source(source)
+            }
+
+            fn query_node_kind(node: &$crate::core::node::Node) -> Option<&'static str> {
+                $crate::core::language::yaml::query_node_kind(node)
+            }
+
+            fn unwrap_root<'tree, 'a>(
+                root: &'a $crate::core::node::RootNode<'tree>,
+            ) -> &'a Vec<$crate::core::node::Node<'tree>> {
+                $crate::core::language::yaml::unwrap_root(root)
+            }
+
+            fn is_skippable(node: &$crate::core::node::Node) -> bool {
+                $crate::core::language::yaml::is_skippable(node)
+            }
+
+            fn is_leaf_like(node: &$crate::core::node::Node) -> bool {
+                $crate::core::language::yaml::is_string_literal(node)
+            }
+
+            fn is_string_literal(node: &$crate::core::node::Node) -> bool {
+                $crate::core::language::yaml::is_string_literal(node)
+            }
+
+            fn string_value<'tree>(node: &$crate::core::node::Node<'tree>) -> &'tree str {
+                $crate::core::language::yaml::string_value(node)
+            }
+
+            fn is_unordered(node: &$crate::core::node::Node) -> bool {
+                $crate::core::language::yaml::is_unordered(node)
+            }
+
+            fn normalize_kind(kind: &'static str) -> &'static str {
+                $crate::core::language::yaml::normalize_kind(kind)
+            }
+        }
+    };
+}
+
+static METAVARIABLE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r":\[(\.\.\.)?[A-Z_][A-Z_0-9]*\]|:\[\.\.\.\]").unwrap());
+static STANDALONE_ELLIPSIS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^ *(\.\.\.|:\[\.\.\.([A-Z_][A-Z_0-9]*)?\]) *$").unwrap());
+
+/// `query_source` rewrites a pattern so that the YAML grammar parses metavariables and ellipses as plain scalars.
+/// Their kinds are restored from the original code by `query_node_kind`.
+///
+/// Unlike the other languages, YAML has no query grammar (i.e. `//third_party/tree-sitter-yaml-query`) yet, so patterns are parsed with the target grammar.
+/// Once a query grammar with metavariables and ellipses is available as a submodule, `query_language` should return it, and these rewrites and `query_node_kind` should be removed.
+pub(super) fn query_source(source: &[u8]) -> Cow<'_, [u8]> {
+    let mut rewritten = source.to_vec();
+
+    // `[` and `]` are indicators of flow sequences, so `:[X]` is parsed as `__X_`
+    for m in METAVARIABLE.find_iter(source) {
+        for b in &mut rewritten[m.range()] {
+            if !matches!(*b, b'A'..=b'Z' | b'0'..=b'9' | b'_') {
+                *b = b'_';
+            }
+        }
+    }
+
+    // an ellipsis on its own line stands for the rest of entries in the mapping, so it is parsed as a key without a value
+    // (e.g. `  ...` as `  ..:`) unless it is the whole pattern
+    let lines = source
+        .split(|b| *b == b'\n')
+        .filter(|line| line.iter().any(|b| !b.is_ascii_whitespace()))
+        .count();
+    if lines > 1 {
+        for c in STANDALONE_ELLIPSIS.captures_iter(source) {
+            let ellipsis = c.get(1).unwrap();
+            rewritten[ellipsis.end() - 1] = b':';
+        }
+    }
+
+    Cow::Owned(rewritten)
+}
+
+/// `query_node_kind` returns the kind of a node which is written as a metavariable or an ellipsis in the pattern.
+pub(super) fn query_node_kind(node: &Node) -> Option<&'static str> {
+    let is_ellipsis = |kind: &Option<&'static str>| {
+        matches!(
+            kind,
+            Some(SHISHO_NODE_ELLIPSIS) | Some(SHISHO_NODE_ELLIPSIS_METAVARIABLE)
+        )
+    };
+    let named_children = node
+        .children
+        .iter()
+        .filter(|c| c.is_named())
+        .collect::<Vec<_>>();
+
+    match node.kind() {
+        NodeType::Normal("flow_node") => shisho_kind(node.as_str()),
+        // `- ...` matches items rather than a value of an item
+        NodeType::Normal("block_sequence_item") => named_children
+            .first()
+            .filter(|child| child.kind() == NodeType::Normal("flow_node"))
+            .map(|child| shisho_kind(child.as_str()))
+            .filter(is_ellipsis)
+            .flatten(),
+        // `...` on its own line in a mapping, which `query_source` turned into a key
+        NodeType::Normal("block_mapping_pair") if named_children.len() == 1 => {
+            Some(shisho_kind(node.as_str()))
+                .filter(is_ellipsis)
+                .flatten()
+        }
+        _ => None,
+    }
+}
+
+fn shisho_kind(s: &str) -> Option<&'static str> {
+    if s == "..." {
+        return Some(SHISHO_NODE_ELLIPSIS);
+    }
+    let inner = s.strip_prefix(":[")?.strip_suffix(']')?;
+    let (name, with_ellipsis) = match inner.strip_prefix("...") {
+        Some(name) => (name, true),
+        None => (inner, false),
+    };
+    let is_name = name
+        .bytes()
+        .enumerate()
+        .all(|(i, b)| b.is_ascii_uppercase() || b == b'_' || (i > 0 && b.is_ascii_digit()));
+    match (with_ellipsis, name.is_empty()) {
+        _ if !is_name => None,
+        (true, true) => Some(SHISHO_NODE_ELLIPSIS),
+        (true, false) => Some(SHISHO_NODE_ELLIPSIS_METAVARIABLE),
+        (false, false) => Some(SHISHO_NODE_METAVARIABLE),
+        (false, true) => None,
+    }
+}
+
+pub(super) fn unwrap_root<'tree, 'a>(root: &'a RootNode<'tree>) -> &'a Vec<Node<'tree>> {
+    // a pattern is the first document of a stream, and a pattern of a mapping or a sequence matches its entries
+    let document = root
+        .as_node()
+        .children
+        .iter()
+        .find(|n| n.kind() == NodeType::Normal("document"))
+        .expect("failed to load the code; no document");
+    document
+        .children
+        .iter()
+        .find(|n| {
+            matches!(
+                n.kind(),
+                NodeType::Normal("block_node") | NodeType::Normal("flow_node")
+            )
+        })
+        .and_then(|value| {
+            value.children.iter().find(|n| {
+                matches!(
+                    n.kind(),
+                    NodeType::Normal("block_mapping")
+                        | NodeType::Normal("flow_mapping")
+                        | NodeType::Normal("block_sequence")
+                        | NodeType::Normal("flow_sequence")
+                )
+            })
+        })
+        .map_or(&document.children, |collection| &collection.children)
+}
+
+pub(super) fn is_skippable(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        NodeType::Normal("comment")
+            | NodeType::Normal("{")
+            | NodeType::Normal("}")
+            | NodeType::Normal("[")
+            | NodeType::Normal("]")
+            | NodeType::Normal(",")
+    )
+}
+
+pub(super) fn is_string_literal(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        NodeType::Normal("plain_scalar")
+            | NodeType::Normal("single_quote_scalar")
+            | NodeType::Normal("double_quote_scalar")
+            | NodeType::Normal("block_scalar")
+    )
+}
+
+pub(super) fn string_value<'tree>(node: &Node<'tree>) -> &'tree str {
+    // `foo`, `'foo'` and `"foo"` are the same string
+    let s = node.as_str();
+    match node.kind() {
+        NodeType::Normal("single_quote_scalar") | NodeType::Normal("double_quote_scalar")
+            if s.len() >= 2 =>
+        {
+            &s[1..s.len() - 1]
+        }
+        _ => s,
+    }
+}
+
+pub(super) fn is_unordered(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        NodeType::Normal("block_mapping") | NodeType::Normal("flow_mapping")
+    )
+}
+
+pub(super) fn normalize_kind(kind: &'static str) -> &'static str {
+    // mappings in the flow style (e.g. `{ privileged: true }`, or JSON) match ones in the block style
+    match kind {
+        "flow_node" => "block_node",
+        "flow_mapping" => "block_mapping",
+        "flow_pair" => "block_mapping_pair",
+        kind => kind,
+    }
+}
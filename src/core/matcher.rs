@@ -1,3 +1,6 @@
+mod budget;
+pub use self::budget::*;
+
 mod item;
 pub use self::item::*;
 
@@ -0,0 +1,77 @@
+use std::{cell::Cell, rc::Rc};
+use thiserror::Error;
+
+/// `StepBudget` bounds the number of steps which matchers take, so that patterns with many ellipses do not hang on long sibling lists.
+/// Clones of a budget share the same counter.
+#[derive(Debug, Clone, Default)]
+pub struct StepBudget {
+    /// maximum number of steps; `None` means no limit
+    limit: Option<usize>,
+    used: Rc<Cell<usize>>,
+}
+
+impl StepBudget {
+    pub fn new(limit: Option<usize>) -> Self {
+        StepBudget {
+            limit,
+            used: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// `consume` takes a step and returns whether matchers can go on.
+    pub fn consume(&self) -> bool {
+        self.used.set(self.used.get() + 1);
+        !self.is_exhausted()
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        match self.limit {
+            Some(limit) => self.used.get() > limit,
+            None => false,
+        }
+    }
+
+    pub fn reset(&self) {
+        self.used.set(0);
+    }
+
+    /// `check` returns an error if the budget has been exhausted.
+    pub fn check(&self) -> Result<(), StepLimitExceeded> {
+        match self.limit {
+            Some(limit) if self.is_exhausted() => Err(StepLimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("matching was aborted after {0} steps")]
+pub struct StepLimitExceeded(pub usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_budget() {
+        let budget = StepBudget::new(Some(2));
+        let shared = budget.clone();
+        assert!(budget.consume());
+        assert!(shared.consume());
+        assert!(budget.check().is_ok());
+        assert!(!shared.consume());
+        assert!(budget.is_exhausted());
+        assert_eq!(
+            budget.check().unwrap_err().to_string(),
+            "matching was aborted after 2 steps"
+        );
+
+        budget.reset();
+        assert!(!shared.is_exhausted());
+
+        let budget = StepBudget::default();
+        for _ in 0..10 {
+            assert!(budget.consume());
+        }
+    }
+}
@@ -5,7 +5,7 @@ use crate::core::{
     constraint::{Constraint, Predicate},
     language::Queryable,
     node::{ConsecutiveNodes, Node},
-    pattern::PatternWithConstraints,
+    pattern::PatternExpression,
     query::MetavariableId,
     tree::RefTreeView,
 };
@@ -26,9 +26,33 @@ impl<'tree> CaptureItem<'tree> {
         }
     }
 
+    /// `value_as_str` returns the text which constraints on the item are evaluated against.
+    /// A captured string literal is evaluated by its value, so that e.g. `"logs"` in JSON and `logs` in YAML are the same.
+    pub fn value_as_str<T: Queryable>(&'tree self) -> &'tree str {
+        if let CaptureItem::Nodes(n) = self {
+            if let [node] = n.as_vec().as_slice() {
+                // a string literal may be wrapped with nodes of the same range (e.g. `flow_node` in YAML)
+                let mut node: &Node<'tree> = node;
+                while let [child] = node.children.as_slice() {
+                    if T::is_string_literal(node)
+                        || child.start_byte() != node.start_byte()
+                        || child.end_byte() != node.end_byte()
+                    {
+                        break;
+                    }
+                    node = child;
+                }
+                if T::is_string_literal(node) {
+                    return T::string_value(node);
+                }
+            }
+        }
+        self.as_str()
+    }
+
     pub fn matches<T: Queryable + 'tree>(
         &self,
-        q: &PatternWithConstraints<T>,
+        q: &PatternExpression<T>,
     ) -> Result<(bool, CaptureMap<'tree>)> {
         match self {
             CaptureItem::Empty => Ok((false, CaptureMap::new())),
@@ -41,9 +65,7 @@ impl<'tree> CaptureItem<'tree> {
                     .iter()
                     .map(|node: &&'tree Node<'tree>| {
                         let ptree = RefTreeView::<'tree, T>::from(*node);
-                        let matches = ptree
-                            .matches(&q.into())
-                            .collect::<Result<Vec<MatchedItem<'tree>>>>()?;
+                        let matches = ptree.matches_expression(q)?;
                         let is_empty = matches.is_empty();
                         let captures = matches
                             .into_iter()
@@ -112,6 +134,61 @@ impl<'tree> MatchedItem<'tree> {
         Ok((true, items))
     }
 
+    /// `find_equal` returns the first item in `others` which has the same range as `self` and consistent captures.
+    pub fn find_equal<'a>(
+        &self,
+        others: &'a [MatchedItem<'tree>],
+    ) -> Option<&'a MatchedItem<'tree>> {
+        others.iter().find(|other| {
+            other.area.start_byte() == self.area.start_byte()
+                && other.area.end_byte() == self.area.end_byte()
+                && self.is_consistent_with(other)
+        })
+    }
+
+    /// `find_container` returns the first item in `others` which contains `self` and has consistent captures.
+    pub fn find_container<'a>(
+        &self,
+        others: &'a [MatchedItem<'tree>],
+    ) -> Option<&'a MatchedItem<'tree>> {
+        others
+            .iter()
+            .find(|other| other.area.contains(&self.area) && self.is_consistent_with(other))
+    }
+
+    /// `intersect` returns an item which covers the intersection of the areas with the union of the captures.
+    /// It returns `None` if the areas are disjoint or captures are inconsistent.
+    pub fn intersect(&self, other: &MatchedItem<'tree>) -> Option<MatchedItem<'tree>> {
+        if !self.is_consistent_with(other) {
+            return None;
+        }
+        let area = self.area.intersection(&other.area)?;
+        let mut captures = self.captures.clone();
+        captures.extend(other.captures.clone());
+        Some(MatchedItem { area, captures })
+    }
+
+    /// `is_equivalent_to` returns whether `other` has the same range and the same captures as `self`.
+    pub fn is_equivalent_to(&self, other: &MatchedItem) -> bool {
+        self.area.start_byte() == other.area.start_byte()
+            && self.area.end_byte() == other.area.end_byte()
+            && self.captures.len() == other.captures.len()
+            && other.captures.iter().all(|(mid, ovalue)| {
+                self.capture_of(mid)
+                    .map(|svalue| svalue.as_str() == ovalue.as_str())
+                    .unwrap_or(false)
+            })
+    }
+
+    /// `is_consistent_with` returns whether every metavariable captured by both items has the same value.
+    pub fn is_consistent_with(&self, other: &MatchedItem) -> bool {
+        other.captures.iter().all(|(mid, ovalue)| {
+            self.capture_of(mid)
+                .map(|svalue| svalue.as_str() == ovalue.as_str())
+                .unwrap_or(true)
+        })
+    }
+
     pub fn satisfies<'c, T: Queryable + 'tree>(
         &self,
         constraint: &'c Constraint<T>,
@@ -154,29 +231,35 @@ impl<'tree> MatchedItem<'tree> {
                 Ok((!matches.iter().any(|m| m.0), CaptureMap::new()))
             }
 
-            Predicate::MatchRegex(r) => Ok((r.is_match(captured_item.as_str()), CaptureMap::new())),
-            Predicate::NotMatchRegex(r) => {
-                Ok((!r.is_match(captured_item.as_str()), CaptureMap::new()))
-            }
+            Predicate::MatchRegex(r) => Ok((
+                r.is_match(captured_item.value_as_str::<T>()),
+                CaptureMap::new(),
+            )),
+            Predicate::NotMatchRegex(r) => Ok((
+                !r.is_match(captured_item.value_as_str::<T>()),
+                CaptureMap::new(),
+            )),
             Predicate::MatchAnyOfRegex(rs) => Ok((
-                rs.into_iter().any(|r| r.is_match(captured_item.as_str())),
+                rs.into_iter()
+                    .any(|r| r.is_match(captured_item.value_as_str::<T>())),
                 CaptureMap::new(),
             )),
             Predicate::NotMatchAnyOfRegex(rs) => Ok((
-                !rs.into_iter().any(|r| r.is_match(captured_item.as_str())),
+                !rs.into_iter()
+                    .any(|r| r.is_match(captured_item.value_as_str::<T>())),
                 CaptureMap::new(),
             )),
 
             Predicate::BeAnyOf(candidates) => Ok((
                 candidates
                     .into_iter()
-                    .any(|r| r.as_str() == captured_item.as_str()),
+                    .any(|r| r.as_str() == captured_item.value_as_str::<T>()),
                 CaptureMap::new(),
             )),
             Predicate::NotBeAnyOf(candidates) => Ok((
                 !candidates
                     .into_iter()
-                    .any(|r| r.as_str() == captured_item.as_str()),
+                    .any(|r| r.as_str() == captured_item.value_as_str::<T>()),
                 CaptureMap::new(),
             )),
         }
@@ -1,20 +1,42 @@
 use crate::core::{
     language::Queryable,
     matcher::{
-        match_string_pattern, CaptureItem, MatchedItem, MatcherState, UnverifiedMetavariable,
+        match_string_pattern, CaptureItem, MatchedItem, MatcherState, StepBudget,
+        UnverifiedMetavariable,
     },
     node::ConsecutiveNodes,
     node::{Node, NodeType, RootNode},
-    query::QueryPattern,
-    tree::TreeTreverser,
+    query::{MetavariableId, QueryPattern},
+    tree::{KindIndex, MatchStart, TreeTreverser},
 };
 
-use std::{convert::TryFrom, marker::PhantomData};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    marker::PhantomData,
+    vec,
+};
+
+/// `Candidates` enumerates sequences of sibling nodes where a query starts matching.
+enum Candidates<'tree> {
+    /// every node in the tree is tried
+    Traverse(TreeTreverser<'tree>),
+    /// only nodes picked from `KindIndex` are tried
+    Indexed(vec::IntoIter<MatchStart<'tree>>),
+}
+
+/// `Siblings` is a sequence of sibling nodes where a query starts matching.
+enum Siblings<'tree> {
+    /// nodes matched with the query in order, from the first one
+    Ordered(Vec<&'tree Node<'tree>>),
+    /// all the named children of a node for which `Queryable::is_unordered` holds, matched with the query in any order
+    Unordered(Vec<&'tree Node<'tree>>),
+}
 
 /// `TreeMatcher` iterates possible matches between query and tree to traverse.
 pub struct TreeMatcher<'tree, 'query, T: Queryable> {
     /// Tree to traverse
-    traverser: TreeTreverser<'tree>,
+    candidates: Candidates<'tree>,
 
     /// Query
     query: &'query RootNode<'query>,
@@ -22,6 +44,9 @@ pub struct TreeMatcher<'tree, 'query, T: Queryable> {
     /// local state for implementing `Iterator`/    
     items: Vec<MatchedItem<'tree>>,
 
+    /// budget of steps; the matcher stops yielding items once it is exhausted
+    budget: StepBudget,
+
     /// just a marker
     _marker: PhantomData<T>,
 }
@@ -30,12 +55,132 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
     pub fn new(traverser: TreeTreverser<'tree>, query: &'query QueryPattern<T>) -> Self {
         TreeMatcher {
             query: &query.root_node,
-            traverser,
+            candidates: Candidates::Traverse(traverser),
             items: vec![],
+            budget: StepBudget::default(),
 
             _marker: PhantomData,
         }
     }
+
+    /// `with_index` creates a matcher which tries only nodes whose kinds can align with the query.
+    /// It yields the same items as `new` in the same order.
+    pub fn with_index(index: &KindIndex<'tree>, query: &'query QueryPattern<T>) -> Self {
+        let candidates = match Self::start_kind(&query.root_node) {
+            Some((kind, offset)) => Candidates::Indexed(index.starts(kind, offset).into_iter()),
+            None => Candidates::Traverse(TreeTreverser::new(index.root())),
+        };
+        TreeMatcher {
+            query: &query.root_node,
+            candidates,
+            items: vec![],
+            budget: StepBudget::default(),
+
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_budget(mut self, budget: StepBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// `start_kind` returns the kind of the first node of the query which needs a target node of the same kind,
+    /// with the number of metavariables before it.
+    /// It returns `None` when the position of such a node in target siblings is not fixed.
+    fn start_kind(query: &'query RootNode<'query>) -> Option<(&'static str, usize)> {
+        let qnodes = T::unwrap_root(query).iter().filter(|n| !T::is_skippable(n));
+        for (offset, qnode) in qnodes.enumerate() {
+            match qnode.kind() {
+                NodeType::Metavariable(_) => continue,
+                NodeType::Normal(kind) if !T::is_string_literal(qnode) => {
+                    return Some((T::normalize_kind(kind), offset))
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// `next_candidates` returns sequences of sibling nodes to be matched with the query next.
+    fn next_candidates(&mut self) -> Option<Vec<Siblings<'tree>>> {
+        let siblings = |parent: &'tree Node<'tree>| -> Vec<&'tree Node<'tree>> {
+            parent
+                .children
+                .iter()
+                .filter(|n| !T::is_skippable(n))
+                .collect()
+        };
+        let unordered = |parent: &'tree Node<'tree>| -> Siblings<'tree> {
+            Siblings::Unordered(
+                parent
+                    .children
+                    .iter()
+                    .filter(|n| n.is_named() && !T::is_skippable(n))
+                    .collect(),
+            )
+        };
+
+        match &mut self.candidates {
+            Candidates::Traverse(traverser) => {
+                let (depth, tnode) = traverser.next()?;
+                let mut candidates = if T::is_unordered(tnode) {
+                    vec![unordered(tnode)]
+                } else {
+                    let tnodes = siblings(tnode);
+                    (0..tnodes.len())
+                        .map(|i| Siblings::Ordered(tnodes[i..].to_vec()))
+                        .collect()
+                };
+                if depth == 0 {
+                    candidates.push(Siblings::Ordered(vec![tnode]));
+                }
+                Some(candidates)
+            }
+            Candidates::Indexed(starts) => {
+                // candidates sharing a parent are returned at once so that items are ordered as in `Traverse`
+                let first = starts.next()?;
+                let parent = match first {
+                    MatchStart::Children(parent, _)
+                    | MatchStart::AnyChild(parent)
+                    | MatchStart::Root(parent) => parent,
+                };
+                let tnodes = siblings(parent);
+                let mut candidates = vec![];
+                let mut start = Some(first);
+                while let Some(s) = start {
+                    match s {
+                        MatchStart::Children(_, i) => {
+                            candidates.push(Siblings::Ordered(tnodes[i..].to_vec()))
+                        }
+                        MatchStart::AnyChild(_) => candidates.push(unordered(parent)),
+                        MatchStart::Root(root) => candidates.push(Siblings::Ordered(vec![root])),
+                    }
+                    start = match starts.as_slice().first() {
+                        Some(MatchStart::Children(p, _))
+                        | Some(MatchStart::AnyChild(p))
+                        | Some(MatchStart::Root(p))
+                            if std::ptr::eq(*p, parent) =>
+                        {
+                            starts.next()
+                        }
+                        _ => None,
+                    };
+                }
+                Some(candidates)
+            }
+        }
+    }
+
+    /// `is_same_kind` returns whether `tnode` and `qnode` are of the same kind after `Queryable::normalize_kind`.
+    fn is_same_kind(tnode: &Node, qnode: &Node) -> bool {
+        match (tnode.kind(), qnode.kind()) {
+            (NodeType::Normal(t), NodeType::Normal(q)) => {
+                T::normalize_kind(t) == T::normalize_kind(q)
+            }
+            (t, q) => t == q,
+        }
+    }
 }
 
 impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
@@ -71,10 +216,17 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
         tsibilings: Vec<&'tree Node<'tree>>,
         qsibilings: Vec<&'query Node<'query>>,
     ) -> Vec<(MatcherState<'tree>, Option<&Node<'tree>>)> {
-        let mut queue: Vec<(usize, usize, Vec<UnverifiedMetavariable>)> = vec![(0, 0, vec![])];
+        let mut queue = SearchQueue::default();
+        queue.push(0, 0, vec![]);
         let mut result: Vec<(MatcherState, Option<&Node<'tree>>)> = vec![];
 
+        // matches of a pair of nodes do not depend on how the pair was reached, so they are calculated once
+        let mut submatches: HashMap<(usize, usize), Vec<MatcherState<'tree>>> = HashMap::new();
+
         while let Some((tidx, qidx, captures)) = queue.pop() {
+            if !self.budget.consume() {
+                break;
+            }
             match (tsibilings.get(tidx), qsibilings.get(qidx)) {
                 (t, None) => {
                     let nodes = tsibilings[..tidx.min(tsibilings.len())].to_vec();
@@ -118,14 +270,14 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
                     NodeType::Ellipsis => {
                         // NOTE: this loop must end with `tsibilings.len()`
                         for tcidx in tidx..=tsibilings.len() {
-                            queue.push((tcidx, qidx + 1, captures.clone()));
+                            queue.push(tcidx, qidx + 1, captures.clone());
                         }
                     }
                     NodeType::EllipsisMetavariable(mid) => {
                         let mut captured_nodes = vec![];
                         // NOTE: this loop must end with `tsibilings.len()`
                         for tcidx in tidx..=tsibilings.len() {
-                            queue.push((
+                            queue.push(
                                 tcidx,
                                 qidx + 1,
                                 [
@@ -133,19 +285,22 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
                                     captures.clone(),
                                 ]
                                 .concat(),
-                            ));
+                            );
                             if let Some(tchild) = tsibilings.get(tcidx) {
                                 captured_nodes.push(tchild);
                             }
                         }
                     }
                     _ => {
-                        for submatch in self.match_intermediate_node(Some(tchild), Some(qchild)) {
-                            queue.push((
+                        let submatches = submatches.entry((tidx, qidx)).or_insert_with(|| {
+                            self.match_intermediate_node(Some(tchild), Some(qchild))
+                        });
+                        for submatch in submatches.iter() {
+                            queue.push(
                                 tidx + 1,
                                 qidx + 1,
-                                [captures.clone(), submatch.captures].concat(),
-                            ));
+                                [captures.clone(), submatch.captures.clone()].concat(),
+                            );
                         }
                     }
                 },
@@ -183,6 +338,24 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
                             "internal error: ellipsis nodes were given to match_intermediate_node"
                         )
                     }
+                    NodeType::DeepExpression => {
+                        // MATCH or UNMATCH: a deep expression matches a node if its inner pattern matches the node or any of its descendants.
+                        let qinner: Vec<&'query Node<'query>> = qnode
+                            .children
+                            .iter()
+                            .filter(|n| !T::is_skippable(n))
+                            .collect();
+                        TreeTreverser::new(tnode)
+                            .flat_map(|(_, descendant)| {
+                                self.match_sibilings(vec![descendant], qinner.clone())
+                            })
+                            .filter(|(_, trailling)| trailling.is_none())
+                            .map(|(submatch, _)| MatcherState {
+                                subtree: ConsecutiveNodes::try_from(vec![tnode]).ok(),
+                                captures: submatch.captures,
+                            })
+                            .collect()
+                    }
                     _ if qnode.children.is_empty() || T::is_leaf_like(qnode) => {
                         // MATCH or UNMATCH: leaf nodes should be compared more.
                         self.match_leaf(tnode, qnode)
@@ -195,11 +368,33 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
                         // (2) both nodes' children match
 
                         // (1): check kinds
-                        if tnode.kind() != qnode.kind() {
+                        if !Self::is_same_kind(tnode, qnode) {
                             return vec![];
                         }
 
                         // (2): get matches of children
+                        if T::is_unordered(tnode) {
+                            return self
+                                .match_unordered(
+                                    tnode
+                                        .children
+                                        .iter()
+                                        .filter(|n| n.is_named() && !T::is_skippable(n))
+                                        .collect(),
+                                    qnode
+                                        .children
+                                        .iter()
+                                        .filter(|n| n.is_named() && !T::is_skippable(n))
+                                        .collect(),
+                                    false,
+                                )
+                                .into_iter()
+                                .map(|(_, captures)| MatcherState {
+                                    subtree: ConsecutiveNodes::try_from(vec![tnode]).ok(),
+                                    captures,
+                                })
+                                .collect();
+                        }
                         self.match_sibilings(
                             tnode
                                 .children
@@ -237,6 +432,83 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
         }
     }
 
+    /// `match_unordered` takes children of a node whose children are unordered, and returns matches where each of `qsibilings` matches a distinct node of `tsibilings`.
+    /// Each match accompanies with the indices of the matched nodes in `tsibilings`.
+    ///
+    /// Nodes of `tsibilings` which no query node matches are allowed only if `qsibilings` includes ellipses, or `partial` is true.
+    /// An ellipsis metavariable captures all of them.
+    /// - Example Input:
+    ///     - tsibilings: A B C
+    ///     - qsibilings: C [ellipsis] A
+    /// - Example Output:
+    ///     - ([2, 0], captures)
+    fn match_unordered(
+        &self,
+        tsibilings: Vec<&'tree Node<'tree>>,
+        qsibilings: Vec<&'query Node<'query>>,
+        partial: bool,
+    ) -> Vec<(Vec<usize>, Vec<UnverifiedMetavariable<'tree>>)> {
+        let (rests, qnodes): (Vec<&Node<'query>>, Vec<&Node<'query>>) =
+            qsibilings.into_iter().partition(|n| {
+                matches!(
+                    n.kind(),
+                    NodeType::Ellipsis | NodeType::EllipsisMetavariable(_)
+                )
+            });
+        let mut result = vec![];
+
+        // matches of a pair of nodes do not depend on the other pairs, so they are calculated once
+        let mut submatches: HashMap<(usize, usize), Vec<MatcherState<'tree>>> = HashMap::new();
+
+        // each state holds the indices of nodes matched with the first query nodes, and captures of them
+        let mut stack: Vec<(Vec<usize>, Vec<UnverifiedMetavariable<'tree>>)> =
+            vec![(vec![], vec![])];
+        while let Some((used, captures)) = stack.pop() {
+            if !self.budget.consume() {
+                break;
+            }
+            match qnodes.get(used.len()) {
+                None => {
+                    let unmatched = tsibilings
+                        .iter()
+                        .enumerate()
+                        .filter(|(tidx, _)| !used.contains(tidx))
+                        .map(|(_, tnode)| *tnode)
+                        .collect::<Vec<&Node<'tree>>>();
+                    if !unmatched.is_empty() && rests.is_empty() && !partial {
+                        continue;
+                    }
+                    let mut captures = captures;
+                    for rest in &rests {
+                        if let NodeType::EllipsisMetavariable(mid) = rest.kind() {
+                            captures.push((mid, CaptureItem::from(unmatched.clone())));
+                        }
+                    }
+                    result.push((used, captures));
+                }
+                Some(qnode) => {
+                    // nodes are pushed in reverse so that matches are yielded in the order of `tsibilings`
+                    for tidx in (0..tsibilings.len()).rev() {
+                        if used.contains(&tidx) {
+                            continue;
+                        }
+                        let submatches =
+                            submatches.entry((tidx, used.len())).or_insert_with(|| {
+                                self.match_intermediate_node(Some(tsibilings[tidx]), Some(qnode))
+                            });
+                        for submatch in submatches.iter() {
+                            stack.push((
+                                [used.clone(), vec![tidx]].concat(),
+                                [captures.clone(), submatch.captures.clone()].concat(),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
     /// `match_leaf` validates the equality of two leaf nodes with `NodeType::Normal`.
     fn match_leaf(
         &self,
@@ -248,7 +520,7 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
 
         if T::is_string_literal(tnode) && T::is_string_literal(qnode) {
             // when both of tnode and qnode is string literal, use string matcher to check the equality of them
-            match_string_pattern(tnode.as_str(), qnode.as_str())
+            match_string_pattern(T::string_value(tnode), T::string_value(qnode))
                 .into_iter()
                 .map(|captures| MatcherState {
                     subtree: ConsecutiveNodes::try_from(vec![tnode]).ok(),
@@ -259,7 +531,7 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
             // otherwise, two nodes match if and only if:
             // (1) two nodes are same kind
             // (2) two nodes are same as string
-            if tnode.kind() == qnode.kind() && T::node_value_eq(tnode, qnode) {
+            if Self::is_same_kind(tnode, qnode) && T::node_value_eq(tnode, qnode) {
                 vec![MatcherState {
                     subtree: ConsecutiveNodes::try_from(vec![tnode]).ok(),
                     captures: vec![],
@@ -271,6 +543,57 @@ impl<'tree, 'query, T: Queryable> TreeMatcher<'tree, 'query, T> {
     }
 }
 
+type SearchState<'tree> = (usize, usize, Vec<UnverifiedMetavariable<'tree>>);
+
+/// `CaptureKey` identifies a captured value in a hashable way.
+/// Captured nodes are identified by the first and the last ones since they are consecutive siblings.
+#[derive(PartialEq, Eq, Hash)]
+enum CaptureKey {
+    Empty,
+    Literal(String),
+    Nodes(usize, usize),
+}
+
+impl From<&CaptureItem<'_>> for CaptureKey {
+    fn from(item: &CaptureItem) -> Self {
+        match item {
+            CaptureItem::Empty => CaptureKey::Empty,
+            CaptureItem::Literal(s) => CaptureKey::Literal(s.clone()),
+            CaptureItem::Nodes(n) => match (n.as_vec().first(), n.as_vec().last()) {
+                (Some(first), Some(last)) => CaptureKey::Nodes(first.id(), last.id()),
+                _ => CaptureKey::Empty,
+            },
+        }
+    }
+}
+
+/// `VisitedState` is a `SearchState` whose captures are replaced with `CaptureKey`.
+type VisitedState = (usize, usize, Vec<(MetavariableId, CaptureKey)>);
+
+/// `SearchQueue` holds states of `match_sibilings` to explore.
+/// A state reached in different ways (e.g. with different splits of ellipses) yields the same matches, so it is queued only once.
+#[derive(Default)]
+struct SearchQueue<'tree> {
+    queue: Vec<SearchState<'tree>>,
+    visited: HashSet<VisitedState>,
+}
+
+impl<'tree> SearchQueue<'tree> {
+    fn push(&mut self, tidx: usize, qidx: usize, captures: Vec<UnverifiedMetavariable<'tree>>) {
+        let key = captures
+            .iter()
+            .map(|(mid, item)| (mid.clone(), CaptureKey::from(item)))
+            .collect();
+        if self.visited.insert((tidx, qidx, key)) {
+            self.queue.push((tidx, qidx, captures));
+        }
+    }
+
+    fn pop(&mut self) -> Option<SearchState<'tree>> {
+        self.queue.pop()
+    }
+}
+
 impl<'tree, 'query, T> Iterator for TreeMatcher<'tree, 'query, T>
 where
     T: Queryable,
@@ -284,30 +607,42 @@ where
             .collect();
 
         loop {
+            if self.budget.is_exhausted() {
+                return None;
+            }
             if let Some(mitem) = self.items.pop() {
                 return Some(mitem);
             }
 
-            if let Some((depth, tnode)) = self.traverser.next() {
-                let tnodes: Vec<&Node<'tree>> = tnode
-                    .children
-                    .iter()
-                    .filter(|n| !T::is_skippable(n))
-                    .collect();
-                let tcandidates =
-                    (0..tnodes.len())
-                        .map(|i| tnodes[i..].to_vec())
-                        .chain(if depth == 0 {
-                            vec![vec![tnode]].into_iter()
-                        } else {
-                            vec![].into_iter()
-                        });
+            if let Some(tcandidates) = self.next_candidates() {
                 for tsibilings in tcandidates {
-                    let items = self
-                        .match_sibilings(tsibilings, qnodes.clone())
-                        .into_iter()
-                        .filter_map(|(mitem, _)| Option::<MatchedItem>::from(mitem))
-                        .collect::<Vec<MatchedItem>>();
+                    let items = match tsibilings {
+                        Siblings::Ordered(tsibilings) => self
+                            .match_sibilings(tsibilings, qnodes.clone())
+                            .into_iter()
+                            .filter_map(|(mitem, _)| Option::<MatchedItem>::from(mitem))
+                            .collect::<Vec<MatchedItem>>(),
+                        Siblings::Unordered(tsibilings) => self
+                            .match_unordered(
+                                tsibilings.clone(),
+                                qnodes.iter().copied().filter(|n| n.is_named()).collect(),
+                                true,
+                            )
+                            .into_iter()
+                            .filter_map(|(used, captures)| {
+                                // the match spans from the first matched node to the last one
+                                let first = *used.iter().min()?;
+                                let last = *used.iter().max()?;
+                                Option::<MatchedItem>::from(MatcherState {
+                                    subtree: ConsecutiveNodes::try_from(
+                                        tsibilings[first..=last].to_vec(),
+                                    )
+                                    .ok(),
+                                    captures,
+                                })
+                            })
+                            .collect::<Vec<MatchedItem>>(),
+                    };
                     self.items.extend(items);
                 }
             } else {
@@ -1,14 +1,17 @@
-use std::convert::TryFrom;
+use std::{collections::HashMap, convert::TryFrom};
 
 use crate::core::language::Queryable;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use super::query::MetavariableId;
 
 const SHISHO_NODE_METAVARIABLE_NAME: &str = "shisho_metavariable_name";
-const SHISHO_NODE_METAVARIABLE: &str = "shisho_metavariable";
-const SHISHO_NODE_ELLIPSIS_METAVARIABLE: &str = "shisho_ellipsis_metavariable";
-const SHISHO_NODE_ELLIPSIS: &str = "shisho_ellipsis";
+pub(crate) const SHISHO_NODE_METAVARIABLE: &str = "shisho_metavariable";
+pub(crate) const SHISHO_NODE_ELLIPSIS_METAVARIABLE: &str = "shisho_ellipsis_metavariable";
+pub(crate) const SHISHO_NODE_ELLIPSIS: &str = "shisho_ellipsis";
+const SHISHO_NODE_DEEP_EXPRESSION: &str = "shisho_deep_expression";
+
 /// `Range` describes a range over a source code in a same manner as [Language Server Protocol](https://microsoft.github.io/language-server-protocol/specifications/specification-current/#range).
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Range {
@@ -26,6 +29,8 @@ pub struct Position {
 pub struct Node<'tree> {
     inner: tree_sitter::Node<'tree>,
     with_extra_newline: bool,
+    /// the kind which overrides one of `inner`; see `Queryable::query_node_kind`
+    kind_override: Option<&'static str>,
 
     pub(crate) source: &'tree [u8],
     pub children: Vec<Node<'tree>>,
@@ -36,10 +41,20 @@ pub enum NodeType {
     Metavariable(MetavariableId),
     EllipsisMetavariable(MetavariableId),
     Ellipsis,
+    /// `<... pattern ...>`, which matches a node if the inner pattern matches the node or any of its descendants
+    DeepExpression,
     Normal(&'static str),
 }
 
 fn get_metavariable_id<'a>(node: &'a Node<'_>) -> &'a str {
+    if node.kind_override.is_some() {
+        // a node marked as a metavariable is written as `:[NAME]` or `:[...NAME]`
+        return node
+            .as_str()
+            .trim_start_matches(":[")
+            .trim_start_matches("...")
+            .trim_end_matches(']');
+    }
     node.children
         .iter()
         .find(|child| child.kind() == NodeType::Normal(SHISHO_NODE_METAVARIABLE_NAME))
@@ -54,12 +69,18 @@ fn get_metavariable_id<'a>(node: &'a Node<'_>) -> &'a str {
 }
 
 impl<'tree> Node<'tree> {
+    /// `id` returns an identifier of the node which is unique in its tree.
+    pub fn id(&self) -> usize {
+        self.inner.id()
+    }
+
     pub fn kind(&self) -> NodeType {
-        match self.inner.kind() {
+        match self.kind_override.unwrap_or_else(|| self.inner.kind()) {
             s if s == SHISHO_NODE_METAVARIABLE => {
                 NodeType::Metavariable(MetavariableId(get_metavariable_id(self).to_string()))
             }
             s if s == SHISHO_NODE_ELLIPSIS => NodeType::Ellipsis,
+            s if s == SHISHO_NODE_DEEP_EXPRESSION => NodeType::DeepExpression,
             s if s == SHISHO_NODE_ELLIPSIS_METAVARIABLE => NodeType::EllipsisMetavariable(
                 MetavariableId(get_metavariable_id(self).to_string()),
             ),
@@ -108,12 +129,70 @@ impl<'tree> Node<'tree> {
         Node {
             inner: tsnode,
             with_extra_newline: extra_newline_byte == Some(tsnode.end_byte() - 1),
+            kind_override: None,
             children,
             source,
         }
     }
 }
 
+/// `SyntaxError` points to the first part of code which tree-sitter could not parse.
+#[derive(Debug, Clone, PartialEq, Error, Serialize, Deserialize)]
+#[error("syntax error at line {}, column {}:\n{}", .row + 1, .column + 1, .snippet)]
+pub struct SyntaxError {
+    /// 0-origin row of the error
+    pub row: usize,
+    /// 0-origin byte column of the error
+    pub column: usize,
+    /// the line with carets under the error
+    pub snippet: String,
+}
+
+impl SyntaxError {
+    /// `find` returns the first ERROR node, or MISSING node inserted on recovery, in `tstree`.
+    pub fn find(tstree: &tree_sitter::Tree, source: &[u8]) -> Option<Self> {
+        let node = find_error_node(tstree.root_node())?;
+        let (start, end) = (node.start_position(), node.end_position());
+
+        let line = source
+            .split(|b| *b == b'\n')
+            .nth(start.row)
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        let line = line.trim_end();
+        let (before, rest) = line.split_at(start.column.min(line.len()));
+        let width = if end.row == start.row {
+            rest.get(..end.column - start.column)
+                .map_or(1, |r| r.chars().count())
+        } else {
+            rest.chars().count()
+        };
+        // tabs are kept so that the carets are aligned in any tab width
+        let indent = before
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        Some(SyntaxError {
+            row: start.row,
+            column: start.column,
+            snippet: format!("{}\n{}{}", line, indent, "^".repeat(width.max(1))),
+        })
+    }
+}
+
+fn find_error_node(node: tree_sitter::Node<'_>) -> Option<tree_sitter::Node<'_>> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let children = node.children(&mut cursor).collect::<Vec<_>>();
+    children.into_iter().find_map(find_error_node)
+}
+
 #[derive(Debug)]
 pub struct RootNode<'tree>(Node<'tree>);
 
@@ -141,6 +220,7 @@ impl<'tree> RootNode<'tree> {
         RootNode::new(Node {
             inner: tsnode,
             with_extra_newline,
+            kind_override: None,
             children,
             source,
         })
@@ -155,6 +235,43 @@ impl<'tree> RootNode<'tree> {
     pub fn as_node(&self) -> &Node<'tree> {
         &self.0
     }
+
+    /// `with_query_kinds` overrides kinds of nodes in the query tree with ones given by `T::query_node_kind`.
+    pub fn with_query_kinds<T: Queryable>(mut self) -> Self {
+        fn mark<T: Queryable>(node: &mut Node) {
+            node.kind_override = T::query_node_kind(node);
+            if node.kind_override.is_none() {
+                node.children.iter_mut().for_each(mark::<T>);
+            }
+        }
+        mark::<T>(&mut self.0);
+        self
+    }
+
+    /// `with_deep_expressions` turns metavariables standing for deep expressions into `NodeType::DeepExpression` nodes,
+    /// whose children are the nodes of the inner patterns in `inner`.
+    pub(crate) fn with_deep_expressions(
+        mut self,
+        inner: &mut HashMap<MetavariableId, Vec<Node<'tree>>>,
+    ) -> Self {
+        fn replace<'tree>(
+            node: &mut Node<'tree>,
+            inner: &mut HashMap<MetavariableId, Vec<Node<'tree>>>,
+        ) {
+            if let NodeType::Metavariable(mid) = node.kind() {
+                if let Some(children) = inner.remove(&mid) {
+                    node.kind_override = Some(SHISHO_NODE_DEEP_EXPRESSION);
+                    node.children = children;
+                }
+                return;
+            }
+            for child in &mut node.children {
+                replace(child, inner);
+            }
+        }
+        replace(&mut self.0, inner);
+        self
+    }
 }
 
 impl<'tree> From<RootNode<'tree>> for Node<'tree> {
@@ -239,6 +356,31 @@ impl<'tree> ConsecutiveNodes<'tree> {
         self.as_vec().len()
     }
 
+    /// `contains` returns whether `other` is located within the range of `self`.
+    pub fn contains(&self, other: &ConsecutiveNodes) -> bool {
+        self.start_byte() <= other.start_byte() && other.end_byte() <= self.end_byte()
+    }
+
+    /// `intersection` returns nodes shared by `self` and `other`.
+    /// When one contains the other, the inner one is returned as it is.
+    pub fn intersection(&self, other: &ConsecutiveNodes<'tree>) -> Option<ConsecutiveNodes<'tree>> {
+        if other.contains(self) {
+            Some(self.clone())
+        } else if self.contains(other) {
+            Some(other.clone())
+        } else {
+            let inner = self
+                .inner
+                .iter()
+                .filter(|n| {
+                    other.start_byte() <= n.start_byte() && n.end_byte() <= other.end_byte()
+                })
+                .copied()
+                .collect::<Vec<&'tree Node<'tree>>>();
+            ConsecutiveNodes::try_from(inner).ok()
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
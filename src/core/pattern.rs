@@ -1,12 +1,18 @@
 use super::{
-    constraint::Constraint, language::Queryable, node::RootNode,
-    ruleset::RawPatternWithConstraints, source::NormalizedSource,
+    constraint::Constraint,
+    language::Queryable,
+    node::{RootNode, SyntaxError},
+    query::MetavariableId,
+    ruleset::RawPatternExpression,
+    source::NormalizedSource,
 };
 use anyhow::{anyhow, Result};
 use std::{
+    collections::HashMap,
     convert::{TryFrom, TryInto},
     marker::PhantomData,
 };
+use thiserror::Error;
 
 #[derive(Debug)]
 pub struct Pattern<T>
@@ -17,15 +23,89 @@ where
     with_extra_newline: bool,
 
     tstree: tree_sitter::Tree,
+    /// inner patterns of deep expressions (`<... P ...>`), which are written as metavariables named by `deep_expression_id` in `source`
+    deep_expressions: Vec<Pattern<T>>,
     _marker: PhantomData<T>,
 }
 
+const DEEP_EXPRESSION_START: &[u8] = b"<...";
+const DEEP_EXPRESSION_END: &[u8] = b"...>";
+
+/// `deep_expression_id` returns the name of the metavariable which stands for the `idx`-th deep expression in a pattern.
+fn deep_expression_id(idx: usize) -> MetavariableId {
+    MetavariableId(format!("__SHISHO_DEEP_EXPRESSION_{}", idx))
+}
+
+/// `lower_deep_expressions` replaces each deep expression (`<... P ...>`) in `source` with a metavariable, and parses `P` separately.
+/// Query grammars do not know deep expressions, so they are parsed as metavariables and replaced in `Pattern::to_root_node`.
+fn lower_deep_expressions<T: Queryable>(source: Vec<u8>) -> Result<(Vec<u8>, Vec<Pattern<T>>)> {
+    let mut lowered = Vec::with_capacity(source.len());
+    let mut inner_patterns = vec![];
+    // nesting level of deep expressions, and the start of the outermost one
+    let mut depth = 0;
+    let mut inner_start = 0;
+    let mut i = 0;
+    while i < source.len() {
+        if source[i..].starts_with(DEEP_EXPRESSION_START) {
+            if depth == 0 {
+                inner_start = i + DEEP_EXPRESSION_START.len();
+            }
+            depth += 1;
+            i += DEEP_EXPRESSION_START.len();
+            continue;
+        }
+        if depth > 0 && source[i..].starts_with(DEEP_EXPRESSION_END) {
+            depth -= 1;
+            if depth == 0 {
+                let inner = std::str::from_utf8(&source[inner_start..i])?.trim();
+                let pattern = Pattern::<T>::try_from(inner).map_err(|e| {
+                    anyhow!(
+                        "failed to parse the deep expression `<... {} ...>`: {}",
+                        inner,
+                        e
+                    )
+                })?;
+                let id = deep_expression_id(inner_patterns.len());
+                lowered.extend_from_slice(format!(":[{}]", id.0).as_bytes());
+                inner_patterns.push(pattern);
+            }
+            i += DEEP_EXPRESSION_END.len();
+            continue;
+        }
+        if depth == 0 {
+            lowered.push(source[i]);
+        }
+        i += 1;
+    }
+    if depth > 0 {
+        return Err(anyhow!("`<...` is not closed with `...>`"));
+    }
+    Ok((lowered, inner_patterns))
+}
+
 impl<T> Pattern<T>
 where
     T: Queryable,
 {
     pub fn to_root_node(&'_ self) -> RootNode<'_> {
-        RootNode::from_tstree(&self.tstree, &self.source, self.with_extra_newline)
+        let root = RootNode::from_tstree(&self.tstree, &self.source, self.with_extra_newline)
+            .with_query_kinds::<T>();
+        if self.deep_expressions.is_empty() {
+            return root;
+        }
+
+        let mut inner = self
+            .deep_expressions
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                (
+                    deep_expression_id(idx),
+                    T::unwrap_root(&p.to_root_node()).clone(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        root.with_deep_expressions(&mut inner)
     }
 
     #[inline]
@@ -54,16 +134,31 @@ where
         let mut parser = tree_sitter::Parser::new();
         parser.set_language(T::query_language())?;
 
+        let with_extra_newline = source.with_extra_newline();
+        let (source, deep_expressions) = lower_deep_expressions::<T>(source.into())?;
+
         let tstree = parser
-            .parse(source.as_ref(), None)
+            .parse(T::query_source(&source), None)
             .ok_or(anyhow!("failed to load the code"))?;
+        // a pattern with syntax errors would never match as intended
+        if let Some(e) = SyntaxError::find(&tstree, &source) {
+            return Err(e.into());
+        }
+        // a pattern without any code (e.g. an empty one, or one with comments only) has nothing to match
+        let root = tstree.root_node();
+        if (0..root.named_child_count())
+            .filter_map(|i| root.named_child(i))
+            .all(|n| n.is_extra())
+        {
+            return Err(anyhow!("the pattern has no code to match"));
+        }
 
-        let with_extra_newline = source.with_extra_newline();
         Ok(Pattern {
-            source: source.into(),
+            source,
             with_extra_newline,
 
             tstree,
+            deep_expressions,
             _marker: PhantomData,
         })
     }
@@ -96,19 +191,114 @@ impl<T: Queryable> PatternWithConstraints<T> {
     }
 }
 
-impl<T: Queryable> TryFrom<RawPatternWithConstraints> for PatternWithConstraints<T> {
+/// `PatternExpression` composes `PatternWithConstraints` with boolean operators.
+#[derive(Debug)]
+pub enum PatternExpression<T: Queryable> {
+    Pattern(PatternWithConstraints<T>),
+    AllOf(Vec<PatternExpression<T>>, Vec<Constraint<T>>),
+    AnyOf(Vec<PatternExpression<T>>),
+    NoneOf(Vec<PatternExpression<T>>),
+    Inside(Box<PatternExpression<T>>),
+    NotInside(Box<PatternExpression<T>>),
+}
+
+/// `ExpressionError` is an error on a `PatternExpression` whose operators are composed in an invalid way.
+#[derive(Debug, Error, PartialEq)]
+pub enum ExpressionError {
+    #[error("all-of requires at least one expression other than none-of, inside or not-inside")]
+    NoPositiveExpression,
+    #[error("none-of, inside and not-inside can be used only in all-of")]
+    NegativeOutsideAllOf,
+    #[error("{0} cannot be used to filter matches in all-of")]
+    NotNegative(&'static str),
+}
+
+impl<T: Queryable> PatternExpression<T> {
+    /// `operator` returns the name of the operator in rule set files.
+    pub fn operator(&self) -> &'static str {
+        match self {
+            PatternExpression::Pattern(_) => "pattern",
+            PatternExpression::AllOf(_, _) => "all-of",
+            PatternExpression::AnyOf(_) => "any-of",
+            PatternExpression::NoneOf(_) => "none-of",
+            PatternExpression::Inside(_) => "inside",
+            PatternExpression::NotInside(_) => "not-inside",
+        }
+    }
+
+    /// `is_positive` returns whether the expression produces matches by itself.
+    /// Other expressions just filter matches of positive ones in `all-of`.
+    pub fn is_positive(&self) -> bool {
+        matches!(
+            self,
+            PatternExpression::Pattern(_)
+                | PatternExpression::AllOf(_, _)
+                | PatternExpression::AnyOf(_)
+        )
+    }
+}
+
+impl<T: Queryable> TryFrom<RawPatternExpression> for PatternExpression<T> {
     type Error = anyhow::Error;
 
-    fn try_from(rpc: RawPatternWithConstraints) -> Result<Self> {
-        let pattern = Pattern::<T>::try_from(rpc.pattern.as_str())?;
-        let constraints = rpc
-            .constraints
-            .iter()
-            .map(|x| Constraint::try_from(x.clone()))
-            .collect::<Result<Vec<Constraint<T>>>>()?;
-        Ok(Self {
-            pattern,
-            constraints,
-        })
+    fn try_from(rpe: RawPatternExpression) -> Result<Self> {
+        let from_vec = |rpes: Vec<RawPatternExpression>| {
+            rpes.into_iter()
+                .map(PatternExpression::<T>::try_from)
+                .collect::<Result<Vec<PatternExpression<T>>>>()
+        };
+
+        match rpe {
+            // `pattern-not`, `pattern-inside` and `pattern-not-inside` are evaluated as `all-of`
+            RawPatternExpression::Pattern(rpc) if !rpc.filters.is_empty() => {
+                PatternExpression::try_from(rpc.into_expression())
+            }
+            RawPatternExpression::Pattern(rpc) => {
+                let pattern = Pattern::<T>::try_from(rpc.pattern.as_str())?;
+                let constraints = rpc
+                    .constraints
+                    .into_iter()
+                    .map(Constraint::try_from)
+                    .collect::<Result<Vec<Constraint<T>>>>()?;
+                Ok(PatternExpression::Pattern(PatternWithConstraints::new(
+                    pattern,
+                    constraints,
+                )))
+            }
+            RawPatternExpression::AllOf {
+                all_of,
+                constraints,
+            } => {
+                let children = from_vec(all_of)?;
+                if !children.iter().any(|c| c.is_positive()) {
+                    return Err(ExpressionError::NoPositiveExpression.into());
+                }
+                let constraints = constraints
+                    .into_iter()
+                    .map(Constraint::try_from)
+                    .collect::<Result<Vec<Constraint<T>>>>()?;
+                Ok(PatternExpression::AllOf(children, constraints))
+            }
+            RawPatternExpression::AnyOf { any_of } => {
+                let children = from_vec(any_of)?;
+                if children.iter().any(|c| !c.is_positive()) {
+                    return Err(ExpressionError::NegativeOutsideAllOf.into());
+                }
+                Ok(PatternExpression::AnyOf(children))
+            }
+            RawPatternExpression::NoneOf { none_of } => {
+                let children = from_vec(none_of)?;
+                if children.iter().any(|c| !c.is_positive()) {
+                    return Err(ExpressionError::NegativeOutsideAllOf.into());
+                }
+                Ok(PatternExpression::NoneOf(children))
+            }
+            RawPatternExpression::Inside { inside } => Ok(PatternExpression::Inside(Box::new(
+                PatternExpression::try_from(*inside)?,
+            ))),
+            RawPatternExpression::NotInside { not_inside } => Ok(PatternExpression::NotInside(
+                Box::new(PatternExpression::try_from(*not_inside)?),
+            )),
+        }
     }
 }
@@ -0,0 +1,246 @@
+//! This module defines a prefilter which skips rules whose required literals do not appear in a target.
+//!
+//! A leaf of a pattern matches only a node with the same text, so a pattern cannot match a target which lacks any of its leaves.
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashSet;
+
+use crate::core::{
+    language::Queryable,
+    node::{Node, NodeType},
+    pattern::{Pattern, PatternExpression},
+};
+
+/// `Requirement` describes literals which must appear in a target for an expression to match.
+/// Literals are compared case-insensitively since some languages (e.g. Dockerfile) have case-insensitive keywords.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    /// no literal is required
+    Nothing,
+    Literal(String),
+    AllOf(Vec<Requirement>),
+    AnyOf(Vec<Requirement>),
+}
+
+impl Requirement {
+    fn all(children: Vec<Requirement>) -> Self {
+        let mut children = children
+            .into_iter()
+            .filter(|c| *c != Requirement::Nothing)
+            .collect::<Vec<Requirement>>();
+        match children.len() {
+            0 => Requirement::Nothing,
+            1 => children.pop().unwrap(),
+            _ => Requirement::AllOf(children),
+        }
+    }
+
+    fn any(mut children: Vec<Requirement>) -> Self {
+        if children.is_empty() || children.contains(&Requirement::Nothing) {
+            return Requirement::Nothing;
+        }
+        match children.len() {
+            1 => children.pop().unwrap(),
+            _ => Requirement::AnyOf(children),
+        }
+    }
+
+    /// `from_expressions` returns the requirement for any of `expressions` to match.
+    pub fn from_expressions<T: Queryable>(expressions: &[PatternExpression<T>]) -> Self {
+        Self::any(expressions.iter().map(Self::from_expression).collect())
+    }
+
+    pub fn from_expression<T: Queryable>(expression: &PatternExpression<T>) -> Self {
+        match expression {
+            PatternExpression::Pattern(pc) => Self::from_pattern(&pc.pattern),
+            PatternExpression::AllOf(children, _) => {
+                Self::all(children.iter().map(Self::from_expression).collect())
+            }
+            PatternExpression::AnyOf(children) => Self::from_expressions(children),
+            PatternExpression::Inside(inner) => Self::from_expression(inner),
+            PatternExpression::NoneOf(_) | PatternExpression::NotInside(_) => Requirement::Nothing,
+        }
+    }
+
+    pub fn from_pattern<T: Queryable>(pattern: &Pattern<T>) -> Self {
+        let root = pattern.to_root_node();
+        let mut literals = vec![];
+        for node in T::unwrap_root(&root) {
+            collect_literals::<T>(node, &mut literals);
+        }
+        Self::all(literals.into_iter().map(Requirement::Literal).collect())
+    }
+
+    fn literals<'a>(&'a self, literals: &mut Vec<&'a str>) {
+        match self {
+            Requirement::Nothing => (),
+            Requirement::Literal(l) => literals.push(l),
+            Requirement::AllOf(children) | Requirement::AnyOf(children) => {
+                for c in children {
+                    c.literals(literals);
+                }
+            }
+        }
+    }
+
+    /// `is_satisfied` returns whether the requirement holds with `found` literals.
+    pub fn is_satisfied(&self, found: &HashSet<&str>) -> bool {
+        match self {
+            Requirement::Nothing => true,
+            Requirement::Literal(l) => found.contains(l.as_str()),
+            Requirement::AllOf(children) => children.iter().all(|c| c.is_satisfied(found)),
+            Requirement::AnyOf(children) => children.iter().any(|c| c.is_satisfied(found)),
+        }
+    }
+}
+
+static METAVARIABLE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r":\[(\.\.\.)?[A-Z_][A-Z_0-9]*\]").unwrap());
+
+/// `collect_literals` collects texts of leaves which a match of `node` must contain.
+/// Metavariables and ellipses, which may match various texts, are excluded.
+fn collect_literals<T: Queryable>(node: &Node, literals: &mut Vec<String>) {
+    match node.kind() {
+        NodeType::Metavariable(_) | NodeType::EllipsisMetavariable(_) | NodeType::Ellipsis => (),
+        _ if T::is_skippable(node) => (),
+        _ if T::is_string_literal(node) || T::is_leaf_like(node) => {
+            // string literals may contain metavariables, so only the fragments between them are required
+            let value = if T::is_string_literal(node) {
+                T::string_value(node)
+            } else {
+                node.as_str()
+            };
+            for fragment in METAVARIABLE.split(value) {
+                if is_significant(fragment) {
+                    literals.push(fragment.to_ascii_lowercase());
+                }
+            }
+        }
+        _ if node.children.is_empty() => {
+            if is_significant(node.as_str()) {
+                literals.push(node.as_str().to_ascii_lowercase());
+            }
+        }
+        _ => {
+            for child in &node.children {
+                collect_literals::<T>(child, literals);
+            }
+        }
+    }
+}
+
+/// `is_significant` returns whether `literal` is worth checking; punctuations and short tokens appear in almost all the targets.
+fn is_significant(literal: &str) -> bool {
+    literal.len() >= 2
+        && literal.chars().any(char::is_alphanumeric)
+        && !literal.chars().any(char::is_whitespace)
+}
+
+/// `Prefilter` finds literals of requirements in a target at once.
+#[derive(Debug)]
+pub struct Prefilter {
+    literals: Vec<String>,
+    automaton: AhoCorasick,
+}
+
+impl Prefilter {
+    pub fn new<'a>(requirements: impl IntoIterator<Item = &'a Requirement>) -> Self {
+        let mut literals = vec![];
+        for r in requirements {
+            r.literals(&mut literals);
+        }
+        let literals = literals
+            .into_iter()
+            .collect::<HashSet<&str>>()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let automaton = AhoCorasickBuilder::new()
+            .ascii_case_insensitive(true)
+            .build(&literals);
+        Prefilter {
+            literals,
+            automaton,
+        }
+    }
+
+    /// `find` returns literals which appear in `body`.
+    pub fn find(&self, body: &str) -> HashSet<&str> {
+        self.automaton
+            .find_overlapping_iter(body)
+            .map(|m| self.literals[m.pattern()].as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::language::HCL;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_requirement() {
+        let r = Requirement::all(vec![
+            Requirement::Literal("aws_s3_bucket".into()),
+            Requirement::any(vec![
+                Requirement::Literal("acl".into()),
+                Requirement::Literal("policy".into()),
+            ]),
+            Requirement::Nothing,
+        ]);
+        let prefilter = Prefilter::new(vec![&r]);
+
+        assert!(r.is_satisfied(&prefilter.find(r#"resource "AWS_S3_BUCKET" "a" { policy = "" }"#)));
+        assert!(!r.is_satisfied(&prefilter.find(r#"resource "aws_s3_bucket" "a" {}"#)));
+        assert!(!r.is_satisfied(&prefilter.find(r#"acl = "private""#)));
+
+        let r = Requirement::any(vec![
+            Requirement::Literal("acl".into()),
+            Requirement::Nothing,
+        ]);
+        assert_eq!(r, Requirement::Nothing);
+        assert!(r.is_satisfied(&HashSet::new()));
+    }
+
+    #[test]
+    fn test_requirement_from_pattern() {
+        let pattern = Pattern::<HCL>::try_from(
+            r#"resource "aws_s3_bucket" :[NAME] {
+  acl = "public-:[X]"
+  ...
+}"#,
+        )
+        .unwrap();
+        let r = Requirement::from_pattern(&pattern);
+        let mut literals = vec![];
+        r.literals(&mut literals);
+        assert!(literals.contains(&"resource"));
+        assert!(literals.contains(&"\"aws_s3_bucket\""));
+        assert!(literals.contains(&"acl"));
+        assert!(!literals.contains(&"name"));
+
+        let prefilter = Prefilter::new(vec![&r]);
+        assert!(r.is_satisfied(&prefilter.find(
+            r#"resource "aws_s3_bucket" "b" {
+  acl = "public-read"
+}"#
+        )));
+        assert!(!r.is_satisfied(&prefilter.find(
+            r#"resource "aws_s3_bucket" "b" {
+  acl = "private"
+}"#
+        )));
+    }
+
+    #[test]
+    fn test_significant_literal() {
+        assert!(is_significant("exec"));
+        assert!(is_significant("FROM"));
+        assert!(!is_significant("="));
+        assert!(!is_significant("a"));
+        assert!(!is_significant("a b"));
+    }
+}
@@ -1,6 +1,9 @@
 mod builder;
+mod edit;
 mod literal;
 
+pub use self::edit::*;
+
 use crate::core::{language::Queryable, matcher::MatchedItem, node::RootNode, pattern::Pattern};
 use anyhow::Result;
 
@@ -75,6 +75,9 @@ where
             NodeType::Ellipsis => Err(anyhow!(
                 "cannot use ellipsis operator inside the transformation query"
             )),
+            NodeType::DeepExpression => Err(anyhow!(
+                "cannot use deep expression operator inside the transformation query"
+            )),
             NodeType::Metavariable(mid) | NodeType::EllipsisMetavariable(mid) => {
                 self.from_metavariable(node, &mid.0)
             }
@@ -0,0 +1,220 @@
+use crate::core::{language::Queryable, matcher::MatchedItem};
+use anyhow::{anyhow, Result};
+use std::cmp::Reverse;
+
+use super::RewriteOption;
+
+/// `Edit` describes a replacement of the byte range `[start_byte, end_byte)` of a source with `body`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub body: String,
+}
+
+impl Edit {
+    pub fn new(start_byte: usize, end_byte: usize, body: String) -> Self {
+        Self {
+            start_byte,
+            end_byte,
+            body,
+        }
+    }
+
+    /// `from_rewrite` generates an edit which replaces the area of `item` with the snippet generated by `roption`.
+    /// `source_len` is used to strip an extra newline that may be added on parsing.
+    pub fn from_rewrite<T: Queryable>(
+        source_len: usize,
+        item: &MatchedItem,
+        roption: &RewriteOption<T>,
+    ) -> Result<Self> {
+        Ok(Self::new(
+            item.area.start_byte().min(source_len),
+            item.area.end_byte().min(source_len),
+            roption.to_rewritten_snippet(item)?,
+        ))
+    }
+
+    #[inline]
+    fn contains(&self, other: &Edit) -> bool {
+        self.start_byte <= other.start_byte && other.end_byte <= self.end_byte
+    }
+}
+
+/// `EditStatus` describes how an edit in `EditSet` was handled on applying the set.
+/// Indices in each variant point to the edit which was applied instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditStatus {
+    Applied,
+    /// the edit was the same as another applied edit
+    Duplicated(usize),
+    /// the edit was inside another applied edit
+    Nested(usize),
+    /// the edit partially overlapped with another applied edit
+    Overlapped(usize),
+}
+
+impl EditStatus {
+    /// `is_effective` returns whether the change described by the edit is included in the result.
+    pub fn is_effective(&self) -> bool {
+        matches!(self, EditStatus::Applied | EditStatus::Duplicated(_))
+    }
+
+    /// `conflict` describes why the edit was left out of the result, if it was.
+    pub fn conflict(&self) -> Option<&'static str> {
+        match self {
+            EditStatus::Applied | EditStatus::Duplicated(_) => None,
+            EditStatus::Nested(_) => Some("inside another change"),
+            EditStatus::Overlapped(_) => Some("overlapped with another change"),
+        }
+    }
+}
+
+/// `EditSet` collects edits for a single source and applies them at once.
+///
+/// Edits are applied in order of their start bytes. When two edits conflict, the one which starts earlier wins;
+/// if both start at the same byte, the longer one wins; if they are still tied, the one added earlier wins.
+#[derive(Debug, Default, Clone)]
+pub struct EditSet {
+    edits: Vec<Edit>,
+}
+
+impl EditSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `push` adds an edit to the set and returns its index.
+    pub fn push(&mut self, edit: Edit) -> usize {
+        self.edits.push(edit);
+        self.edits.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Edit> {
+        self.edits.get(idx)
+    }
+
+    /// `resolve` determines which edits can be applied together.
+    /// The result is indexed in the same order as edits were pushed.
+    pub fn resolve(&self) -> Vec<EditStatus> {
+        let mut order = (0..self.edits.len()).collect::<Vec<usize>>();
+        order.sort_by_key(|idx| {
+            let e = &self.edits[*idx];
+            (e.start_byte, Reverse(e.end_byte), *idx)
+        });
+
+        let mut statuses = vec![EditStatus::Applied; self.edits.len()];
+        let mut last_applied: Option<usize> = None;
+        for idx in order {
+            let edit = &self.edits[idx];
+            if let Some(last_idx) = last_applied {
+                let last = &self.edits[last_idx];
+                if *last == *edit {
+                    statuses[idx] = EditStatus::Duplicated(last_idx);
+                    continue;
+                } else if edit.start_byte < last.end_byte {
+                    statuses[idx] = if last.contains(edit) {
+                        EditStatus::Nested(last_idx)
+                    } else {
+                        EditStatus::Overlapped(last_idx)
+                    };
+                    continue;
+                }
+            }
+            last_applied = Some(idx);
+        }
+        statuses
+    }
+
+    /// `apply` applies all the non-conflicting edits to `source` and returns the result with statuses of the edits.
+    pub fn apply(&self, source: &str) -> Result<(String, Vec<EditStatus>)> {
+        let statuses = self.resolve();
+
+        let mut applied = self
+            .edits
+            .iter()
+            .zip(statuses.iter())
+            .enumerate()
+            .filter(|(_, (_, s))| **s == EditStatus::Applied)
+            .map(|(idx, (e, _))| (idx, e))
+            .collect::<Vec<(usize, &Edit)>>();
+        applied.sort_by_key(|(idx, e)| (e.start_byte, *idx));
+
+        let mut body = String::with_capacity(source.len());
+        let mut last_end_byte = 0;
+        for (_, edit) in applied {
+            let before = source.get(last_end_byte..edit.start_byte).ok_or(anyhow!(
+                "edit range {}..{} is invalid",
+                edit.start_byte,
+                edit.end_byte
+            ))?;
+            body += before;
+            body += edit.body.as_str();
+            last_end_byte = edit.end_byte;
+        }
+        body += source
+            .get(last_end_byte..)
+            .ok_or(anyhow!("edit range ends at invalid byte {}", last_end_byte))?;
+
+        Ok((body, statuses))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start_byte: usize, end_byte: usize, body: &str) -> Edit {
+        Edit::new(start_byte, end_byte, body.into())
+    }
+
+    #[test]
+    fn test_apply_disjoint_edits() {
+        let mut edits = EditSet::new();
+        edits.push(edit(8, 11, "qux"));
+        edits.push(edit(0, 3, "hoge"));
+        edits.push(edit(4, 4, "new "));
+
+        let (body, statuses) = edits.apply("foo bar baz").unwrap();
+        assert_eq!(body, "hoge new bar qux");
+        assert_eq!(statuses, vec![EditStatus::Applied; 3]);
+    }
+
+    #[test]
+    fn test_apply_conflicting_edits() {
+        let mut edits = EditSet::new();
+        edits.push(edit(4, 7, "inner"));
+        edits.push(edit(0, 7, "outer"));
+        edits.push(edit(5, 11, "overlapped"));
+        edits.push(edit(0, 7, "outer"));
+        edits.push(edit(0, 7, "another"));
+
+        let (body, statuses) = edits.apply("foo bar baz").unwrap();
+        assert_eq!(body, "outer baz");
+        assert_eq!(
+            statuses,
+            vec![
+                EditStatus::Nested(1),
+                EditStatus::Applied,
+                EditStatus::Overlapped(1),
+                EditStatus::Duplicated(1),
+                EditStatus::Nested(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_invalid_edits() {
+        let mut edits = EditSet::new();
+        edits.push(edit(4, 20, "x"));
+        assert!(edits.apply("foo bar baz").is_err());
+    }
+}
@@ -1,13 +1,24 @@
 #[cfg(test)]
 mod test;
 
+pub mod compiled;
+pub mod validation;
+
+mod error;
+mod translation;
+pub use self::error::*;
+
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, fs::File, path::Path, str::FromStr};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use walkdir::WalkDir;
 
 use crate::core::{
-    language::Queryable, matcher::MatchedItem, pattern::PatternWithConstraints, tree::RefTreeView,
+    language::Queryable, matcher::MatchedItem, pattern::PatternExpression, tree::RefTreeView,
 };
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -29,23 +40,218 @@ pub struct Rule {
     pub tags: Vec<Tag>,
 
     #[serde(default)]
-    patterns: Vec<RawPatternWithConstraints>,
+    patterns: Vec<RawPatternExpression>,
 
     pattern: Option<String>,
     #[serde(default)]
     constraints: Vec<RawConstraint>,
 
+    #[serde(flatten)]
+    filters: RawPatternFilters,
+
     #[serde(default)]
     rewrite_options: Vec<String>,
     rewrite: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
 pub struct RawPatternWithConstraints {
     pub pattern: String,
 
     #[serde(default)]
     pub constraints: Vec<RawConstraint>,
+
+    #[serde(flatten)]
+    pub filters: RawPatternFilters,
+}
+
+impl RawPatternWithConstraints {
+    /// `into_expression` lowers the filters of the pattern into negative expressions in `all-of`.
+    /// The constraints are moved to the `all-of` so that they can target metavariables captured by `pattern-inside`.
+    pub fn into_expression(self) -> RawPatternExpression {
+        if self.filters.is_empty() {
+            return self.into();
+        }
+        let filters = self.filters.to_expressions();
+        RawPatternExpression::AllOf {
+            all_of: [vec![self.pattern.into()], filters].concat(),
+            constraints: self.constraints,
+        }
+    }
+}
+
+/// `RawPatternExpression` is an item of `patterns`, which is either of a single pattern or a composition of expressions.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged, deny_unknown_fields, remote = "Self")]
+pub enum RawPatternExpression {
+    /// matches parts of code which all of the positive expressions match, and filters them with the negative ones
+    AllOf {
+        #[serde(rename = "all-of")]
+        all_of: Vec<RawPatternExpression>,
+
+        #[serde(default)]
+        constraints: Vec<RawConstraint>,
+    },
+    /// matches parts of code which any of the expressions match
+    AnyOf {
+        #[serde(rename = "any-of")]
+        any_of: Vec<RawPatternExpression>,
+    },
+    /// (only in `all-of`) excludes parts of code which any of the expressions match
+    NoneOf {
+        #[serde(rename = "none-of")]
+        none_of: Vec<RawPatternExpression>,
+    },
+    /// (only in `all-of`) excludes parts of code which are not inside a match of the expression
+    Inside {
+        inside: Box<RawPatternExpression>,
+    },
+    /// (only in `all-of`) excludes parts of code which are inside a match of the expression
+    NotInside {
+        #[serde(rename = "not-inside")]
+        not_inside: Box<RawPatternExpression>,
+    },
+    Pattern(RawPatternWithConstraints),
+}
+
+const PATTERN_EXPRESSION_OPERATORS: [&str; 6] = [
+    "pattern",
+    "all-of",
+    "any-of",
+    "none-of",
+    "inside",
+    "not-inside",
+];
+
+impl<'de> Deserialize<'de> for RawPatternExpression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // NOTE: untagged enums accept unknown fields of `RawPatternWithConstraints`, so an item like `{ pattern: .., any-of: .. }` needs to be rejected here.
+        let value = serde_yaml::Value::deserialize(deserializer)?;
+        let operators = value
+            .as_mapping()
+            .map(|m| {
+                m.iter()
+                    .filter_map(|(k, _)| k.as_str())
+                    .filter(|k| PATTERN_EXPRESSION_OPERATORS.contains(k))
+                    .count()
+            })
+            .unwrap_or(0);
+        if operators != 1 {
+            return Err(D::Error::custom(format!(
+                "each item of `patterns` requires exactly one of {}",
+                PATTERN_EXPRESSION_OPERATORS
+                    .iter()
+                    .map(|o| format!("`{}`", o))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )));
+        }
+        RawPatternExpression::deserialize(value).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for RawPatternExpression {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        RawPatternExpression::serialize(self, serializer)
+    }
+}
+
+impl From<RawPatternWithConstraints> for RawPatternExpression {
+    fn from(rpc: RawPatternWithConstraints) -> Self {
+        RawPatternExpression::Pattern(rpc)
+    }
+}
+
+impl From<String> for RawPatternExpression {
+    fn from(pattern: String) -> Self {
+        RawPatternExpression::Pattern(RawPatternWithConstraints {
+            pattern,
+            ..Default::default()
+        })
+    }
+}
+
+/// `RawPatternFilters` holds patterns to filter matches of a pattern by their positions.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct RawPatternFilters {
+    /// excludes matches whose range equals to a match of one of these patterns
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub pattern_not: Vec<String>,
+
+    /// excludes matches which are not inside a match of each of these patterns
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub pattern_inside: Vec<String>,
+
+    /// excludes matches which are inside a match of one of these patterns
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub pattern_not_inside: Vec<String>,
+}
+
+impl RawPatternFilters {
+    pub fn is_empty(&self) -> bool {
+        self.pattern_not.is_empty()
+            && self.pattern_inside.is_empty()
+            && self.pattern_not_inside.is_empty()
+    }
+
+    /// `to_expressions` converts the filters into expressions which can be put in `all-of`.
+    fn to_expressions(&self) -> Vec<RawPatternExpression> {
+        let nots = self
+            .pattern_not
+            .iter()
+            .map(|p| RawPatternExpression::NoneOf {
+                none_of: vec![p.clone().into()],
+            });
+        let insides = self
+            .pattern_inside
+            .iter()
+            .map(|p| RawPatternExpression::Inside {
+                inside: Box::new(p.clone().into()),
+            });
+        let not_insides = self
+            .pattern_not_inside
+            .iter()
+            .map(|p| RawPatternExpression::NotInside {
+                not_inside: Box::new(p.clone().into()),
+            });
+        nots.chain(insides).chain(not_insides).collect()
+    }
+
+    fn concat(&self, other: &RawPatternFilters) -> RawPatternFilters {
+        RawPatternFilters {
+            pattern_not: [self.pattern_not.clone(), other.pattern_not.clone()].concat(),
+            pattern_inside: [self.pattern_inside.clone(), other.pattern_inside.clone()].concat(),
+            pattern_not_inside: [
+                self.pattern_not_inside.clone(),
+                other.pattern_not_inside.clone(),
+            ]
+            .concat(),
+        }
+    }
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => Ok(vec![s]),
+        OneOrMany::Many(v) => Ok(v),
+    }
 }
 
 impl Rule {
@@ -64,8 +270,9 @@ impl Rule {
 
             title: None,
 
-            patterns,
+            patterns: patterns.into_iter().map(|p| p.into()).collect(),
             constraints: vec![],
+            filters: RawPatternFilters::default(),
             rewrite_options,
             tags,
 
@@ -75,6 +282,8 @@ impl Rule {
         }
     }
 
+    /// `find` compiles the patterns of the rule and returns their matches in `tree`.
+    /// Use `compiled::CompiledRule` to reuse compiled patterns for multiple trees.
     pub fn find<'tree, 'item, T>(
         &self,
         tree: &'tree RefTreeView<'tree, T>,
@@ -83,29 +292,36 @@ impl Rule {
         T: Queryable,
         'tree: 'item,
     {
-        let patterns = self.get_patterns()?;
         let mut matches = vec![];
-        for rpc in patterns {
-            let pc = PatternWithConstraints::<T>::try_from(rpc)?;
-            let lmatches = tree
-                .matches(&pc.as_query())
-                .collect::<Result<Vec<MatchedItem>>>()?;
-            matches.extend(lmatches);
+        for rpe in self.get_patterns()? {
+            let pe = PatternExpression::<T>::try_from(rpe)?;
+            matches.extend(tree.matches_expression(&pe)?);
         }
         Ok(matches)
     }
 
-    pub fn get_patterns(&self) -> Result<Vec<RawPatternWithConstraints>> {
+    pub fn get_patterns(&self) -> Result<Vec<RawPatternExpression>> {
         match (&self.pattern, &self.patterns) {
             (Some(p), patterns) if patterns.is_empty() => Ok(vec![RawPatternWithConstraints {
                 pattern: p.to_string(),
                 constraints: self.constraints.clone(),
-            }]),
+                filters: self.filters.clone(),
+            }
+            .into()]),
             (None, patterns) if !patterns.is_empty() => Ok(patterns
-                .into_iter()
-                .map(|p| RawPatternWithConstraints {
-                    pattern: p.pattern.to_string(),
-                    constraints: [p.constraints.clone(), self.constraints.clone()].concat(),
+                .iter()
+                .map(|p| match p {
+                    RawPatternExpression::Pattern(p) => RawPatternWithConstraints {
+                        pattern: p.pattern.to_string(),
+                        constraints: [p.constraints.clone(), self.constraints.clone()].concat(),
+                        filters: p.filters.concat(&self.filters),
+                    }
+                    .into(),
+                    e if self.constraints.is_empty() && self.filters.is_empty() => e.clone(),
+                    e => RawPatternExpression::AllOf {
+                        all_of: [vec![e.clone()], self.filters.to_expressions()].concat(),
+                        constraints: self.constraints.clone(),
+                    },
                 })
                 .collect()),
             _ => Err(anyhow::anyhow!(
@@ -134,13 +350,23 @@ impl Tag {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// `Severity` of a rule. Variants are ordered from the least severe one; `Unknown` is regarded as the least.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
+    Unknown,
     Low,
     Medium,
     High,
     Critical,
-    Unknown,
+}
+
+impl FromStr for Severity {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(&s.to_lowercase())
+    }
 }
 
 impl Rule {
@@ -171,6 +397,10 @@ pub struct RawConstraint {
     #[serde(default)]
     pub constraints: Vec<RawConstraint>,
 
+    /// filters applied to matches of `pattern` or each of `patterns`
+    #[serde(flatten)]
+    pub filters: RawPatternFilters,
+
     pub string: Option<String>,
     #[serde(default)]
     pub strings: Vec<String>,
@@ -182,16 +412,23 @@ pub struct RawConstraint {
 
 impl RawConstraint {
     pub fn get_pattern_with_constraints(&self) -> Result<Vec<RawPatternWithConstraints>> {
+        if self.pattern.is_none() && self.patterns.is_empty() && !self.filters.is_empty() {
+            return Err(anyhow::anyhow!(
+                "`pattern-not`, `pattern-inside` and `pattern-not-inside` require `pattern` or `patterns`."
+            ));
+        }
         match (&self.pattern, &self.patterns) {
             (Some(p), patterns) if patterns.is_empty() => Ok(vec![RawPatternWithConstraints {
                 pattern: p.to_string(),
                 constraints: self.constraints.clone(),
+                filters: self.filters.clone(),
             }]),
             (None, patterns) if !patterns.is_empty() => Ok(patterns
-                .into_iter()
+                .iter()
                 .map(|p| RawPatternWithConstraints {
                     pattern: p.pattern.to_string(),
                     constraints: [p.constraints.clone(), self.constraints.clone()].concat(),
+                    filters: p.filters.concat(&self.filters),
                 })
                 .collect()),
             (None, patterns) if patterns.is_empty() => Ok(vec![]),
@@ -251,6 +488,11 @@ pub enum Language {
     HCL,
     Dockerfile,
     Go,
+    Kubernetes,
+    CloudFormation,
+    ARM,
+    #[serde(rename = "terraform-json")]
+    TerraformJSON,
 }
 
 impl FromStr for Language {
@@ -269,10 +511,28 @@ impl FromStr for RuleSet {
     }
 }
 
+/// `LoadMode` decides whether loading rule set files stops at the first error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadMode {
+    /// stops at the first file with errors
+    FailFast,
+    /// loads all the files and returns all the errors in them
+    CollectErrors,
+}
+
 pub fn from_path<P: AsRef<Path>>(ruleset_path: P) -> Result<Vec<RuleSet>> {
+    Ok(load(ruleset_path, LoadMode::FailFast)?)
+}
+
+/// `load` loads rule sets from `ruleset_path`, which is either of a rule set file or a directory containing them.
+pub fn load<P: AsRef<Path>>(
+    ruleset_path: P,
+    mode: LoadMode,
+) -> Result<Vec<RuleSet>, RuleSetErrors> {
     let ruleset_path: &Path = ruleset_path.as_ref();
-    if ruleset_path.is_dir() {
+    let paths = if ruleset_path.is_dir() {
         WalkDir::new(ruleset_path)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|e| e.ok())
             .map(|e| e.into_path())
@@ -283,19 +543,74 @@ pub fn from_path<P: AsRef<Path>>(ruleset_path: P) -> Result<Vec<RuleSet>> {
                         Some("yaml") | Some("yml")
                     )
             })
-            .map(from_filepath)
-            .collect::<Result<Vec<RuleSet>>>()
+            .collect::<Vec<PathBuf>>()
+    } else {
+        vec![ruleset_path.to_path_buf()]
+    };
+
+    let mut rulesets = vec![];
+    let mut errors = RuleSetErrors::default();
+    for p in paths {
+        match load_file(&p) {
+            Ok(r) => rulesets.push(r),
+            Err(e) => {
+                errors.0.extend(e.0);
+                if mode == LoadMode::FailFast {
+                    break;
+                }
+            }
+        }
+    }
+    if errors.0.is_empty() {
+        Ok(rulesets)
     } else {
-        Ok(vec![from_filepath(ruleset_path)?])
+        Err(errors)
     }
 }
 
+fn load_file(p: &Path) -> Result<RuleSet, RuleSetErrors> {
+    let body = std::fs::read_to_string(p).map_err(|e| RuleSetError::io(p, e))?;
+    parse(&body, Some(p))
+}
+
 pub fn from_filepath<P: AsRef<Path>>(p: P) -> Result<RuleSet> {
-    let f = File::open(p)?;
-    from_reader(f)
+    Ok(load_file(p.as_ref())?)
+}
+
+pub fn from_reader<R: std::io::Read>(mut r: R) -> Result<RuleSet> {
+    let mut body = String::new();
+    r.read_to_string(&mut body)?;
+    Ok(parse(&body, None)?)
 }
 
-pub fn from_reader<R: std::io::Read>(r: R) -> Result<RuleSet> {
-    let rset: RuleSet = serde_yaml::from_reader(r)?;
-    Ok(rset)
+/// `parse` parses a rule set in `body`, which is read from `path`, and checks conflicts between fields of each rule.
+pub fn parse(body: &str, path: Option<&Path>) -> Result<RuleSet, RuleSetErrors> {
+    let rset: RuleSet =
+        serde_yaml::from_str(body).map_err(|e| RuleSetError::from_yaml_error(e, body, path))?;
+
+    let lines = rule_lines(body, rset.rules.iter().map(|r| r.id.as_str()));
+    let mut errors = vec![];
+    for (rule, line) in rset.rules.iter().zip(lines) {
+        let invalid = |field: &str, e: anyhow::Error| RuleSetError::InvalidRule {
+            origin: Origin {
+                path: path.map(Path::to_path_buf),
+                line: Some(line),
+                column: None,
+                rule_id: Some(rule.id.clone()),
+                field: Some(field.into()),
+            },
+            message: e.to_string(),
+        };
+        if let Err(e) = rule.get_patterns() {
+            errors.push(invalid("pattern", e));
+        }
+        if let Err(e) = rule.get_rewrite_options() {
+            errors.push(invalid("rewrite", e));
+        }
+    }
+    if errors.is_empty() {
+        Ok(rset)
+    } else {
+        Err(RuleSetErrors(errors))
+    }
 }
@@ -0,0 +1,487 @@
+//! This module defines compiled rules, whose patterns are parsed once and reused for all targets.
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{hash_map, HashMap},
+    convert::TryFrom,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::core::{
+    language::{CloudFormation, Dockerfile, Go, Kubernetes, Queryable, TerraformJSON, ARM, HCL},
+    matcher::{CaptureItem, MatchedItem, StepBudget},
+    pattern::{Pattern, PatternExpression},
+    prefilter::{Prefilter, Requirement},
+    query::MetavariableId,
+    ruleset::{Language, Rule},
+    tree::RefTreeView,
+};
+
+/// `CompiledRule` is a rule with parsed patterns, compiled regexes and validated constraints.
+#[derive(Debug)]
+pub struct CompiledRule<T: Queryable> {
+    pub rule: Rule,
+    expressions: Vec<PatternExpression<T>>,
+    rewrite_options: Vec<Pattern<T>>,
+    /// literals required for the rule to match
+    requirement: Requirement,
+    /// hash of the rule, which changes whenever its definition changes
+    digest: String,
+    /// metavariables whose captures are reported instead of whole matches
+    focus: Vec<MetavariableId>,
+}
+
+impl<T: Queryable> CompiledRule<T> {
+    pub fn new(rule: Rule) -> Result<Self> {
+        let context = |e: anyhow::Error| anyhow!("failed to compile rule {}: {}", rule.id, e);
+        let expressions = rule
+            .get_patterns()
+            .and_then(|patterns| {
+                patterns
+                    .into_iter()
+                    .map(PatternExpression::<T>::try_from)
+                    .collect::<Result<Vec<PatternExpression<T>>>>()
+            })
+            .map_err(context)?;
+        let rewrite_options = rule
+            .get_rewrite_options()
+            .and_then(|options| {
+                options
+                    .iter()
+                    .map(|r| Pattern::<T>::try_from(r.as_str()))
+                    .collect::<Result<Vec<Pattern<T>>>>()
+            })
+            .map_err(context)?;
+        let requirement = Requirement::from_expressions(&expressions);
+        let digest = format!("{:x}", Sha256::digest(&serde_json::to_vec(&rule)?));
+        Ok(CompiledRule {
+            rule,
+            expressions,
+            rewrite_options,
+            requirement,
+            digest,
+            focus: vec![],
+        })
+    }
+
+    /// `with_focus` reports the capture of the first bound metavariable in `focus` instead of each whole match.
+    pub fn with_focus(mut self, focus: Vec<MetavariableId>) -> Self {
+        self.focus = focus;
+        self
+    }
+
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+
+    /// `rewrite_options` returns the parsed rewrite options in the order of the rule definition.
+    pub fn rewrite_options(&self) -> &[Pattern<T>] {
+        &self.rewrite_options
+    }
+
+    pub fn find<'tree, 'item>(
+        &self,
+        tree: &'tree RefTreeView<'tree, T>,
+    ) -> Result<Vec<MatchedItem<'item>>>
+    where
+        'tree: 'item,
+    {
+        // the budget of `tree` is spent for each rule separately
+        tree.budget().reset();
+
+        let mut matches = vec![];
+        for pe in &self.expressions {
+            matches.extend(tree.matches_expression(pe)?);
+            tree.budget().check()?;
+        }
+        for item in &mut matches {
+            let focused = self.focus.iter().find_map(|id| match item.capture_of(id) {
+                Some(CaptureItem::Nodes(nodes)) => Some(nodes.clone()),
+                _ => None,
+            });
+            if let Some(area) = focused {
+                item.area = area;
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// `CompiledRuleGroup` holds compiled rules of a language with a prefilter for them.
+#[derive(Debug)]
+pub struct CompiledRuleGroup<T: Queryable> {
+    language: Language,
+    rules: Vec<CompiledRule<T>>,
+    prefilter: Prefilter,
+    /// maximum number of matching steps for a rule on a target
+    step_limit: Option<usize>,
+
+    /// number of pairs of a rule and a target which were checked
+    checked_pairs: AtomicUsize,
+    /// number of pairs of a rule and a target which were skipped by the prefilter
+    skipped_pairs: AtomicUsize,
+}
+
+impl<T: Queryable> CompiledRuleGroup<T> {
+    fn new(language: Language, rules: Vec<Rule>) -> Result<Self> {
+        let rules = rules
+            .into_iter()
+            .map(CompiledRule::new)
+            .collect::<Result<Vec<CompiledRule<T>>>>()?;
+        Ok(Self::from_compiled(language, rules))
+    }
+
+    fn from_compiled(language: Language, rules: Vec<CompiledRule<T>>) -> Self {
+        let prefilter = Prefilter::new(rules.iter().map(|r| &r.requirement));
+        CompiledRuleGroup {
+            language,
+            rules,
+            prefilter,
+            step_limit: None,
+            checked_pairs: AtomicUsize::new(0),
+            skipped_pairs: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    pub fn rules(&self) -> &[CompiledRule<T>] {
+        &self.rules
+    }
+
+    /// `candidates` returns rules which may match `body`, skipping ones whose required literals do not appear in it.
+    pub fn candidates(&self, body: &str) -> Vec<&CompiledRule<T>> {
+        let found = self.prefilter.find(body);
+        let candidates = self
+            .rules
+            .iter()
+            .filter(|r| r.requirement.is_satisfied(&found))
+            .collect::<Vec<&CompiledRule<T>>>();
+
+        self.checked_pairs
+            .fetch_add(self.rules.len(), Ordering::Relaxed);
+        self.skipped_pairs
+            .fetch_add(self.rules.len() - candidates.len(), Ordering::Relaxed);
+        candidates
+    }
+
+    /// `budget` returns a new budget of matching steps for a target.
+    pub fn budget(&self) -> StepBudget {
+        StepBudget::new(self.step_limit)
+    }
+
+    fn stats(&self) -> (usize, usize) {
+        (
+            self.checked_pairs.load(Ordering::Relaxed),
+            self.skipped_pairs.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// `dispatch_language!` evaluates an expression with the type of a language, which is the only place listing all languages:
+///
+/// - `dispatch_language!(language, |Lang| ...)` binds `Lang` to the type of `language: Language`
+/// - `dispatch_language!(rules, |group: Lang| ...)` binds `group` to the group of `rules: CompiledRules`, and `Lang` to its type
+#[macro_export]
+macro_rules! dispatch_language {
+    ($language:expr, |$lang:ident| $body:expr) => {
+        $crate::dispatch_language!(@languages [@language $language, $lang, $body])
+    };
+    ($rules:expr, |$group:ident: $lang:ident| $body:expr) => {
+        $crate::dispatch_language!(@languages [@rules $rules, $group, $lang, $body])
+    };
+    (@languages [$($args:tt)*]) => {
+        $crate::dispatch_language! { $($args)*; HCL, Dockerfile, Go, Kubernetes, CloudFormation, ARM, TerraformJSON }
+    };
+    (@language $language:expr, $lang:ident, $body:expr; $($variant:ident),*) => {
+        match $language {
+            $($crate::core::ruleset::Language::$variant => {
+                #[allow(dead_code)]
+                type $lang = $crate::core::language::$variant;
+                $body
+            })*
+        }
+    };
+    (@rules $rules:expr, $group:ident, $lang:ident, $body:expr; $($variant:ident),*) => {
+        match $rules {
+            $($crate::core::ruleset::compiled::CompiledRules::$variant($group) => {
+                #[allow(dead_code)]
+                type $lang = $crate::core::language::$variant;
+                $body
+            })*
+        }
+    };
+    (@from; $($variant:ident),*) => {
+        $(impl From<CompiledRuleGroup<$crate::core::language::$variant>> for CompiledRules {
+            fn from(group: CompiledRuleGroup<$crate::core::language::$variant>) -> Self {
+                CompiledRules::$variant(group)
+            }
+        })*
+    };
+}
+
+/// `CompiledRules` holds compiled rules of a language.
+#[derive(Debug)]
+pub enum CompiledRules {
+    HCL(CompiledRuleGroup<HCL>),
+    Dockerfile(CompiledRuleGroup<Dockerfile>),
+    Go(CompiledRuleGroup<Go>),
+    Kubernetes(CompiledRuleGroup<Kubernetes>),
+    CloudFormation(CompiledRuleGroup<CloudFormation>),
+    ARM(CompiledRuleGroup<ARM>),
+    TerraformJSON(CompiledRuleGroup<TerraformJSON>),
+}
+
+dispatch_language!(@languages [@from]);
+
+impl CompiledRules {
+    fn new(language: Language, rules: Vec<Rule>) -> Result<Self> {
+        Ok(dispatch_language!(language, |Lang| {
+            CompiledRuleGroup::<Lang>::new(language, rules)?.into()
+        }))
+    }
+
+    fn set_step_limit(&mut self, limit: Option<usize>) {
+        dispatch_language!(self, |group: Lang| group.step_limit = limit)
+    }
+
+    fn stats(&self) -> (usize, usize) {
+        dispatch_language!(self, |group: Lang| group.stats())
+    }
+}
+
+/// `CompiledRuleSet` holds compiled rules grouped by their languages.
+#[derive(Debug, Default)]
+pub struct CompiledRuleSet {
+    rules: HashMap<Language, CompiledRules>,
+    /// ids of rules for HCL which are not applied to Terraform JSON, with the reasons
+    untranslated: Vec<(String, anyhow::Error)>,
+}
+
+impl CompiledRuleSet {
+    /// `new` compiles `rules`. It fails on the first rule which cannot be compiled.
+    /// Rules for HCL are also compiled for Terraform JSON, skipping ones which cannot be translated (see `untranslated`).
+    pub fn new(rules: impl IntoIterator<Item = Rule>) -> Result<Self> {
+        let mut grouped = HashMap::<Language, Vec<Rule>>::new();
+        for rule in rules {
+            grouped.entry(rule.language).or_default().push(rule);
+        }
+        let mut derived = vec![];
+        let mut untranslated = vec![];
+        for rule in grouped.get(&Language::HCL).into_iter().flatten() {
+            let compiled = rule.to_terraform_json().and_then(|(rule, focus)| {
+                Ok(CompiledRule::<TerraformJSON>::new(rule)?.with_focus(focus))
+            });
+            match compiled {
+                Ok(compiled) => derived.push(compiled),
+                Err(e) => untranslated.push((rule.id.clone(), e)),
+            }
+        }
+
+        let mut compiled = CompiledRuleSet {
+            untranslated,
+            ..Default::default()
+        };
+        for (language, rules) in grouped {
+            compiled
+                .rules
+                .insert(language, CompiledRules::new(language, rules)?);
+        }
+        if !derived.is_empty() {
+            let mut rules = match compiled.rules.remove(&Language::TerraformJSON) {
+                Some(CompiledRules::TerraformJSON(group)) => group.rules,
+                _ => vec![],
+            };
+            rules.extend(derived);
+            compiled.rules.insert(
+                Language::TerraformJSON,
+                CompiledRules::TerraformJSON(CompiledRuleGroup::from_compiled(
+                    Language::TerraformJSON,
+                    rules,
+                )),
+            );
+        }
+        Ok(compiled)
+    }
+
+    /// `with_step_limit` aborts matching a rule on a target after `limit` steps.
+    pub fn with_step_limit(mut self, limit: Option<usize>) -> Self {
+        for rules in self.rules.values_mut() {
+            rules.set_step_limit(limit);
+        }
+        self
+    }
+
+    /// `untranslated` returns ids of rules for HCL which are not applied to Terraform JSON, with the reasons.
+    pub fn untranslated(&self) -> &[(String, anyhow::Error)] {
+        &self.untranslated
+    }
+
+    pub fn get(&self, language: &Language) -> Option<&CompiledRules> {
+        self.rules.get(language)
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, Language, CompiledRules> {
+        self.rules.iter()
+    }
+
+    /// `prefilter_stats` returns the number of checked pairs of a rule and a target and the number of pairs skipped by the prefilter.
+    pub fn prefilter_stats(&self) -> (usize, usize) {
+        self.rules
+            .values()
+            .map(CompiledRules::stats)
+            .fold((0, 0), |(c, s), (dc, ds)| (c + dc, s + ds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        matcher::StepLimitExceeded,
+        ruleset::RawPatternWithConstraints,
+        tree::{NormalizedTree, Tree},
+    };
+
+    #[test]
+    fn test_compile_error() {
+        let rule = |id: &str, pattern: &str, regex: &str| {
+            serde_yaml::from_str::<Rule>(&format!(
+                r#"
+id: {}
+language: hcl
+message: test
+pattern: {}
+constraints:
+  - target: X
+    should: match
+    regex-pattern: "{}"
+"#,
+                id, pattern, regex
+            ))
+            .unwrap()
+        };
+
+        let e = CompiledRuleSet::new(vec![
+            rule("valid", "a = :[X]", "^a$"),
+            rule("broken", "a = :[X]", "("),
+        ])
+        .unwrap_err();
+        assert!(e.to_string().contains("rule broken"));
+
+        let rule = Rule::new(
+            "inline".into(),
+            Language::Go,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "a".into(),
+                ..Default::default()
+            }],
+            vec![],
+            vec![],
+        );
+        let compiled = CompiledRuleSet::new(vec![rule]).unwrap();
+        assert!(
+            matches!(compiled.get(&Language::Go), Some(CompiledRules::Go(group)) if group.rules.len() == 1)
+        );
+        assert!(compiled.get(&Language::HCL).is_none());
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let rule = Rule::new(
+            "ellipses".into(),
+            Language::HCL,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "locals {\n  ...\n  a = 1\n  ...\n  b = 2\n  ...\n}".into(),
+                ..Default::default()
+            }],
+            vec![],
+            vec![],
+        );
+        let target = format!(
+            "locals {{\n{}  a = 1\n{}  b = 2\n}}",
+            "  x = 0\n".repeat(50),
+            "  y = 0\n".repeat(50)
+        );
+        let tree = Tree::<HCL>::try_from(target.as_str()).unwrap();
+        let ptree = NormalizedTree::from(&tree);
+
+        let compiled = CompiledRule::<HCL>::new(rule).unwrap();
+        let view = ptree.as_ref_treeview().with_budget(StepBudget::new(None));
+        assert_eq!(compiled.find(&view).unwrap().len(), 1);
+
+        let view = ptree
+            .as_ref_treeview()
+            .with_budget(StepBudget::new(Some(10)));
+        let e = compiled.find(&view).unwrap_err();
+        assert!(e.downcast_ref::<StepLimitExceeded>().is_some());
+    }
+
+    #[test]
+    fn test_terraform_json_rules() {
+        let rule = Rule::new(
+            "public-bucket".into(),
+            Language::HCL,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "resource \"aws_s3_bucket\" :[NAME] {\n  acl = \"public-read\"\n  ...\n}"
+                    .into(),
+                ..Default::default()
+            }],
+            vec![],
+            vec![],
+        );
+        let compiled = CompiledRuleSet::new(vec![rule]).unwrap();
+        let group = match compiled.get(&Language::TerraformJSON) {
+            Some(CompiledRules::TerraformJSON(group)) => group,
+            _ => panic!("rules for HCL were not compiled for Terraform JSON"),
+        };
+        assert_eq!(group.rules.len(), 1);
+
+        let target = r#"{
+  "resource": {
+    "aws_s3_bucket": {
+      "logs": { "bucket": "logs", "acl": "public-read" },
+      "assets": { "bucket": "assets", "acl": "private" }
+    }
+  }
+}"#;
+        let tree = Tree::<TerraformJSON>::try_from(target).unwrap();
+        let ptree = NormalizedTree::from(&tree);
+        let view = ptree.as_ref_treeview().with_budget(StepBudget::new(None));
+        let matches = group.rules[0].find(&view).unwrap();
+        assert_eq!(matches.len(), 1);
+        // the finding points to the name of the block
+        assert_eq!(matches[0].area.as_str().unwrap(), "\"logs\"");
+        assert!(compiled.untranslated().is_empty());
+
+        // rules which cannot be translated are applied only to HCL files
+        let rule = Rule::new(
+            "heredoc".into(),
+            Language::HCL,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "policy = <<EOT\nb\nEOT\n".into(),
+                ..Default::default()
+            }],
+            vec![],
+            vec![],
+        );
+        let compiled = CompiledRuleSet::new(vec![rule]).unwrap();
+        assert!(compiled.get(&Language::HCL).is_some());
+        assert!(compiled.get(&Language::TerraformJSON).is_none());
+        assert_eq!(
+            compiled
+                .untranslated()
+                .iter()
+                .map(|(id, _)| id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["heredoc"]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! This module defines errors on loading rule set files, which point to the rule and the field causing them.
+
+use regex::Regex;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// `Origin` locates the cause of an error in a rule set file as precisely as possible.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Origin {
+    /// path of the file, which is unknown when a rule set is read from a reader
+    pub path: Option<PathBuf>,
+    /// 1-origin line in the file
+    pub line: Option<usize>,
+    /// 1-origin column in the file
+    pub column: Option<usize>,
+    pub rule_id: Option<String>,
+    /// path to the field from the rule (e.g. `constraints[0].should`)
+    pub field: Option<String>,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path {
+            Some(ref p) => write!(f, "{}", p.to_string_lossy())?,
+            None => write!(f, "<input>")?,
+        }
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        if let Some(ref id) = self.rule_id {
+            write!(f, ": rule {}", id)?;
+        }
+        if let Some(ref field) = self.field {
+            write!(f, ": field `{}`", field)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RuleSetError {
+    #[error("{origin}: failed to read the file: {source}")]
+    Io {
+        origin: Origin,
+        #[source]
+        source: std::io::Error,
+    },
+    /// the file is not a valid YAML or does not follow the schema of rule sets
+    #[error("{origin}: {message}")]
+    Syntax { origin: Origin, message: String },
+    /// a rule follows the schema, but its fields conflict with each other
+    #[error("{origin}: {message}")]
+    InvalidRule { origin: Origin, message: String },
+}
+
+impl RuleSetError {
+    pub fn origin(&self) -> &Origin {
+        match self {
+            RuleSetError::Io { origin, .. }
+            | RuleSetError::Syntax { origin, .. }
+            | RuleSetError::InvalidRule { origin, .. } => origin,
+        }
+    }
+
+    pub(crate) fn io(path: &Path, source: std::io::Error) -> Self {
+        RuleSetError::Io {
+            origin: Origin {
+                path: Some(path.to_path_buf()),
+                ..Default::default()
+            },
+            source,
+        }
+    }
+
+    /// `from_yaml_error` locates `e`, which occurred on deserializing `body`, with the rule and the field in the path of `e`.
+    pub(crate) fn from_yaml_error(e: serde_yaml::Error, body: &str, path: Option<&Path>) -> Self {
+        let location = e.location();
+
+        // serde_yaml formats an error as `<path>: <message> at line <l> column <c>`, where the path is omitted at the top level
+        // (see `test_yaml_error_format`; the version of serde_yaml is pinned for this format)
+        let message = e.to_string();
+        let message = Regex::new(r" at line \d+ column \d+$")
+            .unwrap()
+            .replace(&message, "")
+            .to_string();
+        let (yaml_path, message) = match message.split_once(": ") {
+            Some((p, m))
+                if Regex::new(r"^[\w\-]+(\[\d+\])?(\.[\w\-]+(\[\d+\])?)*$")
+                    .unwrap()
+                    .is_match(p) =>
+            {
+                (Some(p.to_string()), m.to_string())
+            }
+            _ => (None, message),
+        };
+
+        let (index, field) = match yaml_path.as_deref().and_then(|p| {
+            Regex::new(r"^rules\[(\d+)\](\.(.+))?$")
+                .unwrap()
+                .captures(p)
+        }) {
+            Some(caps) => (
+                caps[1].parse::<usize>().ok(),
+                caps.get(3).map(|m| m.as_str().to_string()),
+            ),
+            None => (None, yaml_path),
+        };
+        // fields which are missing or unknown are not in the path
+        let field = match Regex::new(r"(missing|unknown) field `([^`]+)`")
+            .unwrap()
+            .captures(&message)
+        {
+            Some(caps) => Some(match field {
+                Some(f) => format!("{}.{}", f, &caps[2]),
+                None => caps[2].to_string(),
+            }),
+            None => field,
+        };
+        let rule_id = index.and_then(|i| {
+            let value = serde_yaml::from_str::<serde_yaml::Value>(body).ok()?;
+            value
+                .get("rules")?
+                .get(i)?
+                .get("id")?
+                .as_str()
+                .map(String::from)
+        });
+
+        let (line, column) = match location {
+            Some(l) => (Some(l.line()), Some(l.column())),
+            None => (
+                rule_id
+                    .as_deref()
+                    .map(|id| rule_lines(body, std::iter::once(id))[0]),
+                None,
+            ),
+        };
+        RuleSetError::Syntax {
+            origin: Origin {
+                path: path.map(Path::to_path_buf),
+                line,
+                column,
+                rule_id,
+                field,
+            },
+            message,
+        }
+    }
+}
+
+/// `RuleSetErrors` collects errors on loading rule set files.
+#[derive(Debug, Error, Default)]
+pub struct RuleSetErrors(pub Vec<RuleSetError>);
+
+impl fmt::Display for RuleSetErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages = self
+            .0
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", messages.join("\n"))
+    }
+}
+
+impl From<RuleSetError> for RuleSetErrors {
+    fn from(e: RuleSetError) -> Self {
+        RuleSetErrors(vec![e])
+    }
+}
+
+/// `rule_lines` returns the 1-origin line of the `id` field of each rule in `body`.
+/// Rules are looked up in order, so a rule ID used twice in a file is located twice.
+pub fn rule_lines<'a>(body: &str, ids: impl Iterator<Item = &'a str>) -> Vec<usize> {
+    let lines = body.lines().collect::<Vec<&str>>();
+    let mut from = 0;
+    ids.map(|id| {
+        let r = Regex::new(&format!(
+            r#"^\s*(-\s+)?id:\s*("{0}"|'{0}'|{0})\s*(#.*)?$"#,
+            regex::escape(id)
+        ))
+        .unwrap();
+        match lines[from..].iter().position(|l| r.is_match(l)) {
+            Some(i) => {
+                from += i + 1;
+                from
+            }
+            // e.g. the ID is written in a flow style; the line of the previous rule is the best guess
+            None => from.max(1),
+        }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ruleset::RuleSet;
+
+    #[test]
+    fn test_rule_lines() {
+        let body = r#"version: "1"
+rules:
+  - id: first
+    language: hcl
+    message: test
+    pattern: a = 1
+  - language: hcl
+    id: "second"
+    message: test
+    pattern: b = 1
+  - id: 'first' # duplicated
+    language: hcl
+    message: test
+    pattern: c = 1
+"#;
+        assert_eq!(
+            rule_lines(body, vec!["first", "second", "first"].into_iter()),
+            vec![3, 8, 11]
+        );
+    }
+
+    #[test]
+    fn test_located_error() {
+        let body = r#"version: "1"
+rules:
+  - id: valid
+    language: hcl
+    message: test
+    pattern: a = 1
+  - id: invalid
+    language: hcl
+    message: test
+    pattern: a = :[X]
+    constraints:
+      - target: X
+        should: be-something
+        string: a
+"#;
+        let e = serde_yaml::from_str::<RuleSet>(body).unwrap_err();
+        let e = RuleSetError::from_yaml_error(e, body, Some(Path::new("rules.yaml")));
+        let origin = e.origin();
+        assert_eq!(origin.rule_id.as_deref(), Some("invalid"));
+        assert_eq!(origin.field.as_deref(), Some("constraints[0].should"));
+        assert_eq!(origin.line, Some(13));
+        assert!(e
+            .to_string()
+            .starts_with("rules.yaml:13:17: rule invalid: field `constraints[0].should`: "));
+
+        let body = r#"version: "1"
+rules:
+  - id: no-message
+    language: hcl
+    pattern: a = 1
+"#;
+        let e = serde_yaml::from_str::<RuleSet>(body).unwrap_err();
+        let e = RuleSetError::from_yaml_error(e, body, None);
+        assert_eq!(e.origin().rule_id.as_deref(), Some("no-message"));
+        assert_eq!(e.origin().field.as_deref(), Some("message"));
+
+        let body = "version: \"1\"\nrules: [\n";
+        let e = serde_yaml::from_str::<RuleSet>(body).unwrap_err();
+        let e = RuleSetError::from_yaml_error(e, body, None);
+        assert_eq!(e.origin().rule_id, None);
+        assert!(e.origin().line.is_some());
+    }
+
+    /// `from_yaml_error` parses messages of serde_yaml, so their format is pinned here together with its version in Cargo.toml.
+    #[test]
+    fn test_yaml_error_format() {
+        let message = |body: &str| {
+            serde_yaml::from_str::<RuleSet>(body)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            message("version: \"1\"\nrules:\n  - id: a\n    language: hcl\n    message: test\n    pattern: a = 1\n    constraints:\n      - target: X\n        should: be-something\n"),
+            "rules[0].constraints[0].should: unknown variant `be-something`, expected one of `match`, `not-match`, `match-any-of`, `not-match-any-of`, `match-regex`, `not-match-regex`, `be-any-of`, `not-be-any-of` at line 9 column 17"
+        );
+        assert_eq!(
+            message("version: \"1\"\nrules:\n  - id: a\n    language: hcl\n    pattern: a = 1\n"),
+            "rules[0]: missing field `message` at line 3 column 7"
+        );
+        assert_eq!(
+            message("rules: []\n"),
+            "missing field `version` at line 1 column 6"
+        );
+    }
+}
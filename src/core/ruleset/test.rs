@@ -1,6 +1,6 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, str::FromStr};
 
-use super::from_path;
+use super::{from_path, LoadMode, RawPatternExpression, RuleSet, RuleSetError};
 
 #[test]
 fn load() {
@@ -12,3 +12,169 @@ fn load() {
     assert!(ruleset.is_ok());
     assert_eq!(ruleset.unwrap().len(), 2);
 }
+
+#[test]
+fn parse_pattern_expressions() {
+    let ruleset = RuleSet::from_str(
+        r#"
+version: "1"
+rules:
+  - id: "test"
+    language: hcl
+    message: test
+    patterns:
+      - pattern: a = 1
+      - all-of:
+          - any-of:
+              - pattern: b = 1
+              - pattern: c = 1
+          - inside:
+              pattern: d = 1
+          - none-of:
+              - pattern: e = 1
+        constraints:
+          - target: X
+            should: match
+            regex-pattern: x
+    pattern-not-inside: f = 1
+"#,
+    )
+    .unwrap();
+
+    let patterns = ruleset.rules[0].get_patterns().unwrap();
+    assert_eq!(patterns.len(), 2);
+    match &patterns[0] {
+        RawPatternExpression::Pattern(p) => {
+            assert_eq!(p.pattern, "a = 1");
+            assert_eq!(p.filters.pattern_not_inside, vec!["f = 1".to_string()]);
+
+            // filters of a pattern are evaluated as negative expressions in all-of
+            match p.clone().into_expression() {
+                RawPatternExpression::AllOf { all_of, .. } => {
+                    assert_eq!(all_of.len(), 2);
+                    assert!(matches!(all_of[0], RawPatternExpression::Pattern(_)));
+                    assert!(matches!(all_of[1], RawPatternExpression::NotInside { .. }));
+                }
+                e => panic!("unexpected expression: {:?}", e),
+            }
+        }
+        _ => panic!("unexpected expression: {:?}", patterns[0]),
+    }
+    match &patterns[1] {
+        RawPatternExpression::AllOf { all_of, .. } => {
+            assert_eq!(all_of.len(), 2);
+            assert!(matches!(all_of[0], RawPatternExpression::AllOf { .. }));
+            assert!(matches!(all_of[1], RawPatternExpression::NotInside { .. }));
+        }
+        _ => panic!("unexpected expression: {:?}", patterns[1]),
+    }
+}
+
+#[test]
+fn parse_invalid_pattern_expressions() {
+    let ruleset = RuleSet::from_str(
+        r#"
+version: "1"
+rules:
+  - id: "test"
+    language: hcl
+    message: test
+    patterns:
+      - pattern: a = 1
+        any-of:
+          - pattern: b = 1
+"#,
+    );
+    assert!(ruleset.is_err());
+}
+
+#[test]
+fn parse_constraint_filters() {
+    let ruleset = RuleSet::from_str(
+        r#"
+version: "1"
+rules:
+  - id: "test"
+    language: hcl
+    message: test
+    pattern: a = :[X]
+    constraints:
+      - target: X
+        should: match
+        pattern: b
+        pattern-not-inside: c
+      - target: X
+        should: match-any-of
+        patterns:
+          - pattern: d
+            pattern-not: e
+        pattern-inside: f
+      - target: X
+        should: match
+        pattern-not: g
+"#,
+    )
+    .unwrap();
+
+    let constraints = &ruleset.rules[0].constraints;
+    let rpcs = constraints[0].get_pattern_with_constraints().unwrap();
+    assert_eq!(rpcs.len(), 1);
+    assert_eq!(rpcs[0].filters.pattern_not_inside, vec!["c".to_string()]);
+
+    let rpcs = constraints[1].get_pattern_with_constraints().unwrap();
+    assert_eq!(rpcs.len(), 1);
+    assert_eq!(rpcs[0].filters.pattern_not, vec!["e".to_string()]);
+    assert_eq!(rpcs[0].filters.pattern_inside, vec!["f".to_string()]);
+
+    // filters without patterns are rejected instead of being ignored
+    assert!(constraints[2].get_pattern_with_constraints().is_err());
+}
+
+#[test]
+fn load_collecting_errors() {
+    let root = std::env::temp_dir().join(format!("shisho-test-ruleset-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(
+        root.join("a.yaml"),
+        r#"version: "1"
+rules:
+  - id: conflicting
+    language: hcl
+    message: test
+    pattern: a = 1
+    patterns:
+      - pattern: b = 1
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("b.yaml"),
+        r#"version: "1"
+rules:
+  - id: unknown-language
+    language: cobol
+    message: test
+    pattern: a = 1
+"#,
+    )
+    .unwrap();
+
+    let errors = super::load(&root, LoadMode::FailFast).unwrap_err();
+    assert_eq!(errors.0.len(), 1);
+
+    let errors = super::load(&root, LoadMode::CollectErrors).unwrap_err();
+    assert_eq!(errors.0.len(), 2);
+    assert!(matches!(errors.0[0], RuleSetError::InvalidRule { .. }));
+    assert_eq!(errors.0[0].origin().rule_id.as_deref(), Some("conflicting"));
+    assert_eq!(errors.0[0].origin().line, Some(3));
+    assert_eq!(errors.0[0].origin().field.as_deref(), Some("pattern"));
+    assert!(matches!(errors.0[1], RuleSetError::Syntax { .. }));
+    assert_eq!(
+        errors.0[1].origin().rule_id.as_deref(),
+        Some("unknown-language")
+    );
+    assert_eq!(errors.0[1].origin().field.as_deref(), Some("language"));
+    assert_eq!(errors.0[1].origin().path, Some(root.join("b.yaml")));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,559 @@
+//! This module translates rules for HCL into rules for Terraform JSON, the JSON syntax of Terraform configurations.
+//! See <https://developer.hashicorp.com/terraform/language/syntax/json> for how the two syntaxes correspond.
+//!
+//! A block `resource "aws_s3_bucket" "logs" { acl = "private" }` is an object `{ "resource": { "aws_s3_bucket": { "logs": { "acl": "private" } } } }`,
+//! and an expression other than literals is a string template such as `"${var.name}"`.
+//! Translated patterns are written in YAML since JSON is parsed as YAML.
+
+use anyhow::{anyhow, Result};
+use std::convert::TryFrom;
+
+use crate::core::{
+    language::HCL,
+    node::{Node, NodeType},
+    pattern::Pattern,
+    query::MetavariableId,
+};
+
+use super::{
+    Language, RawConstraint, RawPatternExpression, RawPatternFilters, RawPatternWithConstraints,
+    Rule,
+};
+
+impl Rule {
+    /// `to_terraform_json` translates the rule for HCL into a rule for Terraform JSON.
+    /// It also returns metavariables which capture names of matched blocks; they point to findings better than the whole matches,
+    /// which start from the outermost key such as `"resource"`.
+    /// Rewrites are dropped since they are written in HCL.
+    pub(crate) fn to_terraform_json(&self) -> Result<(Rule, Vec<MetavariableId>)> {
+        let mut focus = vec![];
+        let patterns = self
+            .get_patterns()?
+            .iter()
+            .map(|rpe| translate_expression(rpe, Some(&mut focus)))
+            .collect::<Result<Vec<RawPatternExpression>>>()
+            .map_err(|e| anyhow!("failed to translate rule {}: {}", self.id, e))?;
+
+        let rule = Rule {
+            id: self.id.clone(),
+            language: Language::TerraformJSON,
+            title: self.title.clone(),
+            message: self.message.clone(),
+            tags: self.tags.clone(),
+
+            patterns,
+            pattern: None,
+            constraints: vec![],
+            filters: RawPatternFilters::default(),
+            rewrite_options: vec![],
+            rewrite: None,
+        };
+        Ok((rule, focus))
+    }
+}
+
+/// `translate_expression` translates patterns in `rpe`, collecting the names of blocks which positive patterns match into `focus`.
+fn translate_expression(
+    rpe: &RawPatternExpression,
+    mut focus: Option<&mut Vec<MetavariableId>>,
+) -> Result<RawPatternExpression> {
+    let translate_all = |rpes: &[RawPatternExpression],
+                         mut focus: Option<&mut Vec<MetavariableId>>| {
+        rpes.iter()
+            .map(|rpe| translate_expression(rpe, focus.as_deref_mut()))
+            .collect::<Result<Vec<RawPatternExpression>>>()
+    };
+
+    Ok(match rpe {
+        RawPatternExpression::AllOf {
+            all_of,
+            constraints,
+        } => RawPatternExpression::AllOf {
+            all_of: translate_all(all_of, focus)?,
+            constraints: translate_constraints(constraints)?,
+        },
+        RawPatternExpression::AnyOf { any_of } => RawPatternExpression::AnyOf {
+            any_of: translate_all(any_of, focus)?,
+        },
+        RawPatternExpression::NoneOf { none_of } => RawPatternExpression::NoneOf {
+            none_of: translate_all(none_of, None)?,
+        },
+        RawPatternExpression::Inside { inside } => RawPatternExpression::Inside {
+            inside: Box::new(translate_expression(inside, None)?),
+        },
+        RawPatternExpression::NotInside { not_inside } => RawPatternExpression::NotInside {
+            not_inside: Box::new(translate_expression(not_inside, None)?),
+        },
+        RawPatternExpression::Pattern(rpc) => {
+            let (pattern, name) = translate_pattern(&rpc.pattern)?;
+            if let (Some(focus), Some(name)) = (focus.as_mut(), name) {
+                focus.push(name);
+            }
+            RawPatternExpression::Pattern(RawPatternWithConstraints {
+                pattern,
+                constraints: translate_constraints(&rpc.constraints)?,
+                filters: translate_filters(&rpc.filters)?,
+            })
+        }
+    })
+}
+
+fn translate_constraints(constraints: &[RawConstraint]) -> Result<Vec<RawConstraint>> {
+    constraints
+        .iter()
+        .map(|c| {
+            Ok(RawConstraint {
+                pattern: c
+                    .pattern
+                    .as_deref()
+                    .map(|p| translate_pattern(p).map(|(p, _)| p))
+                    .transpose()?,
+                patterns: c
+                    .patterns
+                    .iter()
+                    .map(|rpc| {
+                        Ok(RawPatternWithConstraints {
+                            pattern: translate_pattern(&rpc.pattern)?.0,
+                            constraints: translate_constraints(&rpc.constraints)?,
+                            filters: translate_filters(&rpc.filters)?,
+                        })
+                    })
+                    .collect::<Result<Vec<RawPatternWithConstraints>>>()?,
+                constraints: translate_constraints(&c.constraints)?,
+                filters: translate_filters(&c.filters)?,
+                ..c.clone()
+            })
+        })
+        .collect()
+}
+
+fn translate_filters(filters: &RawPatternFilters) -> Result<RawPatternFilters> {
+    let translate_all = |patterns: &[String]| {
+        patterns
+            .iter()
+            .map(|p| translate_pattern(p).map(|(p, _)| p))
+            .collect::<Result<Vec<String>>>()
+    };
+    Ok(RawPatternFilters {
+        pattern_not: translate_all(&filters.pattern_not)?,
+        pattern_inside: translate_all(&filters.pattern_inside)?,
+        pattern_not_inside: translate_all(&filters.pattern_not_inside)?,
+    })
+}
+
+/// `Value` is a value in a translated pattern.
+#[derive(Debug)]
+enum Value {
+    Scalar(String),
+    Mapping(Mapping),
+}
+
+/// `Mapping` is a JSON object, which is written in the block style of YAML.
+#[derive(Debug, Default)]
+struct Mapping {
+    entries: Vec<Entry>,
+    /// whether the mapping may have other keys, which holds for objects keyed by block types and labels
+    open: bool,
+}
+
+#[derive(Debug)]
+enum Entry {
+    Pair(String, Value),
+    /// an ellipsis or an ellipsis metavariable
+    Rest(String),
+}
+
+impl Mapping {
+    /// `insert_block` puts `body` at `path` of keys, merging blocks which share their types and labels.
+    fn insert_block(&mut self, path: &[String], body: Mapping) {
+        let (key, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        if rest.is_empty() {
+            self.entries
+                .push(Entry::Pair(key.clone(), Value::Mapping(body)));
+            return;
+        }
+
+        let existing = self.entries.iter_mut().find_map(|e| match e {
+            Entry::Pair(k, Value::Mapping(m)) if k == key && m.open => Some(m),
+            _ => None,
+        });
+        match existing {
+            Some(m) => m.insert_block(rest, body),
+            None => {
+                let mut m = Mapping {
+                    entries: vec![],
+                    open: true,
+                };
+                m.insert_block(rest, body);
+                self.entries
+                    .push(Entry::Pair(key.clone(), Value::Mapping(m)));
+            }
+        }
+    }
+
+    fn write(&self, indent: usize, out: &mut String) {
+        let pad = " ".repeat(indent);
+        for entry in &self.entries {
+            match entry {
+                Entry::Pair(key, Value::Scalar(value)) => {
+                    out.push_str(&format!("{}{}: {}\n", pad, key, value))
+                }
+                Entry::Pair(key, Value::Mapping(m)) if m.entries.is_empty() && !m.open => {
+                    out.push_str(&format!("{}{}: {{}}\n", pad, key))
+                }
+                Entry::Pair(key, Value::Mapping(m)) => {
+                    out.push_str(&format!("{}{}:\n", pad, key));
+                    m.write(indent + 2, out);
+                }
+                Entry::Rest(rest) => out.push_str(&format!("{}{}\n", pad, rest)),
+            }
+        }
+        if self.open {
+            out.push_str(&format!("{}...\n", pad));
+        }
+    }
+}
+
+/// `translate_pattern` translates an HCL pattern into a YAML pattern.
+/// It also returns the metavariable of the name of the block when the pattern is a single block whose last label is a metavariable.
+fn translate_pattern(pattern: &str) -> Result<(String, Option<MetavariableId>)> {
+    let pattern = Pattern::<HCL>::try_from(pattern)?;
+    let root = pattern.to_root_node();
+    let top = named_children(root.as_node())
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("an empty pattern cannot be translated"))?;
+    if top.kind() != NodeType::Normal("body") {
+        return Ok((translate_value(top)?, None));
+    }
+
+    let mut mapping = Mapping::default();
+    translate_body(top, &mut mapping)?;
+    let mut translated = String::new();
+    mapping.write(0, &mut translated);
+
+    let name = match named_children(top).as_slice() {
+        [block] if block.kind() == NodeType::Normal("block") => {
+            match block_labels(block).as_slice() {
+                [_, .., label] => match label.kind() {
+                    NodeType::Metavariable(id) => Some(id),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    Ok((translated, name))
+}
+
+fn translate_body(body: &Node, mapping: &mut Mapping) -> Result<()> {
+    for child in named_children(body) {
+        match child.kind() {
+            NodeType::Normal("attribute") => match named_children(child).as_slice() {
+                [key, value] => mapping.entries.push(Entry::Pair(
+                    key.as_str().to_string(),
+                    Value::Scalar(translate_value(value)?),
+                )),
+                _ => return Err(unsupported(child)),
+            },
+            NodeType::Normal("block") => {
+                let path = block_labels(child)
+                    .into_iter()
+                    .map(|label| label.as_str().to_string())
+                    .collect::<Vec<String>>();
+                let mut inner = Mapping::default();
+                if let Some(body) = named_children(child)
+                    .into_iter()
+                    .find(|n| n.kind() == NodeType::Normal("body"))
+                {
+                    translate_body(body, &mut inner)?;
+                }
+                mapping.insert_block(&path, inner);
+            }
+            NodeType::Ellipsis | NodeType::EllipsisMetavariable(_) => mapping
+                .entries
+                .push(Entry::Rest(child.as_str().to_string())),
+            _ => return Err(unsupported(child)),
+        }
+    }
+    Ok(())
+}
+
+/// `block_labels` returns the type and the labels of `block`.
+fn block_labels<'a, 'tree>(block: &'a Node<'tree>) -> Vec<&'a Node<'tree>> {
+    named_children(block)
+        .into_iter()
+        .take_while(|n| n.kind() != NodeType::Normal("block_start"))
+        .collect()
+}
+
+fn translate_value(node: &Node) -> Result<String> {
+    let children = named_children(node);
+    match node.kind() {
+        NodeType::Metavariable(_) | NodeType::EllipsisMetavariable(_) | NodeType::Ellipsis => {
+            Ok(node.as_str().to_string())
+        }
+        NodeType::Normal("expression")
+        | NodeType::Normal("expr_term")
+        | NodeType::Normal("literal_value")
+        | NodeType::Normal("collection_value")
+        | NodeType::Normal("template_expr")
+            if children.len() == 1 =>
+        {
+            translate_value(children[0])
+        }
+        NodeType::Normal("numeric_lit")
+        | NodeType::Normal("bool_lit")
+        | NodeType::Normal("null_lit")
+        | NodeType::Normal("string_lit")
+        | NodeType::Normal("quoted_template") => Ok(node.as_str().to_string()),
+        NodeType::Normal("tuple") => Ok(format!(
+            "[{}]",
+            elements(node, "tuple")
+                .into_iter()
+                .map(translate_value)
+                .collect::<Result<Vec<String>>>()?
+                .join(", ")
+        )),
+        NodeType::Normal("object") => Ok(format!(
+            "{{{}}}",
+            elements(node, "object")
+                .into_iter()
+                .map(|elem| match elem.kind() {
+                    NodeType::Normal("object_elem") => match named_children(elem).as_slice() {
+                        [key, value] => Ok(format!(
+                            "{}: {}",
+                            translate_key(key)?,
+                            translate_value(value)?
+                        )),
+                        _ => Err(unsupported(elem)),
+                    },
+                    _ => translate_value(elem),
+                })
+                .collect::<Result<Vec<String>>>()?
+                .join(", ")
+        )),
+        NodeType::Normal("heredoc_template") => Err(unsupported(node)),
+        NodeType::DeepExpression => Err(anyhow!(
+            "deep expressions (`<... pattern ...>`) cannot be translated for Terraform JSON"
+        )),
+        // other expressions are written in string templates
+        _ => Ok(format!(
+            "\"${{{}}}\"",
+            node.as_str().replace('\\', "\\\\").replace('"', "\\\"")
+        )),
+    }
+}
+
+fn translate_key(node: &Node) -> Result<String> {
+    let children = named_children(node);
+    match node.kind() {
+        NodeType::Metavariable(_)
+        | NodeType::Normal("identifier")
+        | NodeType::Normal("string_lit") => Ok(node.as_str().to_string()),
+        _ if children.len() == 1 => translate_key(children[0]),
+        _ => Err(unsupported(node)),
+    }
+}
+
+/// `elements` returns elements of a tuple or an object, which may be wrapped with `tuple_elems` or `object_elems`.
+fn elements<'a, 'tree>(node: &'a Node<'tree>, collection: &str) -> Vec<&'a Node<'tree>> {
+    named_children(node)
+        .into_iter()
+        .flat_map(|n| match n.kind() {
+            NodeType::Normal(kind) if kind == format!("{}_elems", collection) => named_children(n),
+            NodeType::Normal(kind)
+                if kind == format!("{}_start", collection)
+                    || kind == format!("{}_end", collection) =>
+            {
+                vec![]
+            }
+            _ => vec![n],
+        })
+        .collect()
+}
+
+fn named_children<'a, 'tree>(node: &'a Node<'tree>) -> Vec<&'a Node<'tree>> {
+    node.children
+        .iter()
+        .filter(|n| n.is_named() && n.kind() != NodeType::Normal("comment"))
+        .collect()
+}
+
+fn unsupported(node: &Node) -> anyhow::Error {
+    anyhow!(
+        "`{}` cannot be translated for Terraform JSON",
+        node.as_str()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_pattern() {
+        let (pattern, name) = translate_pattern(
+            r#"resource "aws_s3_bucket" :[NAME] {
+  acl    = "public-read"
+  bucket = var.name
+  tags   = { env = :[ENV] }
+  :[...]
+}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pattern,
+            r#"resource:
+  "aws_s3_bucket":
+    :[NAME]:
+      acl: "public-read"
+      bucket: "${var.name}"
+      tags: {env: :[ENV]}
+      :[...]
+    ...
+  ...
+"#
+        );
+        assert_eq!(name, Some(MetavariableId("NAME".into())));
+
+        let (pattern, name) = translate_pattern(
+            r#"variable "region" {}
+variable "zone" { default = ["a", 1] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pattern,
+            r#"variable:
+  "region": {}
+  "zone":
+    default: ["a", 1]
+  ...
+"#
+        );
+        assert_eq!(name, None);
+
+        let (pattern, _) = translate_pattern(r#""AES256""#).unwrap();
+        assert_eq!(pattern, r#""AES256""#);
+    }
+
+    #[test]
+    fn test_translate_nested_blocks() {
+        let (pattern, name) = translate_pattern(
+            r#"resource "aws_instance" :[NAME] {
+  root_block_device {
+    encrypted = false
+  }
+  ...
+}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pattern,
+            r#"resource:
+  "aws_instance":
+    :[NAME]:
+      root_block_device:
+        encrypted: false
+      ...
+    ...
+  ...
+"#
+        );
+        assert_eq!(name, Some(MetavariableId("NAME".into())));
+    }
+
+    #[test]
+    fn test_translate_multiple_labels() {
+        // blocks sharing their types are merged into one object
+        let (pattern, name) = translate_pattern(
+            r#"resource "aws_s3_bucket" :[NAME] {
+  ...
+}
+resource "aws_s3_bucket_acl" :[ACL] {
+  acl = "public-read"
+}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pattern,
+            r#"resource:
+  "aws_s3_bucket":
+    :[NAME]:
+      ...
+    ...
+  "aws_s3_bucket_acl":
+    :[ACL]:
+      acl: "public-read"
+    ...
+  ...
+"#
+        );
+        // a pattern with multiple blocks has no single name to point to
+        assert_eq!(name, None);
+    }
+
+    #[test]
+    fn test_translate_attributes_with_metavariables() {
+        let (pattern, _) = translate_pattern(
+            r#"ami = :[AMI]
+user_data = file(:[PATH])
+tags = { Name = :[TAG], env = "prod" }
+ports = [:[PORT], 443]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pattern,
+            r#"ami: :[AMI]
+user_data: "${file(:[PATH])}"
+tags: {Name: :[TAG], env: "prod"}
+ports: [:[PORT], 443]
+"#
+        );
+    }
+
+    #[test]
+    fn test_translate_ellipsis_in_body() {
+        // an ellipsis metavariable captures the rest of keys of the object
+        let (pattern, _) = translate_pattern(
+            r#"provider "aws" {
+  region = :[REGION]
+  :[...REST]
+}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pattern,
+            r#"provider:
+  "aws":
+    region: :[REGION]
+    :[...REST]
+  ...
+"#
+        );
+    }
+
+    #[test]
+    fn test_translate_unsupported() {
+        // heredocs are not string templates
+        assert!(translate_pattern("a = <<EOT\nb\nEOT\n").is_err());
+
+        let rule = Rule::new(
+            "heredoc".into(),
+            Language::HCL,
+            "test".into(),
+            vec![RawPatternWithConstraints {
+                pattern: "policy = <<EOT\nb\nEOT\n".into(),
+                ..Default::default()
+            }],
+            vec![],
+            vec![],
+        );
+        let e = rule.to_terraform_json().unwrap_err();
+        assert!(e.to_string().contains("failed to translate rule heredoc"));
+    }
+}
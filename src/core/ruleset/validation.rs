@@ -0,0 +1,363 @@
+//! This module defines checks of rules for mistakes which are not detected until they are used, or which are never reported.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{collections::HashSet, convert::TryFrom, marker::PhantomData};
+
+use crate::core::{
+    language::{Queryable, TerraformJSON},
+    node::{Node, NodeType},
+    pattern::{Pattern, PatternExpression},
+    ruleset::{
+        compiled::CompiledRule, Language, RawConstraint, RawPatternExpression,
+        RawPatternWithConstraints, RawPredicate, Rule,
+    },
+};
+use crate::dispatch_language;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum ProblemLevel {
+    /// the rule works, but probably not as intended
+    Warning,
+    /// the rule fails to compile or fails on matching
+    Error,
+}
+
+/// `Problem` is a mistake found in a rule.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Problem {
+    pub level: ProblemLevel,
+    pub message: String,
+}
+
+impl Problem {
+    fn error(message: String) -> Self {
+        Problem {
+            level: ProblemLevel::Error,
+            message,
+        }
+    }
+
+    fn warning(message: String) -> Self {
+        Problem {
+            level: ProblemLevel::Warning,
+            message,
+        }
+    }
+}
+
+/// `validate` compiles `rule` for its language and returns the problems found in it.
+pub fn validate(rule: &Rule) -> Vec<Problem> {
+    let mut problems = dispatch_language!(rule.language, |Lang| {
+        Validator::<Lang>::default().validate(rule)
+    });
+    // rules for HCL are also applied to Terraform JSON unless they cannot be translated
+    if rule.language == Language::HCL && problems.iter().all(|p| p.level == ProblemLevel::Warning) {
+        if let Err(e) = rule
+            .to_terraform_json()
+            .and_then(|(rule, _)| CompiledRule::<TerraformJSON>::new(rule))
+        {
+            problems.push(Problem::warning(format!(
+                "the rule is not applied to Terraform JSON files: {}",
+                e
+            )));
+        }
+    }
+    problems
+}
+
+struct Validator<T: Queryable> {
+    problems: Vec<Problem>,
+    /// whether any pattern failed to parse
+    parse_failed: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Queryable> Default for Validator<T> {
+    fn default() -> Self {
+        Validator {
+            problems: vec![],
+            parse_failed: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Queryable> Validator<T> {
+    fn validate(mut self, rule: &Rule) -> Vec<Problem> {
+        let mut bound = HashSet::new();
+        for rpe in rule.get_patterns().unwrap_or_default() {
+            bound.extend(self.check_expression(&rpe));
+        }
+        for rewrite in rule.get_rewrite_options().unwrap_or_default() {
+            let mut unbound = self
+                .check_pattern(&rewrite, "rewrite")
+                .into_iter()
+                .filter(|m| !bound.contains(m))
+                .collect::<Vec<String>>();
+            unbound.sort();
+            for m in unbound {
+                self.problems.push(Problem::warning(format!(
+                    "rewrite refers to :[{}], which no pattern binds",
+                    m
+                )));
+            }
+        }
+
+        let compiled = rule.get_rewrite_options().and_then(|_| {
+            rule.get_patterns()?
+                .into_iter()
+                .map(PatternExpression::<T>::try_from)
+                .collect::<anyhow::Result<Vec<PatternExpression<T>>>>()
+        });
+        // a pattern which fails to parse has been reported with its field above
+        if let (Err(e), false) = (compiled, self.parse_failed) {
+            self.problems
+                .push(Problem::error(format!("failed to compile: {}", e)));
+        }
+
+        // the same expression may be checked several times since constraints and filters of a rule are copied to each of its patterns
+        let mut problems: Vec<Problem> = vec![];
+        for p in self.problems {
+            if !problems.contains(&p) {
+                problems.push(p);
+            }
+        }
+        problems
+    }
+
+    /// `check_expression` checks `rpe` and returns metavariables bound by its matches.
+    fn check_expression(&mut self, rpe: &RawPatternExpression) -> HashSet<String> {
+        match rpe {
+            RawPatternExpression::Pattern(rpc) => self.check_pattern_with_constraints(rpc),
+            RawPatternExpression::AllOf {
+                all_of,
+                constraints,
+            } => {
+                let mut bound = HashSet::new();
+                for child in all_of {
+                    let child_bound = self.check_expression(child);
+                    if is_positive(child) {
+                        bound.extend(child_bound);
+                    }
+                }
+                bound.extend(self.check_constraints(constraints, &bound));
+                bound
+            }
+            RawPatternExpression::AnyOf { any_of } => any_of
+                .iter()
+                .flat_map(|child| self.check_expression(child))
+                .collect(),
+            RawPatternExpression::NoneOf { none_of } => {
+                for child in none_of {
+                    self.check_expression(child);
+                }
+                HashSet::new()
+            }
+            RawPatternExpression::Inside { inside: child }
+            | RawPatternExpression::NotInside { not_inside: child } => {
+                self.check_expression(child);
+                HashSet::new()
+            }
+        }
+    }
+
+    fn check_pattern_with_constraints(
+        &mut self,
+        rpc: &RawPatternWithConstraints,
+    ) -> HashSet<String> {
+        let mut bound = self.check_pattern(&rpc.pattern, "pattern");
+        for filter in rpc
+            .filters
+            .pattern_not
+            .iter()
+            .chain(rpc.filters.pattern_not_inside.iter())
+        {
+            self.check_pattern(filter, "pattern");
+        }
+        // metavariables captured by `pattern-inside` are added to matches before constraints are checked
+        for filter in &rpc.filters.pattern_inside {
+            let captured = self.check_pattern(filter, "pattern");
+            bound.extend(captured);
+        }
+        let captured = self.check_constraints(&rpc.constraints, &bound);
+        bound.extend(captured);
+        bound
+    }
+
+    /// `check_constraints` checks that `constraints` target metavariables in `bound`.
+    /// It returns metavariables which patterns of satisfied constraints add to a match.
+    fn check_constraints(
+        &mut self,
+        constraints: &[RawConstraint],
+        bound: &HashSet<String>,
+    ) -> HashSet<String> {
+        let mut captured = HashSet::new();
+        for c in constraints {
+            if !bound.contains(&c.target) {
+                self.problems.push(Problem::error(format!(
+                    "constraint targets :[{}], which the pattern does not bind",
+                    c.target
+                )));
+            }
+            for rpc in c.get_pattern_with_constraints().unwrap_or_default() {
+                let constraint_bound = self.check_pattern_with_constraints(&rpc);
+                if matches!(c.should, RawPredicate::Match | RawPredicate::MatchAnyOf) {
+                    captured.extend(constraint_bound);
+                }
+            }
+        }
+        captured
+    }
+
+    /// `check_pattern` parses `source` and returns metavariables in it.
+    fn check_pattern(&mut self, source: &str, field: &str) -> HashSet<String> {
+        let pattern = match Pattern::<T>::try_from(source) {
+            Ok(p) => p,
+            Err(e) => {
+                self.parse_failed = true;
+                self.problems
+                    .push(Problem::error(format!("failed to parse {}: {}", field, e)));
+                return HashSet::new();
+            }
+        };
+        let mut metavariables = HashSet::new();
+        for node in T::unwrap_root(&pattern.to_root_node()) {
+            collect_metavariables::<T>(node, &mut metavariables);
+        }
+        // `:[_]` matches anything without capturing it
+        metavariables.remove("_");
+        metavariables
+    }
+}
+
+fn is_positive(rpe: &RawPatternExpression) -> bool {
+    matches!(
+        rpe,
+        RawPatternExpression::Pattern(_)
+            | RawPatternExpression::AllOf { .. }
+            | RawPatternExpression::AnyOf { .. }
+    )
+}
+
+static METAVARIABLE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r":\[(\.\.\.)?(?P<name>[A-Z_][A-Z_0-9]*)\]").unwrap());
+
+fn collect_metavariables<T: Queryable>(node: &Node, metavariables: &mut HashSet<String>) {
+    match node.kind() {
+        NodeType::Metavariable(mid) | NodeType::EllipsisMetavariable(mid) => {
+            metavariables.insert(mid.0);
+        }
+        _ if T::is_string_literal(node) => {
+            for caps in METAVARIABLE.captures_iter(node.as_str()) {
+                metavariables.insert(caps["name"].to_string());
+            }
+        }
+        _ => {
+            for child in &node.children {
+                collect_metavariables::<T>(child, metavariables);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ruleset::RuleSet;
+    use std::str::FromStr;
+
+    fn validate_first(ruleset: &str) -> Vec<Problem> {
+        let ruleset = RuleSet::from_str(ruleset).unwrap();
+        validate(&ruleset.rules[0])
+    }
+
+    #[test]
+    fn test_validate() {
+        let problems = validate_first(
+            r#"
+version: "1"
+rules:
+  - id: "valid"
+    language: hcl
+    message: test
+    pattern: |
+      resource "aws_ebs_volume" :[NAME] {
+        encrypted = :[X]
+      }
+    constraints:
+      - target: X
+        should: match
+        pattern: "false"
+    rewrite: |
+      resource "aws_ebs_volume" :[NAME] {
+        encrypted = true
+      }
+"#,
+        );
+        assert_eq!(problems, vec![]);
+
+        let problems = validate_first(
+            r#"
+version: "1"
+rules:
+  - id: "invalid"
+    language: hcl
+    message: test
+    patterns:
+      - pattern: a = :[X]
+      - all-of:
+          - pattern: b = :[Y]
+          - none-of:
+              - pattern: c = :[Z]
+        constraints:
+          - target: Z
+            should: match
+            regex-pattern: z
+    constraints:
+      - target: W
+        should: not-match
+        regex-pattern: w
+    rewrite: a = "${:[X]}-:[V]"
+"#,
+        );
+        let messages = problems
+            .iter()
+            .map(|p| (p.level, p.message.as_str()))
+            .collect::<Vec<_>>();
+        assert!(messages.contains(&(
+            ProblemLevel::Error,
+            "constraint targets :[W], which the pattern does not bind"
+        )));
+        assert!(messages.contains(&(
+            ProblemLevel::Error,
+            "constraint targets :[Z], which the pattern does not bind"
+        )));
+        assert!(messages.contains(&(
+            ProblemLevel::Warning,
+            "rewrite refers to :[V], which no pattern binds"
+        )));
+        assert!(!messages.iter().any(|(_, m)| m.contains(":[X]")));
+        assert_eq!(
+            messages.iter().filter(|(_, m)| m.contains(":[W]")).count(),
+            1
+        );
+
+        let problems = validate_first(
+            r#"
+version: "1"
+rules:
+  - id: "broken"
+    language: hcl
+    message: test
+    pattern: |
+      resource "aws_ebs_volume" :[NAME] {
+        encrypted = = false
+      }
+"#,
+        );
+        assert!(problems.iter().any(|p| p.level == ProblemLevel::Error
+            && p.message
+                .starts_with("failed to parse pattern: syntax error at line 2")));
+    }
+}
@@ -3,7 +3,10 @@ use anyhow::Result;
 use crate::core::language::Queryable;
 use std::marker::PhantomData;
 
-use super::{matcher::MatchedItem, rewriter::RewriteOption};
+use super::{
+    matcher::MatchedItem,
+    rewriter::{Edit, EditSet, EditStatus, RewriteOption},
+};
 
 #[derive(Clone)]
 pub struct Code<L>
@@ -28,18 +31,15 @@ where
     T: Queryable,
 {
     pub fn to_rewritten_form(self, item: &MatchedItem, roption: RewriteOption<T>) -> Result<Self> {
-        let current_code = self.as_str().as_bytes();
-
-        let before_snippet = String::from_utf8(current_code[0..item.area.start_byte()].to_vec())?;
-        let snippet = roption.to_rewritten_snippet(item)?;
-        let after_snippet = String::from_utf8(
-            current_code[item.area.end_byte().min(current_code.len())..current_code.len()].to_vec(),
-        )?;
+        let mut edits = EditSet::new();
+        edits.push(Edit::from_rewrite(self.code.len(), item, &roption)?);
+        Ok(self.to_edited_form(&edits)?.0)
+    }
 
-        Ok(Code::from(format!(
-            "{}{}{}",
-            before_snippet, snippet, after_snippet
-        )))
+    /// `to_edited_form` applies all the non-conflicting edits in `edits` at once.
+    pub fn to_edited_form(self, edits: &EditSet) -> Result<(Self, Vec<EditStatus>)> {
+        let (code, statuses) = edits.apply(self.as_str())?;
+        Ok((Code::from(code), statuses))
     }
 }
 
@@ -0,0 +1,136 @@
+//! This module defines inline suppression comments such as `# shisho:ignore rule-id -- reason`.
+
+use crate::core::{language::Queryable, tree::RefTreeView};
+
+pub const SUPPRESSION_MARKER: &str = "shisho:ignore";
+const REASON_SEPARATOR: &str = "--";
+
+/// `Suppression` describes a `shisho:ignore` comment in a target.
+///
+/// A suppression applies to items starting on the line of the comment.
+/// If the comment occupies its own line, it also applies to items starting on the next line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suppression {
+    /// 0-origin row where the comment starts
+    pub start_row: usize,
+    /// 0-origin row where the comment ends
+    pub end_row: usize,
+    /// whether the comment is the only content of its first line
+    pub standalone: bool,
+
+    /// IDs of rules to suppress; empty means all rules
+    pub rule_ids: Vec<String>,
+    pub reason: Option<String>,
+}
+
+impl Suppression {
+    /// `parse` reads `shisho:ignore` directive in a comment including its delimiters (`#`, `//` or `/* */`).
+    /// It returns `None` if the comment is not a directive.
+    pub fn parse(comment: &str, start_row: usize, standalone: bool) -> Option<Self> {
+        let comment = comment.trim_end();
+        let end_row = start_row + comment.matches('\n').count();
+
+        let body = comment.strip_suffix("*/").unwrap_or(comment);
+        let body = body
+            .trim_start_matches(|c: char| c == '#' || c == '/' || c == '*' || c.is_whitespace());
+        let body = body.strip_prefix(SUPPRESSION_MARKER)?;
+        if !(body.is_empty() || body.starts_with(char::is_whitespace)) {
+            return None;
+        }
+
+        let (ids, reason) = match body.find(REASON_SEPARATOR) {
+            Some(idx) => (
+                &body[..idx],
+                Some(body[idx + REASON_SEPARATOR.len()..].trim()),
+            ),
+            None => (body, None),
+        };
+        Some(Suppression {
+            start_row,
+            end_row,
+            standalone,
+
+            rule_ids: ids
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect(),
+            reason: reason.filter(|r| !r.is_empty()).map(String::from),
+        })
+    }
+
+    /// `is_valid` returns whether the suppression has a reason, which is required to take effect.
+    pub fn is_valid(&self) -> bool {
+        self.reason.is_some()
+    }
+
+    /// `suppresses` returns whether the suppression applies to an item of `rule_id` starting on `row` (0-origin).
+    pub fn suppresses(&self, rule_id: &str, row: usize) -> bool {
+        self.is_valid()
+            && (row == self.start_row || (self.standalone && row == self.end_row + 1))
+            && (self.rule_ids.is_empty() || self.rule_ids.iter().any(|id| id == rule_id))
+    }
+}
+
+/// `find_suppressions` collects suppression comments in `tree`.
+pub fn find_suppressions<'tree, T: Queryable + 'tree>(
+    tree: &RefTreeView<'tree, T>,
+) -> Vec<Suppression> {
+    tree.traverse()
+        .filter(|(_, node)| T::is_comment(node))
+        .filter_map(|(_, node)| {
+            let line_start = node.source[..node.start_byte()]
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map(|idx| idx + 1)
+                .unwrap_or(0);
+            let standalone = node.source[line_start..node.start_byte()]
+                .iter()
+                .all(|b| b.is_ascii_whitespace());
+            Suppression::parse(node.as_str(), node.start_position().row, standalone)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let s = Suppression::parse("# shisho:ignore rule-a -- accepted risk", 3, true).unwrap();
+        assert_eq!(s.rule_ids, vec!["rule-a".to_string()]);
+        assert_eq!(s.reason, Some("accepted risk".into()));
+        assert!(s.suppresses("rule-a", 3));
+        assert!(s.suppresses("rule-a", 4));
+        assert!(!s.suppresses("rule-a", 5));
+        assert!(!s.suppresses("rule-b", 3));
+
+        let s = Suppression::parse("// shisho:ignore rule-a, rule-b -- test", 0, false).unwrap();
+        assert_eq!(s.rule_ids, vec!["rule-a".to_string(), "rule-b".to_string()]);
+        assert!(s.suppresses("rule-b", 0));
+        assert!(!s.suppresses("rule-b", 1));
+
+        let s = Suppression::parse("/* shisho:ignore -- generated\n */", 1, true).unwrap();
+        assert!(s.rule_ids.is_empty());
+        assert_eq!(s.end_row, 2);
+        assert!(s.suppresses("any-rule", 3));
+    }
+
+    #[test]
+    fn test_parse_without_reason() {
+        let s = Suppression::parse("# shisho:ignore rule-a", 0, true).unwrap();
+        assert!(!s.is_valid());
+        assert!(!s.suppresses("rule-a", 0));
+
+        let s = Suppression::parse("# shisho:ignore rule-a --  ", 0, true).unwrap();
+        assert!(!s.is_valid());
+    }
+
+    #[test]
+    fn test_parse_non_directive() {
+        assert!(Suppression::parse("# just a comment", 0, true).is_none());
+        assert!(Suppression::parse("# see shisho:ignore -- x", 0, true).is_none());
+        assert!(Suppression::parse("# shisho:ignored -- x", 0, true).is_none());
+    }
+}
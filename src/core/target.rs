@@ -1,27 +1,117 @@
+use ansi_term::Color;
 use anyhow::Result;
 use encoding_rs::Encoding;
 use encoding_rs_io::DecodeReaderBytesBuilder;
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    DirEntry, Match, WalkBuilder,
+};
 use itertools::Itertools;
+use once_cell::sync::Lazy;
 use pathdiff::diff_paths;
+use regex::Regex;
 use std::{
-    env,
+    collections::HashMap,
+    env, fmt,
     io::Read,
+    ops::Range,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
-use walkdir::WalkDir;
 
-use crate::core::ruleset::Language;
+use crate::core::{changes::ChangeSet, ruleset::Language};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Target {
     pub path: Option<PathBuf>,
     pub body: String,
+
+    /// rows (0-origin, end-exclusive) to report findings in; `None` means all the rows
+    pub changed_rows: Option<Vec<Range<usize>>>,
+    /// encoding which was used to decode the target
+    pub encoding: Option<&'static Encoding>,
+    /// language used instead of the one detected from the path
+    pub language_override: Option<Language>,
+}
+
+/// `ScopedPattern` is a glob pattern which matches paths relative to `base`.
+/// A path also matches if any of its ancestor directories matches.
+#[derive(Debug)]
+struct ScopedPattern {
+    base: PathBuf,
+    pattern: glob::Pattern,
+}
+
+impl ScopedPattern {
+    fn new(base: &Path, pattern: &str) -> Result<Self> {
+        Ok(ScopedPattern {
+            base: base.canonicalize()?,
+            pattern: glob::Pattern::new(pattern.trim_start_matches("./"))
+                .map_err(|e| anyhow::anyhow!("failed to load pattern {}: {}", pattern, e))?,
+        })
+    }
+
+    fn matches(&self, p: &Path) -> bool {
+        p.canonicalize()
+            .ok()
+            .and_then(|p| {
+                p.strip_prefix(&self.base)
+                    .ok()
+                    .map(|rel| rel.ancestors().any(|a| self.pattern.matches_path(a)))
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// name of the ignore file which is specific to shisho; it follows the syntax of `.gitignore`
+pub const IGNORE_FILE_NAME: &str = ".shishoignore";
+
+/// names of directories which usually contain third-party code
+const VENDORED_DIRS: [&str; 3] = ["vendor", "node_modules", "bower_components"];
+
+/// number of leading bytes to look for NUL bytes to detect binary files
+const BINARY_DETECTION_LENGTH: usize = 8192;
+
+/// `SkipReason` describes why a file found in traversal was not loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkipReason {
+    Ignored,
+    Hidden,
+    Vendored,
+    Binary,
+    TooLarge(u64),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Ignored => write!(f, "matched an ignore file"),
+            SkipReason::Hidden => write!(f, "hidden"),
+            SkipReason::Vendored => write!(f, "vendored"),
+            SkipReason::Binary => write!(f, "binary"),
+            SkipReason::TooLarge(size) => write!(f, "too large ({} bytes)", size),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct TargetLoader {
     exclude_path_pattern: Vec<glob::Pattern>,
     encoding: Option<&'static Encoding>,
+    changes: Option<ChangeSet>,
+
+    /// whether to skip files matching ignore files, hidden files and vendored files in traversal
+    respect_ignore: bool,
+    /// files larger than this size (in bytes) are skipped
+    max_file_size: Option<u64>,
+    /// whether to print skipped files with their reasons
+    verbose: bool,
+
+    scoped_include_patterns: Vec<ScopedPattern>,
+    scoped_exclude_patterns: Vec<ScopedPattern>,
+    scoped_encodings: Vec<(ScopedPattern, &'static Encoding)>,
+    /// languages by file extensions (e.g. `tfvars`) or file names
+    languages: HashMap<String, Language>,
 }
 
 impl TargetLoader {
@@ -71,43 +161,213 @@ impl TargetLoader {
         Ok(TargetLoader {
             exclude_path_pattern,
             encoding,
+            changes: None,
+
+            respect_ignore: true,
+            max_file_size: None,
+            verbose: false,
+
+            scoped_include_patterns: vec![],
+            scoped_exclude_patterns: vec![],
+            scoped_encodings: vec![],
+            languages: HashMap::new(),
         })
     }
 
+    /// `with_includes` restricts targets to the paths matching any of `patterns`, which are relative to `base`.
+    pub fn with_includes(mut self, base: &Path, patterns: &[String]) -> Result<Self> {
+        for p in patterns {
+            self.scoped_include_patterns
+                .push(ScopedPattern::new(base, p)?);
+        }
+        Ok(self)
+    }
+
+    /// `with_excludes` excludes the paths matching any of `patterns`, which are relative to `base`.
+    pub fn with_excludes(mut self, base: &Path, patterns: &[String]) -> Result<Self> {
+        for p in patterns {
+            self.scoped_exclude_patterns
+                .push(ScopedPattern::new(base, p)?);
+        }
+        Ok(self)
+    }
+
+    /// `with_encodings` decodes the paths matching each pattern, which is relative to `base`, with the paired encoding.
+    /// The encoding given to `TargetLoader::new` takes precedence over them.
+    pub fn with_encodings(
+        mut self,
+        base: &Path,
+        encodings: &[(String, &'static Encoding)],
+    ) -> Result<Self> {
+        for (p, encoding) in encodings {
+            self.scoped_encodings
+                .push((ScopedPattern::new(base, p)?, *encoding));
+        }
+        Ok(self)
+    }
+
+    /// `with_languages` overrides languages of files by their extensions or names.
+    pub fn with_languages(mut self, languages: HashMap<String, Language>) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    /// `with_changes` restricts targets to the files in `changes`.
+    pub fn with_changes(mut self, changes: ChangeSet) -> Self {
+        self.changes = Some(changes);
+        self
+    }
+
+    /// `with_ignore` sets whether to respect ignore files (`.gitignore`, `.ignore` and `.shishoignore`)
+    /// and to skip hidden and vendored files in directory traversal.
+    pub fn with_ignore(mut self, respect_ignore: bool) -> Self {
+        self.respect_ignore = respect_ignore;
+        self
+    }
+
+    /// `with_max_file_size` skips files larger than `max_file_size` bytes.
+    pub fn with_max_file_size(mut self, max_file_size: Option<u64>) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// `with_verbose` sets whether to print skipped files with their reasons to stderr.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
     pub fn from(&self, p: PathBuf) -> Result<Vec<Target>> {
-        if p.is_dir() {
-            Ok(self.from_dir(p))
-        } else if self.should_load(&p) {
-            Ok(vec![self.from_file(p)?])
+        let ignore_errors = p.is_dir();
+        let mut targets = vec![];
+        for p in self.paths(p) {
+            match self.load(p) {
+                Ok(target) => targets.extend(target),
+                Err(_) if ignore_errors => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(targets)
+    }
+
+    /// `paths` lists files to load at `p`, sorted by their paths.
+    pub fn paths(&self, p: PathBuf) -> Vec<PathBuf> {
+        let mut paths = if p.is_dir() {
+            self.paths_in_dir(p)
         } else {
-            Ok(vec![])
+            vec![p]
+        };
+        paths.retain(|p| self.should_load(p) && self.is_in_scope(p) && self.is_changed(p));
+        paths.sort();
+        paths
+    }
+
+    fn paths_in_dir(&self, p: PathBuf) -> Vec<PathBuf> {
+        let (found, skipped) = self.walk(&p);
+        for (p, reason) in skipped {
+            self.skip(&p, reason);
         }
+        found.into_iter().filter(|p| p.is_file()).collect()
     }
 
-    fn from_dir(&self, p: PathBuf) -> Vec<Target> {
-        WalkDir::new(p)
-            .into_iter()
+    /// `walk` lists entries under `root`, and entries skipped in the traversal with their reasons.
+    fn walk(&self, root: &Path) -> (Vec<PathBuf>, Vec<(PathBuf, SkipReason)>) {
+        let skipped = Arc::new(Mutex::new(vec![]));
+        let mut builder = WalkBuilder::new(root);
+        // ignore files are handled in `filter_entry` instead of the ignore crate, which does not tell skipped entries
+        builder.standard_filters(false);
+        if self.respect_ignore {
+            let absolute_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+            let ignore_files = IgnoreFiles::new(&absolute_root);
+            let root = root.to_path_buf();
+            let skipped = skipped.clone();
+            builder.filter_entry(move |e| {
+                let reason = skip_reason_of_entry(e).or_else(|| {
+                    let p = absolute_root.join(e.path().strip_prefix(&root).unwrap_or(e.path()));
+                    let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                    if e.depth() > 0 && ignore_files.is_ignored(&p, is_dir) {
+                        Some(SkipReason::Ignored)
+                    } else {
+                        None
+                    }
+                });
+                match reason {
+                    Some(reason) => {
+                        // a skipped directory is recorded once without its contents
+                        skipped
+                            .lock()
+                            .unwrap()
+                            .push((e.path().to_path_buf(), reason));
+                        false
+                    }
+                    None => true,
+                }
+            });
+        }
+
+        let found = builder
+            .build()
             .filter_map(|e| e.ok())
             .map(|e| e.into_path())
-            .filter(|p| p.is_file() && self.should_load(p))
-            .map(move |p| self.from_file(p))
-            .filter_map(|e| e.ok())
-            .collect()
+            .collect();
+        let skipped = std::mem::take(&mut *skipped.lock().unwrap());
+        (found, skipped)
     }
 
-    fn from_file(&self, p: PathBuf) -> Result<Target> {
+    /// `load` loads a file at `p`. It returns `None` if the file should be skipped.
+    pub fn load(&self, p: PathBuf) -> Result<Option<Target>> {
+        if let Some(max_file_size) = self.max_file_size {
+            let size = std::fs::metadata(&p)?.len();
+            if size > max_file_size {
+                self.skip(&p, SkipReason::TooLarge(size));
+                return Ok(None);
+            }
+        }
+
+        let encoding = self.encoding.or_else(|| {
+            self.scoped_encodings
+                .iter()
+                .find(|(pattern, _)| pattern.matches(&p))
+                .map(|(_, encoding)| *encoding)
+        });
         let body_bytes = std::fs::read(&p)?;
+        if is_binary(&body_bytes, encoding) {
+            self.skip(&p, SkipReason::Binary);
+            return Ok(None);
+        }
+
         let mut decoder = DecodeReaderBytesBuilder::new()
-            .encoding(self.encoding)
+            .encoding(encoding)
             .build(&body_bytes[..]);
 
         let mut body_string = String::new();
         decoder.read_to_string(&mut body_string)?;
 
-        Ok(Target {
+        let changed_rows = self
+            .changes
+            .as_ref()
+            .and_then(|c| c.changed_rows(&p))
+            .cloned();
+        let language_override = p.file_name().and_then(|name| {
+            let name = name.to_string_lossy();
+            self.languages
+                .iter()
+                .find(|(key, _)| name == key.as_str() || name.ends_with(&format!(".{}", key)))
+                .map(|(_, lang)| *lang)
+        });
+        Ok(Some(Target {
             path: Some(p),
             body: body_string,
-        })
+            changed_rows,
+            encoding,
+            language_override,
+        }))
+    }
+
+    fn skip(&self, p: &Path, reason: SkipReason) {
+        if self.verbose {
+            report_skip(p, reason);
+        }
     }
 
     pub fn from_reader<R: std::io::Read>(&self, r: R) -> Result<Target> {
@@ -121,6 +381,8 @@ impl TargetLoader {
         Ok(Target {
             path: None,
             body: body_string,
+            encoding: self.encoding,
+            ..Default::default()
         })
     }
 
@@ -129,6 +391,127 @@ impl TargetLoader {
             .iter()
             .all(|gpattern| !gpattern.matches(p.as_os_str().to_str().unwrap()))
     }
+
+    fn is_in_scope(&self, p: &Path) -> bool {
+        (self.scoped_include_patterns.is_empty()
+            || self.scoped_include_patterns.iter().any(|s| s.matches(p)))
+            && !self.scoped_exclude_patterns.iter().any(|s| s.matches(p))
+    }
+
+    fn is_changed(&self, p: &Path) -> bool {
+        match self.changes {
+            Some(ref c) => c.changed_rows(p).is_some(),
+            None => true,
+        }
+    }
+}
+
+/// `IgnoreFiles` matches paths against ignore files (`.shishoignore`, `.ignore`, `.gitignore` and `.git/info/exclude`) in their ancestor directories and the global gitignore.
+/// Ignore files in deeper directories take precedence, and ones in the same directory take precedence in the above order.
+struct IgnoreFiles {
+    /// matchers of ignore files in each directory, which are loaded on demand
+    matchers: Mutex<HashMap<PathBuf, Arc<Vec<Gitignore>>>>,
+    /// the outermost directory whose ignore files are used
+    root: PathBuf,
+    global: Gitignore,
+}
+
+impl IgnoreFiles {
+    /// `new` creates matchers for traversal from `root`, which needs to be an absolute path.
+    /// Ignore files are used up to the root of the git repository which contains `root`, or `root` itself outside of repositories,
+    /// so that ones outside the project (e.g. `.gitignore` in the home directory) never apply.
+    fn new(root: &Path) -> Self {
+        let root = root
+            .ancestors()
+            .find(|d| d.join(".git").exists())
+            .unwrap_or(root);
+        IgnoreFiles {
+            matchers: Mutex::new(HashMap::new()),
+            root: root.to_path_buf(),
+            global: Gitignore::global().0,
+        }
+    }
+
+    /// `is_ignored` returns whether `p`, which needs to be an absolute path under the root, is ignored.
+    fn is_ignored(&self, p: &Path, is_dir: bool) -> bool {
+        // the lock is released before matching so that it is held only while loading ignore files
+        let matchers = {
+            let mut matchers = self.matchers.lock().unwrap();
+            p.ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(&self.root))
+                .map(|dir| {
+                    matchers
+                        .entry(dir.to_path_buf())
+                        .or_insert_with(|| Arc::new(Self::load(dir)))
+                        .clone()
+                })
+                .collect::<Vec<_>>()
+        };
+        for m in matchers.iter().flat_map(|m| m.iter()) {
+            match m.matched(p, is_dir) {
+                Match::None => (),
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        self.global.matched(p, is_dir).is_ignore()
+    }
+
+    fn load(dir: &Path) -> Vec<Gitignore> {
+        [
+            IGNORE_FILE_NAME,
+            ".ignore",
+            ".gitignore",
+            ".git/info/exclude",
+        ]
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|p| p.is_file())
+        .filter_map(|p| {
+            let mut builder = GitignoreBuilder::new(dir);
+            builder.add(p);
+            builder.build().ok()
+        })
+        .collect()
+    }
+}
+
+/// `skip_reason_of_entry` decides whether to skip an entry in traversal by its name.
+/// The root of traversal is never skipped.
+fn skip_reason_of_entry(e: &DirEntry) -> Option<SkipReason> {
+    if e.depth() == 0 {
+        return None;
+    }
+    let name = e.file_name().to_string_lossy();
+    if name.starts_with('.') {
+        Some(SkipReason::Hidden)
+    } else if VENDORED_DIRS.contains(&name.as_ref()) && e.path().is_dir() {
+        Some(SkipReason::Vendored)
+    } else {
+        None
+    }
+}
+
+fn report_skip(p: &Path, reason: SkipReason) {
+    eprintln!(
+        "{}: {} ({})",
+        Color::Yellow.paint("skipped"),
+        p.to_string_lossy(),
+        reason
+    );
+}
+
+/// `is_binary` detects binary files by NUL bytes in their beginning.
+/// Files with a BOM or in UTF-16 are regarded as text since they may contain NUL bytes.
+fn is_binary(bytes: &[u8], encoding: Option<&'static Encoding>) -> bool {
+    if Encoding::for_bom(bytes).is_some()
+        || encoding == Some(encoding_rs::UTF_16LE)
+        || encoding == Some(encoding_rs::UTF_16BE)
+    {
+        return false;
+    }
+    bytes[..bytes.len().min(BINARY_DETECTION_LENGTH)].contains(&0)
 }
 
 impl Target {
@@ -145,7 +528,8 @@ impl Target {
         self.relative_path_from(&env::current_dir().unwrap())
     }
 
-    fn relative_path_from(&self, base: &Path) -> String {
+    /// `relative_path_from` returns the path relative to `base`, which needs to be canonicalized.
+    pub fn relative_path_from(&self, base: &Path) -> String {
         if let Some(ref p) = self.path {
             let p = p.canonicalize().unwrap();
             let p = diff_paths(p, base).unwrap();
@@ -155,12 +539,29 @@ impl Target {
         }
     }
 
+    /// `is_changed` returns whether any row from `start_row` to `end_row` (0-origin, inclusive) is to be reported.
+    pub fn is_changed(&self, start_row: usize, end_row: usize) -> bool {
+        match self.changed_rows {
+            Some(ref rows) => rows.iter().any(|r| r.start <= end_row && start_row < r.end),
+            None => true,
+        }
+    }
+
     pub fn is_file(&self) -> bool {
         self.path.is_some()
     }
 
     pub fn language(&self) -> Option<Language> {
+        if self.language_override.is_some() {
+            return self.language_override;
+        }
         let p = self.path.as_ref().and_then(|p| p.canonicalize().ok())?;
+        // Terraform configurations in the JSON syntax, e.g. `main.tf.json` or `terraform.tfvars.json`
+        let name = p.file_name()?.to_str()?;
+        if name.ends_with(".tf.json") || name.ends_with(".tfvars.json") {
+            return Some(Language::TerraformJSON);
+        }
+
         let ext = if let Some(ext) = p.extension() {
             Some(ext)
         } else {
@@ -169,7 +570,17 @@ impl Target {
 
         match ext.to_str() {
             Some("go") => return Some(Language::Go),
-            Some("tf") => return Some(Language::HCL),
+            // `tfvars` for variables of Terraform, and `hcl` for Terragrunt, Packer (`*.pkr.hcl`), Nomad and so on
+            Some("tf") | Some("tfvars") | Some("hcl") => return Some(Language::HCL),
+            Some("yaml") | Some("yml") if is_kubernetes_manifest(&self.body) => {
+                return Some(Language::Kubernetes)
+            }
+            Some("json") if is_arm_template(&self.body) => return Some(Language::ARM),
+            Some("yaml") | Some("yml") | Some("json") | Some("template")
+                if is_cloudformation_template(&self.body) =>
+            {
+                return Some(Language::CloudFormation)
+            }
             _ => (),
         };
 
@@ -186,11 +597,46 @@ impl Target {
     }
 }
 
+static DOCUMENT_SEPARATOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^---.*$").unwrap());
+static KUBERNETES_API_VERSION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?m)^["']?apiVersion["']?\s*:"#).unwrap());
+static KUBERNETES_KIND: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?m)^["']?kind["']?\s*:"#).unwrap());
+
+/// `is_kubernetes_manifest` returns whether any YAML document in `body` has top-level `apiVersion` and `kind` keys.
+fn is_kubernetes_manifest(body: &str) -> bool {
+    DOCUMENT_SEPARATOR.split(body).any(|document| {
+        KUBERNETES_API_VERSION.is_match(document) && KUBERNETES_KIND.is_match(document)
+    })
+}
+
+static ARM_SCHEMA: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#""\$schema"\s*:\s*"[^"]*[dD]eploymentTemplate\.json"#).unwrap());
+
+/// `is_arm_template` returns whether `body` is an Azure Resource Manager template, whose `$schema` is a schema of deployment templates.
+fn is_arm_template(body: &str) -> bool {
+    ARM_SCHEMA.is_match(body)
+}
+
+static CLOUDFORMATION_VERSION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?m)^\s*["']?AWSTemplateFormatVersion["']?\s*:"#).unwrap());
+static CLOUDFORMATION_RESOURCES: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?m)(^|[\s{,])["']?Resources["']?\s*:"#).unwrap());
+static CLOUDFORMATION_RESOURCE_TYPE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"["']?Type["']?\s*:\s*["']?AWS::"#).unwrap());
+
+/// `is_cloudformation_template` returns whether `body` is a CloudFormation template in YAML or JSON,
+/// which has `AWSTemplateFormatVersion`, or `Resources` with AWS resource types.
+fn is_cloudformation_template(body: &str) -> bool {
+    CLOUDFORMATION_VERSION.is_match(body)
+        || (CLOUDFORMATION_RESOURCES.is_match(body) && CLOUDFORMATION_RESOURCE_TYPE.is_match(body))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
-    use super::{Target, TargetLoader};
+    use super::{Language, SkipReason, Target, TargetLoader};
 
     #[test]
     fn test_loader_exclusion() {
@@ -218,6 +664,7 @@ mod tests {
             let t = Target {
                 path: Some(PathBuf::from(format!("{}", file!()))),
                 body: "".to_string(),
+                ..Default::default()
             };
 
             let p = t.relative_path_from(&PathBuf::from("/workdir/hoge"));
@@ -234,4 +681,233 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_loader_scope() {
+        let base = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/cli/tests/ruleset/generic");
+        let loader = TargetLoader::new(vec![], None)
+            .unwrap()
+            .with_includes(&base, &["constraints".into(), "./encoding/*.go".into()])
+            .unwrap()
+            .with_excludes(
+                &base,
+                &["constraints/*.yaml".into(), "**/utf_16le.go".into()],
+            )
+            .unwrap()
+            .with_encodings(&base, &[("**/shift_jis.go".into(), encoding_rs::SHIFT_JIS)])
+            .unwrap()
+            .with_languages(
+                vec![("match.tf".to_string(), Language::Go)]
+                    .into_iter()
+                    .collect(),
+            );
+
+        let mut targets = loader.from(base.clone()).unwrap();
+        targets.sort_by_key(|t| t.path.clone());
+        let files = targets
+            .iter()
+            .map(|t| t.path.as_ref().unwrap().strip_prefix(&base).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                Path::new("constraints/match.tf"),
+                Path::new("constraints/unmatch.tf"),
+                Path::new("encoding/shift_jis.go"),
+            ]
+        );
+        assert_eq!(targets[0].language(), Some(Language::Go));
+        assert_eq!(targets[1].language(), Some(Language::HCL));
+        assert_eq!(targets[2].encoding, Some(encoding_rs::SHIFT_JIS));
+    }
+
+    #[test]
+    fn test_loader_ignore() {
+        let dir = tempfile::tempdir().unwrap();
+        // ignore files outside the repository never apply
+        std::fs::write(dir.path().join(".gitignore"), "*\n").unwrap();
+        let base = dir.path().join("project");
+        std::fs::create_dir_all(base.join(".git")).unwrap();
+        for (p, body) in [
+            (".gitignore", "*.log\n".as_bytes()),
+            ("nested/.gitignore", b"generated.tf\n"),
+            (".shishoignore", b"fixtures\n"),
+            ("main.tf", b"a = 1\n"),
+            ("debug.log", b"log\n"),
+            ("nested/main.tf", b"a = 1\n"),
+            ("nested/debug.log", b"log\n"),
+            ("nested/generated.tf", b"a = 1\n"),
+            ("fixtures/main.tf", b"a = 1\n"),
+            (".hidden/main.tf", b"a = 1\n"),
+            ("vendor/main.tf", b"a = 1\n"),
+            ("binary.tf", b"a\0b"),
+            ("large.tf", &[b'a'; 64]),
+        ] {
+            let p = base.join(p);
+            std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+            std::fs::write(p, body).unwrap();
+        }
+
+        let files = |loader: TargetLoader| {
+            let mut files = loader
+                .from(base.clone())
+                .unwrap()
+                .into_iter()
+                .map(|t| t.path.unwrap().strip_prefix(&base).unwrap().to_path_buf())
+                .collect::<Vec<PathBuf>>();
+            files.sort();
+            files
+        };
+
+        let loader = TargetLoader::new(vec![], None)
+            .unwrap()
+            .with_max_file_size(Some(32));
+        let (_, skipped) = loader.walk(&base);
+        let mut skipped = skipped
+            .into_iter()
+            .map(|(p, reason)| (p.strip_prefix(&base).unwrap().to_path_buf(), reason))
+            .collect::<Vec<(PathBuf, SkipReason)>>();
+        skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            skipped,
+            vec![
+                (PathBuf::from(".git"), SkipReason::Hidden),
+                (PathBuf::from(".gitignore"), SkipReason::Hidden),
+                (PathBuf::from(".hidden"), SkipReason::Hidden),
+                (PathBuf::from(".shishoignore"), SkipReason::Hidden),
+                (PathBuf::from("debug.log"), SkipReason::Ignored),
+                (PathBuf::from("fixtures"), SkipReason::Ignored),
+                (PathBuf::from("nested/.gitignore"), SkipReason::Hidden),
+                (PathBuf::from("nested/debug.log"), SkipReason::Ignored),
+                (PathBuf::from("nested/generated.tf"), SkipReason::Ignored),
+                (PathBuf::from("vendor"), SkipReason::Vendored),
+            ]
+        );
+        assert_eq!(
+            files(loader),
+            vec![PathBuf::from("main.tf"), PathBuf::from("nested/main.tf")]
+        );
+
+        // ignore files are used up to the root of the repository even when a subdirectory is traversed
+        let loader = TargetLoader::new(vec![], None).unwrap();
+        let (found, _) = loader.walk(&base.join("nested"));
+        assert!(!found.contains(&base.join("nested/debug.log")));
+        assert!(found.contains(&base.join("nested/main.tf")));
+
+        // all the files except the binary one, including ignore files themselves
+        let loader = TargetLoader::new(vec![], None).unwrap().with_ignore(false);
+        assert_eq!(files(loader).len(), 12);
+    }
+
+    #[test]
+    fn test_changed_rows() {
+        let t = Target {
+            changed_rows: Some(vec![2..4, 10..11]),
+            ..Default::default()
+        };
+        assert!(t.is_changed(0, 2));
+        assert!(t.is_changed(3, 5));
+        assert!(t.is_changed(10, 10));
+        assert!(!t.is_changed(0, 1));
+        assert!(!t.is_changed(4, 9));
+
+        assert!(Target::default().is_changed(0, 0));
+    }
+
+    #[test]
+    fn test_language() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        let language = |name: &str, body: &str| {
+            let p = base.join(name);
+            std::fs::write(&p, body).unwrap();
+            Target {
+                path: Some(p),
+                body: body.to_string(),
+                ..Default::default()
+            }
+            .language()
+        };
+
+        assert_eq!(language("main.tf", "a = 1\n"), Some(Language::HCL));
+        assert_eq!(
+            language("terraform.tfvars", "region = \"us-east-1\"\n"),
+            Some(Language::HCL)
+        );
+        assert_eq!(
+            language("terragrunt.hcl", "inputs = {}\n"),
+            Some(Language::HCL)
+        );
+        assert_eq!(
+            language("image.pkr.hcl", "source \"a\" \"b\" {}\n"),
+            Some(Language::HCL)
+        );
+        assert_eq!(
+            language("main.tf.json", r#"{ "resource": {} }"#),
+            Some(Language::TerraformJSON)
+        );
+        assert_eq!(
+            language("terraform.tfvars.json", r#"{ "region": "us-east-1" }"#),
+            Some(Language::TerraformJSON)
+        );
+        assert_eq!(
+            language("pod.yaml", "apiVersion: v1\nkind: Pod\n"),
+            Some(Language::Kubernetes)
+        );
+        assert_eq!(
+            language(
+                "all.yml",
+                "# generated\nfoo: bar\n---\nkind: Service\napiVersion: v1\n"
+            ),
+            Some(Language::Kubernetes)
+        );
+        // keys of different documents or nested ones do not make a manifest
+        assert_eq!(
+            language("split.yaml", "apiVersion: v1\n---\nkind: Pod\n"),
+            None
+        );
+        assert_eq!(
+            language("values.yaml", "app:\n  apiVersion: v1\n  kind: Pod\n"),
+            None
+        );
+        assert_eq!(language("pod.json", "{}"), None);
+
+        assert_eq!(
+            language(
+                "stack.yaml",
+                "AWSTemplateFormatVersion: 2010-09-09\nResources: {}\n"
+            ),
+            Some(Language::CloudFormation)
+        );
+        assert_eq!(
+            language(
+                "stack.template",
+                "Resources:\n  Logs:\n    Type: AWS::S3::Bucket\n"
+            ),
+            Some(Language::CloudFormation)
+        );
+        assert_eq!(
+            language(
+                "stack.json",
+                r#"{ "Resources": { "Logs": { "Type": "AWS::S3::Bucket" } } }"#
+            ),
+            Some(Language::CloudFormation)
+        );
+        assert_eq!(language("package.json", r#"{ "name": "app" }"#), None);
+
+        assert_eq!(
+            language(
+                "azuredeploy.json",
+                r#"{ "$schema": "https://schema.management.azure.com/schemas/2019-04-01/deploymentTemplate.json#", "resources": [] }"#
+            ),
+            Some(Language::ARM)
+        );
+        assert_eq!(
+            language(
+                "parameters.json",
+                r#"{ "$schema": "https://schema.management.azure.com/schemas/2019-04-01/deploymentParameters.json#" }"#
+            ),
+            None
+        );
+    }
 }
@@ -1,16 +1,18 @@
 use crate::core::{
     language::Queryable,
-    matcher::{MatchedItem, TreeMatcher},
+    matcher::{MatchedItem, StepBudget, TreeMatcher},
 };
 use anyhow::{anyhow, Result};
 use std::{
-    collections::VecDeque,
+    cell::OnceCell,
+    collections::{HashMap, VecDeque},
     convert::{TryFrom, TryInto},
     marker::PhantomData,
 };
 
 use super::{
-    node::{Node, RootNode},
+    node::{Node, NodeType, RootNode, SyntaxError},
+    pattern::{ExpressionError, PatternExpression},
     query::Query,
     source::NormalizedSource,
 };
@@ -30,6 +32,12 @@ where
     pub fn to_root_node(&'_ self) -> RootNode<'_> {
         RootNode::from_tstree(&self.tstree, &self.source, self.with_extra_newline)
     }
+
+    /// `syntax_error` returns the first part of the code which could not be parsed.
+    /// Such code is still matched, but parts around the error may be missing from the tree.
+    pub fn syntax_error(&self) -> Option<SyntaxError> {
+        SyntaxError::find(&self.tstree, &self.source)
+    }
 }
 
 impl<'tree, T> TryFrom<NormalizedSource> for Tree<'tree, T>
@@ -124,6 +132,11 @@ where
 pub struct RefTreeView<'tree, T> {
     pub view_root: &'tree Node<'tree>,
     pub source: &'tree [u8],
+
+    /// index of nodes by their kinds, which is built on the first query
+    index: OnceCell<KindIndex<'tree>>,
+    /// budget of steps shared by matchers on this view
+    budget: StepBudget,
     _marker: PhantomData<T>,
 }
 
@@ -140,25 +153,144 @@ where
         'tree: 'query,
         'query: 'view,
     {
-        TreeMatcher::new(self.traverse(), &q.pattern).filter_map(move |mut x| {
-            let captures = match x.satisfies_all(q.constraints) {
-                Ok((true, captures)) => captures,
-                Ok((false, _)) => return None,
-                Err(e) => {
-                    return Some(Err(anyhow::anyhow!(
-                        "failed to validate a match with constraints: {}",
-                        e
-                    )))
+        TreeMatcher::with_index(self.index(), &q.pattern)
+            .with_budget(self.budget.clone())
+            .filter_map(move |mut x| {
+                let captures = match x.satisfies_all(q.constraints) {
+                    Ok((true, captures)) => captures,
+                    Ok((false, _)) => return None,
+                    Err(e) => {
+                        return Some(Err(anyhow::anyhow!(
+                            "failed to validate a match with constraints: {}",
+                            e
+                        )))
+                    }
+                };
+                x.captures.extend(captures);
+
+                Some(Ok(x))
+            })
+    }
+
+    /// `matches_expression` returns all the matches of the given expression.
+    pub fn matches_expression(
+        &'view self,
+        e: &PatternExpression<T>,
+    ) -> Result<Vec<MatchedItem<'tree>>> {
+        match e {
+            PatternExpression::Pattern(pc) => self.matches(&pc.as_query()).collect(),
+            PatternExpression::AnyOf(es) => {
+                let mut items = vec![];
+                for e in es {
+                    items.extend(self.matches_expression(e)?);
                 }
-            };
-            x.captures.extend(captures);
-            Some(Ok(x))
-        })
+                Ok(items)
+            }
+            PatternExpression::AllOf(es, constraints) => {
+                let (positives, negatives): (
+                    Vec<&PatternExpression<T>>,
+                    Vec<&PatternExpression<T>>,
+                ) = es.iter().partition(|e| e.is_positive());
+
+                // (1) take intersections of matches of positive expressions
+                let mut positives = positives.into_iter();
+                let mut items = match positives.next() {
+                    Some(e) => self.matches_expression(e)?,
+                    None => return Err(ExpressionError::NoPositiveExpression.into()),
+                };
+                for e in positives {
+                    let others = self.matches_expression(e)?;
+                    let mut intersections: Vec<MatchedItem<'tree>> = vec![];
+                    // indices of intersections by their ranges, so that duplicates are looked up only among ones with the same range
+                    let mut by_range: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+                    for item in &items {
+                        for other in &others {
+                            if let Some(i) = item.intersect(other) {
+                                // the same part of code may be matched with different captures, which are different matches
+                                let same_range = by_range
+                                    .entry((i.area.start_byte(), i.area.end_byte()))
+                                    .or_default();
+                                if !same_range
+                                    .iter()
+                                    .any(|&k| intersections[k].is_equivalent_to(&i))
+                                {
+                                    same_range.push(intersections.len());
+                                    intersections.push(i);
+                                }
+                            }
+                        }
+                    }
+                    items = intersections;
+                }
+
+                // (2) filter them with negative expressions
+                for e in negatives {
+                    items = match e {
+                        PatternExpression::NoneOf(es) => {
+                            let mut others = vec![];
+                            for e in es {
+                                others.extend(self.matches_expression(e)?);
+                            }
+                            items
+                                .into_iter()
+                                .filter(|item| item.find_equal(&others).is_none())
+                                .collect()
+                        }
+                        PatternExpression::Inside(e) => {
+                            let others = self.matches_expression(e)?;
+                            items
+                                .into_iter()
+                                .filter_map(|mut item| {
+                                    let captures = item.find_container(&others)?.captures.clone();
+                                    item.captures.extend(captures);
+                                    Some(item)
+                                })
+                                .collect()
+                        }
+                        PatternExpression::NotInside(e) => {
+                            let others = self.matches_expression(e)?;
+                            items
+                                .into_iter()
+                                .filter(|item| item.find_container(&others).is_none())
+                                .collect()
+                        }
+                        e => return Err(ExpressionError::NotNegative(e.operator()).into()),
+                    };
+                }
+
+                // (3) validate them with constraints
+                let mut result = vec![];
+                for mut item in items {
+                    if let (true, captures) = item.satisfies_all(constraints)? {
+                        item.captures.extend(captures);
+                        result.push(item);
+                    }
+                }
+                Ok(result)
+            }
+            PatternExpression::NoneOf(_)
+            | PatternExpression::Inside(_)
+            | PatternExpression::NotInside(_) => Err(ExpressionError::NegativeOutsideAllOf.into()),
+        }
+    }
+
+    pub fn with_budget(mut self, budget: StepBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn budget(&self) -> &StepBudget {
+        &self.budget
     }
 
-    pub fn traverse(&'view self) -> TreeTreverser<'tree> {
+    pub fn traverse(&self) -> TreeTreverser<'tree> {
         TreeTreverser::new(self.view_root)
     }
+
+    pub fn index(&'view self) -> &'view KindIndex<'tree> {
+        self.index
+            .get_or_init(|| KindIndex::new::<T>(self.view_root))
+    }
 }
 
 impl<'tree, T> From<&'tree NormalizedTree<'tree, T>> for RefTreeView<'tree, T>
@@ -169,6 +301,8 @@ where
         RefTreeView {
             view_root: &t.view_root,
             source: t.source,
+            index: OnceCell::new(),
+            budget: StepBudget::default(),
             _marker: PhantomData,
         }
     }
@@ -183,11 +317,113 @@ where
         RefTreeView {
             view_root: t,
             source,
+            index: OnceCell::new(),
+            budget: StepBudget::default(),
             _marker: PhantomData,
         }
     }
 }
 
+/// `MatchStart` describes a sequence of sibling nodes which a query can start matching at.
+#[derive(Debug, Clone, Copy)]
+pub enum MatchStart<'tree> {
+    /// non-skippable children of the node from the given index
+    Children(&'tree Node<'tree>, usize),
+    /// named children of the node in any order, for a node whose children are unordered
+    AnyChild(&'tree Node<'tree>),
+    /// the root node itself
+    Root(&'tree Node<'tree>),
+}
+
+/// `KindIndex` maps kinds of nodes to their positions in a tree.
+/// Positions of each kind are sorted in the order in which `TreeTreverser` visits their parents.
+#[derive(Debug)]
+pub struct KindIndex<'tree> {
+    root: &'tree Node<'tree>,
+    positions: HashMap<&'static str, Vec<MatchStart<'tree>>>,
+}
+
+impl<'tree> KindIndex<'tree> {
+    pub fn new<T: Queryable>(root: &'tree Node<'tree>) -> Self {
+        let mut index = KindIndex {
+            root,
+            positions: HashMap::new(),
+        };
+        for (depth, node) in TreeTreverser::new(root) {
+            if T::is_unordered(node) {
+                let mut kinds = vec![];
+                for child in node
+                    .children
+                    .iter()
+                    .filter(|n| n.is_named() && !T::is_skippable(n))
+                {
+                    if let NodeType::Normal(kind) = child.kind() {
+                        let kind = T::normalize_kind(kind);
+                        if !kinds.contains(&kind) {
+                            kinds.push(kind);
+                            index
+                                .positions
+                                .entry(kind)
+                                .or_default()
+                                .push(MatchStart::AnyChild(node));
+                        }
+                    }
+                }
+            } else {
+                for (i, child) in node
+                    .children
+                    .iter()
+                    .filter(|n| !T::is_skippable(n))
+                    .enumerate()
+                {
+                    if let NodeType::Normal(kind) = child.kind() {
+                        index
+                            .positions
+                            .entry(T::normalize_kind(kind))
+                            .or_default()
+                            .push(MatchStart::Children(node, i));
+                    }
+                }
+            }
+            if depth == 0 {
+                if let NodeType::Normal(kind) = node.kind() {
+                    index
+                        .positions
+                        .entry(T::normalize_kind(kind))
+                        .or_default()
+                        .push(MatchStart::Root(node));
+                }
+            }
+        }
+        index
+    }
+
+    pub fn root(&self) -> &'tree Node<'tree> {
+        self.root
+    }
+
+    /// `starts` returns positions where a sequence of siblings whose `offset`-th node is of `kind` starts.
+    /// Children of a node whose children are unordered are returned regardless of `offset`.
+    pub fn starts(&self, kind: &str, offset: usize) -> Vec<MatchStart<'tree>> {
+        self.positions
+            .get(kind)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .filter_map(|p| match *p {
+                        MatchStart::Children(parent, i) if i >= offset => {
+                            Some(MatchStart::Children(parent, i - offset))
+                        }
+                        MatchStart::AnyChild(parent) => Some(MatchStart::AnyChild(parent)),
+                        MatchStart::Root(root) if offset == 0 => Some(MatchStart::Root(root)),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
 pub struct TreeTreverser<'a> {
     queue: VecDeque<(usize, &'a Node<'a>)>,
 }
@@ -216,3 +452,76 @@ impl<'a> Iterator for TreeTreverser<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{language::HCL, pattern::Pattern, pattern::PatternWithConstraints};
+
+    #[test]
+    fn test_indexed_matcher() {
+        let tree = Tree::<HCL>::try_from(
+            r#"resource "rtype" "rname1" {
+  attr = "value"
+  nested {
+    attr = 1
+    other = 2
+  }
+}
+locals {
+  attr = 3
+}"#,
+        )
+        .unwrap();
+        let ptree = NormalizedTree::from(&tree);
+        let ptree = ptree.as_ref_treeview();
+
+        for (p, expected) in [
+            ("attr = :[X]", 3),
+            (":[X] = 2", 1),
+            ("nested { ... }", 1),
+            (r#"resource :[...X] { ... }"#, 1),
+            ("locals { ... }", 1),
+        ] {
+            let pattern = Pattern::<HCL>::try_from(p).unwrap();
+            let pc = PatternWithConstraints::new(pattern, vec![]);
+            let query = pc.as_query();
+
+            let areas = |matcher: TreeMatcher<HCL>| {
+                matcher
+                    .map(|item| (item.area.start_byte(), item.area.end_byte()))
+                    .collect::<Vec<(usize, usize)>>()
+            };
+            let indexed = areas(TreeMatcher::with_index(ptree.index(), &query.pattern));
+            let traversed = areas(TreeMatcher::new(ptree.traverse(), &query.pattern));
+            assert_eq!(indexed.len(), expected, "pattern: {}", p);
+            assert_eq!(indexed, traversed, "pattern: {}", p);
+        }
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let tree = Tree::<HCL>::try_from(
+            r#"resource "rtype" "rname" {
+  attr = "value"
+}"#,
+        )
+        .unwrap();
+        assert_eq!(tree.syntax_error(), None);
+
+        let tree = Tree::<HCL>::try_from(
+            r#"resource "rtype" "rname" {
+  attr = = "value"
+}"#,
+        )
+        .unwrap();
+        let e = tree.syntax_error().unwrap();
+        assert_eq!(e.row, 1);
+        assert!(e.snippet.starts_with("  attr = = \"value\"\n"));
+        assert!(e.snippet.ends_with('^'));
+
+        let e = Pattern::<HCL>::try_from("attr = = :[X]").unwrap_err();
+        assert!(e.to_string().starts_with("syntax error at line 1"));
+        assert!(Pattern::<HCL>::try_from("attr = :[X]").is_ok());
+    }
+}
@@ -7,8 +7,11 @@ fn main() {
 
     let exit_code = match opts.sub_command {
         cli::SubCommand::Completion(opts) => cli::subcommand::completion::run(opts),
-        cli::SubCommand::Check(opts) => cli::subcommand::check::run(opts),
+        cli::SubCommand::Check(opts) => cli::subcommand::check::run(opts.into()),
         cli::SubCommand::Find(opts) => cli::subcommand::find::run(opts),
+        cli::SubCommand::Fix(opts) => cli::subcommand::fix::run(opts),
+        cli::SubCommand::Test(opts) => cli::subcommand::test::run(opts),
+        cli::SubCommand::Validate(opts) => cli::subcommand::validate::run(opts),
     };
 
     std::process::exit(exit_code)